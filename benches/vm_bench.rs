@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use pras::{
-    compiler::{code::Bytecode, Compiler},
+    compiler::{code::Bytecode, peephole::peephole, Compiler},
     lexer, parser,
     vm::Vm,
 };
@@ -20,9 +20,49 @@ pub static INPUT: &str = "
     fib(10)
     #dekhao(fib(22),1,2,3,4)";
 
+/// A loop that never ends, stopped by the fuel limit
+pub static LOOP_INPUT: &str = "
+    dhori f = ekti kaj(a)
+        jotokhon sotti:
+            dhori b = a + 1
+            dhori c = b * 2
+            jodi (c == b) tahole dekhao(c) nahole c sesh
+        sesh
+    sesh
+    f(1)";
+
+/// Calls that return, each to one waiting on the stack
+pub static CALLS_INPUT: &str = "
+    dhori count = ekti kaj(n)
+        jodi (n == 0) tahole ferao(0) nahole ferao(1 + count(n - 1)) sesh
+    sesh
+    count(500)";
+
+/// Closures made and called on every step
+pub static CLOSURES_INPUT: &str = "
+    dhori adder = ekti kaj(x) ekti kaj(y) x + y sesh sesh
+    dhori apply = ekti kaj(n, acc)
+        jodi (n == 0) tahole ferao(acc) nahole ferao(apply(n - 1, adder(n)(acc))) sesh
+    sesh
+    apply(1000, 0)";
+
+const LOOP_FUEL: u64 = 100_000;
+
+fn compile(input: &str) -> Bytecode {
+    let mut p = parser::Parser::new(lexer::Lexer::new(input));
+    let prog = p.parse_program().expect("parser error on benchmark");
+    peephole(Compiler::new().compile(prog))
+}
+
+fn vm_loop_bench(bc: &Bytecode) {
+    let mut vm = Vm::new(bc.to_owned());
+    vm.set_fuel(Some(LOOP_FUEL));
+    vm.run().expect_err("loop benchmark ran out");
+}
+
 fn vm_bench(bc: &Bytecode) {
     let mut vm = Vm::new(bc.to_owned());
-    vm.run();
+    vm.run().expect("runtime error on benchmark");
     _ = vm.last_pop()
 
     //while !l.is_at_eof() {
@@ -39,6 +79,20 @@ fn criterion_benchmark(c: &mut Criterion) {
     let mut com = Compiler::new();
     let bc = com.compile(prog);
     c.bench_function("vm_fib_10", |b| b.iter(|| vm_bench(black_box(&bc))));
+
+    let bc = peephole(bc);
+    c.bench_function("vm_fib_10_peephole", |b| {
+        b.iter(|| vm_bench(black_box(&bc)))
+    });
+
+    let mut g = c.benchmark_group("dispatch");
+    let bc = compile(LOOP_INPUT);
+    g.bench_function("loop", |b| b.iter(|| vm_loop_bench(black_box(&bc))));
+    let bc = compile(CALLS_INPUT);
+    g.bench_function("calls", |b| b.iter(|| vm_bench(black_box(&bc))));
+    let bc = compile(CLOSURES_INPUT);
+    g.bench_function("closures", |b| b.iter(|| vm_bench(black_box(&bc))));
+    g.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,19 +1,49 @@
+pub mod visit;
+
 use std::{fmt::Display, rc::Rc};
 
 use crate::{
     errorhelper::ParserError,
-    token::{NumberToken, Token},
+    token::{NumberToken, Span, Token},
 };
+pub use visit::{Fold, MutVisitor, Visitor};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Node {
-    Program(Program),
-    Stmt(Stmt),
-    Expr(Rc<Expr>),
-    Identifier(Identifier),
+/// A node a pass can be run on; `node.accept(&mut v)` calls the visitor
+/// method for the node's type
+pub trait AstNode {
+    fn accept<V: Visitor>(&self, v: &mut V);
+    fn accept_mut<V: MutVisitor>(&mut self, v: &mut V);
+}
+
+impl AstNode for Program {
+    fn accept<V: Visitor>(&self, v: &mut V) {
+        v.visit_program(self)
+    }
+
+    fn accept_mut<V: MutVisitor>(&mut self, v: &mut V) {
+        v.visit_program_mut(self)
+    }
 }
 
-pub trait AstNode {}
+impl AstNode for Stmt {
+    fn accept<V: Visitor>(&self, v: &mut V) {
+        v.visit_stmt(self)
+    }
+
+    fn accept_mut<V: MutVisitor>(&mut self, v: &mut V) {
+        v.visit_stmt_mut(self)
+    }
+}
+
+impl AstNode for Expr {
+    fn accept<V: Visitor>(&self, v: &mut V) {
+        v.visit_expr(self)
+    }
+
+    fn accept_mut<V: MutVisitor>(&mut self, v: &mut V) {
+        v.visit_expr_mut(self)
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Program {
@@ -43,37 +73,52 @@ impl Display for Identifier {
     }
 }
 
+/// An expression. `token` is its first (or, for operators, its operator)
+/// token; `span` covers all of its source, from the first token to the last.
 #[derive(Debug, Clone, PartialEq, PartialOrd, Ord)]
 pub enum Expr {
     NumExpr {
         token: Rc<Token>,
+        span: Span,
         value: NumberToken,
         is_int: bool,
     },
     IdentExpr {
         token: Rc<Token>,
+        span: Span,
         value: String,
     },
     BoolExpr {
         token: Rc<Token>,
+        span: Span,
         value: bool,
     },
     StringExpr {
         token: Rc<Token>,
+        span: Span,
         value: String,
     },
+    /// `"text {expr} text"`; literal parts are `StringExpr`s, empty ones are left out
+    InterpExpr {
+        token: Rc<Token>,
+        span: Span,
+        parts: Vec<Rc<Expr>>,
+    },
     Break {
         token: Rc<Token>,
+        span: Span,
         value: String,
     },
     PrefixExpr {
         token: Rc<Token>,
+        span: Span,
         op: Rc<Token>,
         right: Rc<Expr>,
     },
 
     InfixExpr {
         token: Rc<Token>,
+        span: Span,
         left: Rc<Expr>,
         op: Rc<Token>,
         right: Rc<Expr>,
@@ -81,26 +126,31 @@ pub enum Expr {
 
     ArrayExpr {
         token: Rc<Token>,
+        span: Span,
         elems: Vec<Rc<Expr>>,
     },
     IndexExpr {
         token: Rc<Token>,
+        span: Span,
         left: Rc<Expr>,
         index: Rc<Expr>,
     },
 
     IncludeExpr {
         token: Rc<Token>,
+        span: Span,
         filename: Rc<Expr>,
     },
     IfExpr {
         token: Rc<Token>,
+        span: Span,
         cond: Rc<Expr>,
         trueblock: Rc<Stmt>,
         elseblock: Option<Rc<Stmt>>,
     },
     WhileExpr {
         token: Rc<Token>,
+        span: Span,
         cond: Rc<Expr>,
         stmts: Rc<Stmt>, //Block Stmt
     },
@@ -109,11 +159,13 @@ pub enum Expr {
 
     CallExpr {
         token: Rc<Token>,
+        span: Span,
         func: Rc<Expr>,
         args: Vec<Rc<Expr>>,
     },
     HashExpr {
         token: Rc<Token>,
+        span: Span,
         pairs: Vec<(Rc<Expr>, Rc<Expr>)>,
     },
     NullExpr,
@@ -123,9 +175,12 @@ pub enum Expr {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct FuncExpr {
     pub token: Rc<Token>,
+    pub span: Span,
     pub name: String,
     pub params: Rc<Vec<Identifier>>,
     pub body: Rc<Stmt>,
+    /// Text of the `##` doc comment written before the function
+    pub doc: Option<String>,
 }
 
 impl Expr {
@@ -139,37 +194,129 @@ impl Expr {
     pub const fn is_error(&self) -> bool {
         matches!(self, Self::ErrExpr(..))
     }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Self::NumExpr { span, .. }
+            | Self::IdentExpr { span, .. }
+            | Self::BoolExpr { span, .. }
+            | Self::StringExpr { span, .. }
+            | Self::InterpExpr { span, .. }
+            | Self::Break { span, .. }
+            | Self::PrefixExpr { span, .. }
+            | Self::InfixExpr { span, .. }
+            | Self::ArrayExpr { span, .. }
+            | Self::IndexExpr { span, .. }
+            | Self::IncludeExpr { span, .. }
+            | Self::IfExpr { span, .. }
+            | Self::WhileExpr { span, .. }
+            | Self::FuncExpr(FuncExpr { span, .. })
+            | Self::CallExpr { span, .. }
+            | Self::HashExpr { span, .. } => *span,
+            Self::ErrExpr(e) => e.token.as_ref().map_or(Span::EMPTY, |t| t.span),
+            Self::NullExpr => Span::EMPTY,
+        }
+    }
+
+    /// Span of the expression, for the parser to widen it (over
+    /// parentheses, say)
+    pub(crate) fn span_mut(&mut self) -> Option<&mut Span> {
+        match self {
+            Self::NumExpr { span, .. }
+            | Self::IdentExpr { span, .. }
+            | Self::BoolExpr { span, .. }
+            | Self::StringExpr { span, .. }
+            | Self::InterpExpr { span, .. }
+            | Self::Break { span, .. }
+            | Self::PrefixExpr { span, .. }
+            | Self::InfixExpr { span, .. }
+            | Self::ArrayExpr { span, .. }
+            | Self::IndexExpr { span, .. }
+            | Self::IncludeExpr { span, .. }
+            | Self::IfExpr { span, .. }
+            | Self::WhileExpr { span, .. }
+            | Self::FuncExpr(FuncExpr { span, .. })
+            | Self::CallExpr { span, .. }
+            | Self::HashExpr { span, .. } => Some(span),
+            Self::ErrExpr(_) | Self::NullExpr => None,
+        }
+    }
+}
+
+impl Stmt {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::LetStmt { span, .. }
+            | Self::ReturnStmt { span, .. }
+            | Self::ShowStmt { span, .. }
+            | Self::BlockStmt { span, .. }
+            | Self::ExprStmt { span, .. } => *span,
+        }
+    }
 }
 
-impl AstNode for Expr {}
 impl Eq for Expr {}
 impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let result: String = match self {
             Self::NumExpr {
                 token: _,
+                span: _,
                 value,
                 is_int: _,
             } => match value {
                 NumberToken::Int(i) => format!("({i})"),
                 NumberToken::Float(f) => format!("({f})"),
             },
-            Self::IdentExpr { token: _, value } => format!("ident({value})"),
-            Self::BoolExpr { token: _, value } => format!("bool({value})"),
-            Self::StringExpr { token: _, value } => format!("str({value})"),
-            Self::Break { token: _, value: _ } => "break()".to_string(),
+            Self::IdentExpr {
+                token: _,
+                span: _,
+                value,
+            } => format!("ident({value})"),
+            Self::BoolExpr {
+                token: _,
+                span: _,
+                value,
+            } => format!("bool({value})"),
+            Self::StringExpr {
+                token: _,
+                span: _,
+                value,
+            } => format!("str({value})"),
+            Self::InterpExpr {
+                token: _,
+                span: _,
+                parts,
+            } => {
+                let mut ps = String::new();
+                for p in parts {
+                    ps.push_str(format!("{p},").as_str());
+                }
+                format!("interp({ps})")
+            }
+            Self::Break {
+                token: _,
+                span: _,
+                value: _,
+            } => "break()".to_string(),
             Self::PrefixExpr {
                 token: _,
+                span: _,
                 op,
                 right,
             } => format!("pre({}{})", op.literal, right),
             Self::InfixExpr {
                 token: _,
+                span: _,
                 left,
                 op,
                 right,
             } => format!("inf({}{}{})", left, op.literal, right),
-            Self::ArrayExpr { token: _, elems } => {
+            Self::ArrayExpr {
+                token: _,
+                span: _,
+                elems,
+            } => {
                 let mut arrs: String = String::new();
                 for e in elems {
                     arrs.push_str(format!("{e}").as_str());
@@ -178,6 +325,7 @@ impl Display for Expr {
             }
             Self::IndexExpr {
                 token: _,
+                span: _,
                 left,
                 index,
             } => {
@@ -185,6 +333,7 @@ impl Display for Expr {
             }
             Self::IfExpr {
                 token: _,
+                span: _,
                 cond,
                 trueblock,
                 elseblock,
@@ -195,6 +344,7 @@ impl Display for Expr {
 
             Self::WhileExpr {
                 token: _,
+                span: _,
                 cond,
                 stmts,
             } => {
@@ -210,12 +360,17 @@ impl Display for Expr {
 
                 format!("func({ps}:{})", f.body)
             }
-            Self::IncludeExpr { token: _, filename } => {
+            Self::IncludeExpr {
+                token: _,
+                span: _,
+                filename,
+            } => {
                 format!("inc({filename})")
             }
 
             Self::CallExpr {
                 token: _,
+                span: _,
                 func,
                 args,
             } => {
@@ -227,7 +382,11 @@ impl Display for Expr {
                 format!("call({func}:{ar})")
             }
 
-            Self::HashExpr { token: _, pairs } => {
+            Self::HashExpr {
+                token: _,
+                span: _,
+                pairs,
+            } => {
                 let mut hp = String::new();
 
                 for (a, b) in pairs {
@@ -245,36 +404,43 @@ impl Display for Expr {
     }
 }
 
+/// A statement; `span` covers all of its source, the trailing `;` included
 #[derive(Debug, Clone, PartialEq, PartialOrd, Ord)]
 pub enum Stmt {
     LetStmt {
         token: Rc<Token>,
+        span: Span,
         name: Identifier,
         value: Rc<Expr>,
+        /// Text of the `##` doc comment written before the binding
+        doc: Option<String>,
     },
 
     ReturnStmt {
         token: Rc<Token>,
+        span: Span,
         rval: Rc<Expr>,
     },
 
     ShowStmt {
         token: Rc<Token>,
+        span: Span,
         value: Vec<Rc<Expr>>,
     },
 
     BlockStmt {
         token: Rc<Token>,
+        span: Span,
         stmts: Vec<Rc<Stmt>>,
     },
 
     ExprStmt {
         token: Rc<Token>,
+        span: Span,
         expr: Rc<Expr>,
     },
 }
 
-impl AstNode for Stmt {}
 impl Eq for Stmt {}
 
 impl Display for Stmt {
@@ -282,16 +448,26 @@ impl Display for Stmt {
         let result: String = match self {
             Self::LetStmt {
                 token: _,
+                span: _,
                 name,
                 value,
+                doc: _,
             } => {
                 format!("let<{name}:{value}>")
             }
-            Self::ReturnStmt { token: _, rval } => {
+            Self::ReturnStmt {
+                token: _,
+                span: _,
+                rval,
+            } => {
                 format!("ret<{rval}>")
             }
 
-            Self::ShowStmt { token: _, value } => {
+            Self::ShowStmt {
+                token: _,
+                span: _,
+                value,
+            } => {
                 let mut res = String::new();
                 for v in value {
                     res.push_str(format!("{v},").as_str())
@@ -299,7 +475,11 @@ impl Display for Stmt {
                 format!("show<{res}>")
             }
 
-            Self::BlockStmt { token: _, stmts } => {
+            Self::BlockStmt {
+                token: _,
+                span: _,
+                stmts,
+            } => {
                 let mut res = String::new();
 
                 for s in stmts {
@@ -309,7 +489,11 @@ impl Display for Stmt {
                 format!("blk<{res}>")
             }
 
-            Self::ExprStmt { token: _, expr } => {
+            Self::ExprStmt {
+                token: _,
+                span: _,
+                expr,
+            } => {
                 format!("{expr}")
             }
         };
@@ -0,0 +1,451 @@
+//! Traversal of the AST.
+//!
+//! Three traits cover the usual kinds of pass:
+//!
+//! * [`Visitor`] looks at a tree through shared references (linters,
+//!   analysers).
+//! * [`MutVisitor`] edits a tree in place. Children are behind `Rc`s, so a
+//!   child that is shared with another tree is cloned before it is changed.
+//! * [`Fold`] takes a tree by value and builds a new one, possibly with
+//!   nodes of a different kind (optimisers).
+//!
+//! Every method has a default that walks into the node's children through
+//! the matching `walk_*` / `fold_*` function, so a pass only overrides the
+//! nodes it cares about. An override that still wants the children visited
+//! calls the walk function itself.
+
+use std::rc::Rc;
+
+use super::{Expr, FuncExpr, Identifier, Program, Stmt};
+
+pub trait Visitor: Sized {
+    fn visit_program(&mut self, prog: &Program) {
+        walk_program(self, prog)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    /// A name being bound: the name of a `let` or a parameter
+    fn visit_ident(&mut self, _ident: &Identifier) {}
+
+    fn visit_func(&mut self, func: &FuncExpr) {
+        walk_func(self, func)
+    }
+}
+
+pub fn walk_program<V: Visitor>(v: &mut V, prog: &Program) {
+    for s in &prog.stmts {
+        v.visit_stmt(s);
+    }
+}
+
+pub fn walk_stmt<V: Visitor>(v: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::LetStmt { name, value, .. } => {
+            v.visit_ident(name);
+            v.visit_expr(value);
+        }
+        Stmt::ReturnStmt { rval, .. } => v.visit_expr(rval),
+        Stmt::ShowStmt { value, .. } => {
+            for e in value {
+                v.visit_expr(e);
+            }
+        }
+        Stmt::BlockStmt { stmts, .. } => {
+            for s in stmts {
+                v.visit_stmt(s);
+            }
+        }
+        Stmt::ExprStmt { expr, .. } => v.visit_expr(expr),
+    }
+}
+
+pub fn walk_expr<V: Visitor>(v: &mut V, expr: &Expr) {
+    match expr {
+        Expr::InterpExpr { parts, .. } => {
+            for p in parts {
+                v.visit_expr(p);
+            }
+        }
+        Expr::PrefixExpr { right, .. } => v.visit_expr(right),
+        Expr::InfixExpr { left, right, .. } => {
+            v.visit_expr(left);
+            v.visit_expr(right);
+        }
+        Expr::ArrayExpr { elems, .. } => {
+            for e in elems {
+                v.visit_expr(e);
+            }
+        }
+        Expr::IndexExpr { left, index, .. } => {
+            v.visit_expr(left);
+            v.visit_expr(index);
+        }
+        Expr::IncludeExpr { filename, .. } => v.visit_expr(filename),
+        Expr::IfExpr {
+            cond,
+            trueblock,
+            elseblock,
+            ..
+        } => {
+            v.visit_expr(cond);
+            v.visit_stmt(trueblock);
+            if let Some(eb) = elseblock {
+                v.visit_stmt(eb);
+            }
+        }
+        Expr::WhileExpr { cond, stmts, .. } => {
+            v.visit_expr(cond);
+            v.visit_stmt(stmts);
+        }
+        Expr::FuncExpr(f) => v.visit_func(f),
+        Expr::CallExpr { func, args, .. } => {
+            v.visit_expr(func);
+            for a in args {
+                v.visit_expr(a);
+            }
+        }
+        Expr::HashExpr { pairs, .. } => {
+            for (k, val) in pairs {
+                v.visit_expr(k);
+                v.visit_expr(val);
+            }
+        }
+        Expr::NumExpr { .. }
+        | Expr::IdentExpr { .. }
+        | Expr::BoolExpr { .. }
+        | Expr::StringExpr { .. }
+        | Expr::Break { .. }
+        | Expr::NullExpr
+        | Expr::ErrExpr(_) => {}
+    }
+}
+
+pub fn walk_func<V: Visitor>(v: &mut V, func: &FuncExpr) {
+    for p in func.params.iter() {
+        v.visit_ident(p);
+    }
+    v.visit_stmt(&func.body);
+}
+
+pub trait MutVisitor: Sized {
+    fn visit_program_mut(&mut self, prog: &mut Program) {
+        walk_program_mut(self, prog)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Identifier) {}
+
+    fn visit_func_mut(&mut self, func: &mut FuncExpr) {
+        walk_func_mut(self, func)
+    }
+}
+
+pub fn walk_program_mut<V: MutVisitor>(v: &mut V, prog: &mut Program) {
+    for s in &mut prog.stmts {
+        v.visit_stmt_mut(Rc::make_mut(s));
+    }
+}
+
+pub fn walk_stmt_mut<V: MutVisitor>(v: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::LetStmt { name, value, .. } => {
+            v.visit_ident_mut(name);
+            v.visit_expr_mut(Rc::make_mut(value));
+        }
+        Stmt::ReturnStmt { rval, .. } => v.visit_expr_mut(Rc::make_mut(rval)),
+        Stmt::ShowStmt { value, .. } => {
+            for e in value {
+                v.visit_expr_mut(Rc::make_mut(e));
+            }
+        }
+        Stmt::BlockStmt { stmts, .. } => {
+            for s in stmts {
+                v.visit_stmt_mut(Rc::make_mut(s));
+            }
+        }
+        Stmt::ExprStmt { expr, .. } => v.visit_expr_mut(Rc::make_mut(expr)),
+    }
+}
+
+pub fn walk_expr_mut<V: MutVisitor>(v: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::InterpExpr { parts, .. } => {
+            for p in parts {
+                v.visit_expr_mut(Rc::make_mut(p));
+            }
+        }
+        Expr::PrefixExpr { right, .. } => v.visit_expr_mut(Rc::make_mut(right)),
+        Expr::InfixExpr { left, right, .. } => {
+            v.visit_expr_mut(Rc::make_mut(left));
+            v.visit_expr_mut(Rc::make_mut(right));
+        }
+        Expr::ArrayExpr { elems, .. } => {
+            for e in elems {
+                v.visit_expr_mut(Rc::make_mut(e));
+            }
+        }
+        Expr::IndexExpr { left, index, .. } => {
+            v.visit_expr_mut(Rc::make_mut(left));
+            v.visit_expr_mut(Rc::make_mut(index));
+        }
+        Expr::IncludeExpr { filename, .. } => v.visit_expr_mut(Rc::make_mut(filename)),
+        Expr::IfExpr {
+            cond,
+            trueblock,
+            elseblock,
+            ..
+        } => {
+            v.visit_expr_mut(Rc::make_mut(cond));
+            v.visit_stmt_mut(Rc::make_mut(trueblock));
+            if let Some(eb) = elseblock {
+                v.visit_stmt_mut(Rc::make_mut(eb));
+            }
+        }
+        Expr::WhileExpr { cond, stmts, .. } => {
+            v.visit_expr_mut(Rc::make_mut(cond));
+            v.visit_stmt_mut(Rc::make_mut(stmts));
+        }
+        Expr::FuncExpr(f) => v.visit_func_mut(f),
+        Expr::CallExpr { func, args, .. } => {
+            v.visit_expr_mut(Rc::make_mut(func));
+            for a in args {
+                v.visit_expr_mut(Rc::make_mut(a));
+            }
+        }
+        Expr::HashExpr { pairs, .. } => {
+            for (k, val) in pairs {
+                v.visit_expr_mut(Rc::make_mut(k));
+                v.visit_expr_mut(Rc::make_mut(val));
+            }
+        }
+        Expr::NumExpr { .. }
+        | Expr::IdentExpr { .. }
+        | Expr::BoolExpr { .. }
+        | Expr::StringExpr { .. }
+        | Expr::Break { .. }
+        | Expr::NullExpr
+        | Expr::ErrExpr(_) => {}
+    }
+}
+
+pub fn walk_func_mut<V: MutVisitor>(v: &mut V, func: &mut FuncExpr) {
+    for p in Rc::make_mut(&mut func.params) {
+        v.visit_ident_mut(p);
+    }
+    v.visit_stmt_mut(Rc::make_mut(&mut func.body));
+}
+
+pub trait Fold: Sized {
+    fn fold_program(&mut self, prog: Program) -> Program {
+        fold_program(self, prog)
+    }
+
+    fn fold_stmt(&mut self, stmt: Rc<Stmt>) -> Rc<Stmt> {
+        fold_stmt(self, stmt)
+    }
+
+    fn fold_expr(&mut self, expr: Rc<Expr>) -> Rc<Expr> {
+        fold_expr(self, expr)
+    }
+
+    fn fold_ident(&mut self, ident: Identifier) -> Identifier {
+        ident
+    }
+
+    fn fold_func(&mut self, func: FuncExpr) -> FuncExpr {
+        fold_func(self, func)
+    }
+}
+
+fn fold_all<F: Fold>(f: &mut F, exprs: Vec<Rc<Expr>>) -> Vec<Rc<Expr>> {
+    exprs.into_iter().map(|e| f.fold_expr(e)).collect()
+}
+
+pub fn fold_program<F: Fold>(f: &mut F, prog: Program) -> Program {
+    Program {
+        stmts: prog.stmts.into_iter().map(|s| f.fold_stmt(s)).collect(),
+    }
+}
+
+pub fn fold_stmt<F: Fold>(f: &mut F, stmt: Rc<Stmt>) -> Rc<Stmt> {
+    let stmt = match Rc::unwrap_or_clone(stmt) {
+        Stmt::LetStmt {
+            token,
+            span,
+            name,
+            value,
+            doc,
+        } => Stmt::LetStmt {
+            token,
+            span,
+            name: f.fold_ident(name),
+            value: f.fold_expr(value),
+            doc,
+        },
+        Stmt::ReturnStmt { token, span, rval } => Stmt::ReturnStmt {
+            token,
+            span,
+            rval: f.fold_expr(rval),
+        },
+        Stmt::ShowStmt { token, span, value } => Stmt::ShowStmt {
+            token,
+            span,
+            value: fold_all(f, value),
+        },
+        Stmt::BlockStmt { token, span, stmts } => Stmt::BlockStmt {
+            token,
+            span,
+            stmts: stmts.into_iter().map(|s| f.fold_stmt(s)).collect(),
+        },
+        Stmt::ExprStmt { token, span, expr } => Stmt::ExprStmt {
+            token,
+            span,
+            expr: f.fold_expr(expr),
+        },
+    };
+    Rc::new(stmt)
+}
+
+pub fn fold_expr<F: Fold>(f: &mut F, expr: Rc<Expr>) -> Rc<Expr> {
+    if matches!(
+        *expr,
+        Expr::NumExpr { .. }
+            | Expr::IdentExpr { .. }
+            | Expr::BoolExpr { .. }
+            | Expr::StringExpr { .. }
+            | Expr::Break { .. }
+            | Expr::NullExpr
+            | Expr::ErrExpr(_)
+    ) {
+        return expr;
+    }
+
+    let expr = match Rc::unwrap_or_clone(expr) {
+        Expr::InterpExpr { token, span, parts } => Expr::InterpExpr {
+            token,
+            span,
+            parts: fold_all(f, parts),
+        },
+        Expr::PrefixExpr {
+            token,
+            span,
+            op,
+            right,
+        } => Expr::PrefixExpr {
+            token,
+            span,
+            op,
+            right: f.fold_expr(right),
+        },
+        Expr::InfixExpr {
+            token,
+            span,
+            left,
+            op,
+            right,
+        } => Expr::InfixExpr {
+            token,
+            span,
+            left: f.fold_expr(left),
+            op,
+            right: f.fold_expr(right),
+        },
+        Expr::ArrayExpr { token, span, elems } => Expr::ArrayExpr {
+            token,
+            span,
+            elems: fold_all(f, elems),
+        },
+        Expr::IndexExpr {
+            token,
+            span,
+            left,
+            index,
+        } => Expr::IndexExpr {
+            token,
+            span,
+            left: f.fold_expr(left),
+            index: f.fold_expr(index),
+        },
+        Expr::IncludeExpr {
+            token,
+            span,
+            filename,
+        } => Expr::IncludeExpr {
+            token,
+            span,
+            filename: f.fold_expr(filename),
+        },
+        Expr::IfExpr {
+            token,
+            span,
+            cond,
+            trueblock,
+            elseblock,
+        } => Expr::IfExpr {
+            token,
+            span,
+            cond: f.fold_expr(cond),
+            trueblock: f.fold_stmt(trueblock),
+            elseblock: elseblock.map(|eb| f.fold_stmt(eb)),
+        },
+        Expr::WhileExpr {
+            token,
+            span,
+            cond,
+            stmts,
+        } => Expr::WhileExpr {
+            token,
+            span,
+            cond: f.fold_expr(cond),
+            stmts: f.fold_stmt(stmts),
+        },
+        Expr::FuncExpr(func) => Expr::FuncExpr(f.fold_func(func)),
+        Expr::CallExpr {
+            token,
+            span,
+            func,
+            args,
+        } => Expr::CallExpr {
+            token,
+            span,
+            func: f.fold_expr(func),
+            args: fold_all(f, args),
+        },
+        Expr::HashExpr { token, span, pairs } => Expr::HashExpr {
+            token,
+            span,
+            pairs: pairs
+                .into_iter()
+                .map(|(k, v)| (f.fold_expr(k), f.fold_expr(v)))
+                .collect(),
+        },
+        leaf => leaf,
+    };
+    Rc::new(expr)
+}
+
+pub fn fold_func<F: Fold>(f: &mut F, func: FuncExpr) -> FuncExpr {
+    let params = Rc::unwrap_or_clone(func.params)
+        .into_iter()
+        .map(|p| f.fold_ident(p))
+        .collect();
+    FuncExpr {
+        params: Rc::new(params),
+        body: f.fold_stmt(func.body),
+        ..func
+    }
+}
@@ -1,9 +1,21 @@
+use crate::token::NumberToken;
+
 const BN_NUM_ZERO: char = '\u{09e6}';
 const BN_NUM_NINE: char = '\u{09ef}';
 //const BN_ALPHA_START : char = '\u{0985}';
 const BN_RANGE_START: char = '\u{0980}';
 const BN_RANGE_END: char = '\u{09fe}';
 
+/// Which digits numbers are rendered with when they are shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumeralStyle {
+    /// `0-9`
+    #[default]
+    Ascii,
+    /// `০-৯`
+    Bengali,
+}
+
 pub const fn is_bn_num(c: char) -> bool {
     c >= BN_NUM_ZERO && c <= BN_NUM_NINE
 }
@@ -31,5 +43,54 @@ pub fn parse_bn_num(c: &str) -> String {
         }
     }
 
-    return String::from_iter(result.iter());
+    String::from_iter(result.iter())
+}
+
+/// Reverse of `parse_bn_num`; ASCII digits are replaced with Bengali digits,
+/// everything else (sign, decimal point) is kept as is
+pub fn to_bn_num(c: &str) -> String {
+    c.chars()
+        .map(|item| match item.to_digit(10) {
+            Some(d) => char::from_u32(BN_NUM_ZERO as u32 + d).unwrap_or(item),
+            None => item,
+        })
+        .collect()
+}
+
+pub fn number_to_bn(n: &NumberToken) -> String {
+    to_bn_num(&n.to_string())
+}
+
+pub fn render_number(n: &NumberToken, style: NumeralStyle) -> String {
+    match style {
+        NumeralStyle::Ascii => n.to_string(),
+        NumeralStyle::Bengali => number_to_bn(n),
+    }
+}
+
+/// Guess if a program is written in Bengali script;
+/// Letters inside string literals and comments are not counted
+pub fn is_bn_source(src: &str) -> bool {
+    let mut bn_count = 0;
+    let mut ascii_count = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+
+    for c in src.chars() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if in_string {
+            in_string = c != '"';
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '#' {
+            in_comment = true;
+        } else if c.is_ascii_alphabetic() {
+            ascii_count += 1;
+        } else if is_bn_char(c) {
+            bn_count += 1;
+        }
+    }
+
+    bn_count > ascii_count
 }
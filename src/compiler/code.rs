@@ -1,7 +1,8 @@
 use std::fmt::Display;
 use std::rc::Rc;
 
-use crate::obj::Object;
+use super::disasm::disassemble;
+use crate::{obj::Object, token::Span};
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -38,6 +39,19 @@ pub enum Opcode {
     Dummy,
     Mod,
     Show,
+    GetBuiltin,
+    ToString,
+    Concat,
+    TailCall,
+    DeferFree,
+    FillFrees,
+    // superinstructions, made from the ones above by `peephole`
+    ConstZero,
+    ConstOne,
+    IncLocal,
+    JumpNotGT,
+    JumpNotEqual,
+    JumpEqual,
 }
 
 #[allow(dead_code)]
@@ -50,17 +64,54 @@ pub struct OpDef {
 pub struct Bytecode {
     pub instructions: Rc<Instructions>,
     pub constants: Vec<Rc<Object>>,
+    /// Source of `instructions`; functions carry their own
+    pub lines: Rc<LineTable>,
+}
+
+/// Where compiled code came from. Each entry maps an instruction offset to
+/// the span of the innermost statement or expression it was compiled
+/// from; the entry covers the instructions up to the next entry's offset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineTable {
+    entries: Vec<(usize, Span)>,
+}
+
+impl LineTable {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Record that the code from `pos` on comes from `span`
+    pub fn add(&mut self, pos: usize, span: Span) {
+        match self.entries.last_mut() {
+            Some((p, s)) if *p == pos => *s = span,
+            Some((_, s)) if *s == span => {}
+            _ => self.entries.push((pos, span)),
+        }
+    }
+
+    /// Forget the code from `pos` on
+    pub fn truncate(&mut self, pos: usize) {
+        let keep = self.entries.partition_point(|(p, _)| *p < pos);
+        self.entries.truncate(keep);
+    }
+
+    /// Span of the code at instruction offset `pos`
+    pub fn lookup(&self, pos: usize) -> Option<Span> {
+        let i = self.entries.partition_point(|(p, _)| *p <= pos);
+        i.checked_sub(1).map(|i| self.entries[i].1)
+    }
+
+    pub fn entries(&self) -> &[(usize, Span)] {
+        &self.entries
+    }
 }
 
 impl Display for Bytecode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut result = String::new();
-
-        result.push_str(&format!(
-            "Instructions:\n{}\n\nConstants:{:?}\n",
-            self.instructions, self.constants
-        ));
-        write!(f, "{result}")
+        f.write_str(&disassemble(self, None))
     }
 }
 
@@ -73,7 +124,7 @@ impl OpDef {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Instructions {
     pub ins: Vec<u8>,
 }
@@ -112,6 +163,18 @@ pub fn get_def(op: &Opcode) -> OpDef {
         Opcode::Dummy => OpDef::new("OpDummy", vec![]),
         Opcode::Mod => OpDef::new("OpMod", vec![]),
         Opcode::Show => OpDef::new("OpShow", vec![1]),
+        Opcode::GetBuiltin => OpDef::new("OpGetBuiltin", vec![1]),
+        Opcode::ToString => OpDef::new("OpToString", vec![]),
+        Opcode::Concat => OpDef::new("OpConcat", vec![2]),
+        Opcode::TailCall => OpDef::new("OpTailCall", vec![1]),
+        Opcode::DeferFree => OpDef::new("OpDeferFree", vec![1, 1]),
+        Opcode::FillFrees => OpDef::new("OpFillFrees", vec![1]),
+        Opcode::ConstZero => OpDef::new("OpConstZero", vec![]),
+        Opcode::ConstOne => OpDef::new("OpConstOne", vec![]),
+        Opcode::IncLocal => OpDef::new("OpIncLocal", vec![1, 2, 1]),
+        Opcode::JumpNotGT => OpDef::new("OpJumpNotGT", vec![2]),
+        Opcode::JumpNotEqual => OpDef::new("OpJumpNotEqual", vec![2]),
+        Opcode::JumpEqual => OpDef::new("OpJumpEqual", vec![2]),
     }
 }
 
@@ -192,6 +255,40 @@ impl Display for Instructions {
     }
 }
 
+impl Opcode {
+    /// Whether the instruction jumps; its first operand is the target
+    pub const fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Opcode::Jump
+                | Opcode::JumpNotTruthy
+                | Opcode::JumpNotGT
+                | Opcode::JumpNotEqual
+                | Opcode::JumpEqual
+        )
+    }
+}
+
+/// One instruction of `Instructions`, as `decode` reads it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub pos: usize,
+    pub op: Opcode,
+    pub operands: Vec<usize>,
+}
+
+pub fn decode(ins: &Instructions) -> Vec<Decoded> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < ins.ins.len() {
+        let op = u8_to_op(ins.ins[pos]);
+        let (operands, width) = read_operands(&get_def(&op), ins.ins[pos + 1..].to_vec());
+        out.push(Decoded { pos, op, operands });
+        pos += 1 + width;
+    }
+    out
+}
+
 pub fn u8_to_op(o: u8) -> Opcode {
     unsafe { ::std::mem::transmute(o) }
 }
@@ -205,7 +302,7 @@ impl Instructions {
     pub const fn new() -> Self {
         Self { ins: Vec::new() }
     }
-    pub fn fmt_ins(def: &OpDef, ops: &Vec<usize>) -> String {
+    pub fn fmt_ins(def: &OpDef, ops: &[usize]) -> String {
         if def.op_width.len() != ops.len() {
             return format!(
                 "not enough operands for defination; W=>{} G=>{}",
@@ -217,6 +314,7 @@ impl Instructions {
             0 => def.name.to_string(),
             1 => format!("{} {}", def.name, ops[0]),
             2 => format!("{} {} {}", def.name, ops[0], ops[1]),
+            3 => format!("{} {} {} {}", def.name, ops[0], ops[1], ops[2]),
             _ => "ERR=> unsupported operand width".to_string(),
         }
     }
@@ -0,0 +1,167 @@
+//! Human-readable listing of compiled code, for `pras disasm`.
+//!
+//! The top level comes first, then every function in the order the code
+//! creates them, each under a header with its name, arity and number of
+//! locals. `OpConst` shows the constant it loads, jumps show the label of
+//! their target, and with the source at hand each run of instructions is
+//! preceded by the line it was compiled from:
+//!
+//! ```text
+//! == <main> ==
+//!    1 | dhori a = 1
+//! 0000 OpConst 0                 ; 1
+//! 0003 OpSetGlobal 0
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write, rc::Rc};
+
+use super::code::{decode, get_def, Bytecode, Instructions, LineTable, Opcode};
+use crate::obj::{CompFunc, Object};
+
+/// Width of the instruction column, before the `;` comments
+const INS_WIDTH: usize = 30;
+
+pub struct Disassembler<'a> {
+    bytecode: &'a Bytecode,
+    source: Option<Vec<&'a str>>,
+    out: String,
+    /// Constant indices of the functions listed or queued to be
+    listed: Vec<usize>,
+}
+
+impl<'a> Disassembler<'a> {
+    /// `source` is the program `bytecode` was compiled from, to interleave
+    /// its lines with the instructions; without it only the code is listed
+    pub fn new(bytecode: &'a Bytecode, source: Option<&'a str>) -> Self {
+        Self {
+            bytecode,
+            source: source.map(|s| s.lines().collect()),
+            out: String::new(),
+            listed: Vec::new(),
+        }
+    }
+
+    pub fn disassemble(mut self) -> String {
+        let bc = self.bytecode;
+        self.out.push_str("== <main> ==\n");
+        self.listing(&bc.instructions, &bc.lines);
+
+        let mut next = 0;
+        loop {
+            // functions no closure refers to (there should be none) come last
+            if next == self.listed.len() {
+                match (0..self.bytecode.constants.len())
+                    .find(|i| self.function(*i).is_some() && !self.listed.contains(i))
+                {
+                    Some(i) => self.listed.push(i),
+                    None => break,
+                }
+            }
+            let index = self.listed[next];
+            next += 1;
+            let Some(f) = self.function(index) else {
+                continue;
+            };
+            let _ = writeln!(
+                self.out,
+                "\n== fn {} (constant {index}, params {}, locals {}) ==",
+                self.fn_name(index),
+                f.num_params,
+                f.num_locals
+            );
+            self.listing(&f.fnin, &f.lines);
+        }
+        self.out
+    }
+
+    fn function(&self, index: usize) -> Option<Rc<CompFunc>> {
+        match self.bytecode.constants.get(index).map(AsRef::as_ref) {
+            Some(Object::Compfunc(f)) => Some(f.clone()),
+            _ => None,
+        }
+    }
+
+    fn fn_name(&self, index: usize) -> String {
+        match self.function(index) {
+            Some(f) if !f.name.is_empty() => f.name.clone(),
+            _ => "<anonymous>".to_string(),
+        }
+    }
+
+    fn describe_const(&self, index: usize) -> String {
+        match self.bytecode.constants.get(index).map(AsRef::as_ref) {
+            Some(Object::String { value, .. }) => format!("{value:?}"),
+            Some(Object::Compfunc(_)) => format!("<fn {}>", self.fn_name(index)),
+            Some(obj) => obj.to_string(),
+            None => "<missing constant>".to_string(),
+        }
+    }
+
+    fn listing(&mut self, ins: &Instructions, lines: &LineTable) {
+        let code = decode(ins);
+
+        let mut labels = BTreeMap::new();
+        for d in &code {
+            if d.op.is_jump() {
+                labels.insert(d.operands[0], 0);
+            }
+        }
+        for (n, label) in labels.values_mut().enumerate() {
+            *label = n;
+        }
+
+        let mut last_line = 0;
+        for d in code {
+            if let Some(label) = labels.get(&d.pos) {
+                let _ = writeln!(self.out, "L{label}:");
+            }
+            if let Some(span) = lines.lookup(d.pos) {
+                if span.line != last_line {
+                    last_line = span.line;
+                    self.source_line(span.line);
+                }
+            }
+
+            let def = get_def(&d.op);
+            let text = format!("{:04} {}", d.pos, Instructions::fmt_ins(&def, &d.operands));
+            let comment = match d.op {
+                Opcode::Const => Some(self.describe_const(d.operands[0])),
+                Opcode::IncLocal => Some(format!("+ {}", self.describe_const(d.operands[1]))),
+                op if op.is_jump() => labels.get(&d.operands[0]).map(|l| format!("L{l}")),
+                Opcode::Closure => {
+                    let index = d.operands[0];
+                    if self.function(index).is_some() && !self.listed.contains(&index) {
+                        self.listed.push(index);
+                    }
+                    Some(self.describe_const(index))
+                }
+                _ => None,
+            };
+            let _ = match comment {
+                Some(c) => writeln!(self.out, "{text:INS_WIDTH$} ; {c}"),
+                None => writeln!(self.out, "{text}"),
+            };
+        }
+
+        // a jump to the end of the code
+        if let Some(label) = labels.get(&ins.ins.len()) {
+            let _ = writeln!(self.out, "L{label}:");
+        }
+    }
+
+    fn source_line(&mut self, line: usize) {
+        let Some(text) = self
+            .source
+            .as_ref()
+            .and_then(|s| s.get(line.wrapping_sub(1)))
+        else {
+            return;
+        };
+        let _ = writeln!(self.out, "{line:4} | {}", text.trim_end());
+    }
+}
+
+/// `bytecode` listed by a `Disassembler`
+pub fn disassemble(bytecode: &Bytecode, source: Option<&str>) -> String {
+    Disassembler::new(bytecode, source).disassemble()
+}
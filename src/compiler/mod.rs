@@ -1,16 +1,18 @@
 use crate::{
     ast,
-    obj::{CompFunc, Object},
-    token::{Token, TokenType},
+    obj::{builtins::BUILTINS, CompFunc, Object},
+    token::{NumberToken, Span, Token, TokenType},
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use self::{
-    code::{get_def, make_ins, u8_to_op, Bytecode, Instructions, Opcode},
+    code::{get_def, make_ins, u8_to_op, Bytecode, Instructions, LineTable, Opcode},
     symtab::{Symbol, Table},
 };
 
 pub mod code;
+pub mod disasm;
+pub mod peephole;
 pub mod symtab;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,16 +39,58 @@ impl Default for EmittedIns {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CompScope {
     pub ins: code::Instructions,
+    pub lines: LineTable,
     last_ins: EmittedIns,
     prev_ins: EmittedIns,
+    /// Locals whose `dhori` is being compiled, and whether a closure made
+    /// in its value captured them before they were set
+    defining: Vec<(usize, bool)>,
+}
+
+/// What makes two constants the same, see `Compiler::add_const`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConstKey {
+    Int(i64),
+    /// Bit pattern, so `0.0` and `-0.0` stay apart
+    Float(u64),
+    Str(String),
+    Func {
+        name: String,
+        fnin: Rc<Instructions>,
+        num_locals: usize,
+        num_params: usize,
+    },
+}
+
+impl ConstKey {
+    fn of(obj: &Object) -> Option<Self> {
+        match obj {
+            Object::Number { value, .. } => Some(match value {
+                NumberToken::Int(i) => Self::Int(*i),
+                NumberToken::Float(f) => Self::Float(f.to_bits()),
+            }),
+            Object::String { value, .. } => Some(Self::Str(value.clone())),
+            Object::Compfunc(f) => Some(Self::Func {
+                name: f.name.clone(),
+                fnin: f.fnin.clone(),
+                num_locals: f.num_locals,
+                num_params: f.num_params,
+            }),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Compiler {
     pub symtab: Rc<RefCell<symtab::Table>>,
     constants: Vec<Rc<Object>>,
+    /// Index of every constant in `constants`, by value
+    const_index: HashMap<ConstKey, usize>,
     scopes: Vec<CompScope>,
     scope_index: usize,
+    /// Span of the node being compiled, for the line tables
+    span: Span,
 }
 
 impl Default for Compiler {
@@ -59,15 +103,24 @@ impl Compiler {
     pub fn new() -> Self {
         let mainscope = CompScope {
             ins: code::Instructions::new(),
+            lines: LineTable::new(),
             last_ins: EmittedIns::new(),
             prev_ins: EmittedIns::new(),
+            defining: Vec::new(),
         };
 
+        let mut symtab = symtab::Table::new();
+        for (i, b) in BUILTINS.iter().enumerate() {
+            symtab.define_builtin(i, b.name);
+        }
+
         Self {
-            symtab: Rc::new(RefCell::new(symtab::Table::new())),
+            symtab: Rc::new(RefCell::new(symtab)),
             constants: Vec::new(),
+            const_index: HashMap::new(),
             scopes: vec![mainscope],
             scope_index: 0,
+            span: Span::EMPTY,
         }
     }
 
@@ -104,14 +157,21 @@ impl Compiler {
     }
 
     pub fn compile_stmt(&mut self, stmt: &ast::Stmt) {
+        let outer = std::mem::replace(&mut self.span, stmt.span());
         match stmt {
             ast::Stmt::LetStmt {
                 token: _,
+                span: _,
                 name,
                 value,
+                doc: _,
             } => {
                 let sm = self.sym_define(&name.name);
+                let scope = &mut self.scopes[self.scope_index];
+                scope.defining.push((sm.index, false));
                 self.compiler_expr(value);
+                let scope = &mut self.scopes[self.scope_index];
+                let captured = scope.defining.pop().is_some_and(|(_, c)| c);
 
                 match sm.scope {
                     symtab::Scope::Global => {
@@ -119,24 +179,47 @@ impl Compiler {
                     }
                     symtab::Scope::Local => {
                         self.emit(Opcode::SetLocal, Some(&vec![sm.index]));
+                        if captured {
+                            self.emit(Opcode::FillFrees, Some(&vec![sm.index]));
+                        }
                     }
                     _ => {}
                 };
             }
-            ast::Stmt::ExprStmt { token: _, expr } => {
+            ast::Stmt::ExprStmt {
+                token: _,
+                span: _,
+                expr,
+            } => {
                 self.compiler_expr(expr);
                 self.emit(Opcode::Pop, None);
             }
-            ast::Stmt::BlockStmt { token: _, stmts } => {
+            ast::Stmt::BlockStmt {
+                token: _,
+                span: _,
+                stmts,
+            } => {
                 for s in stmts {
                     self.compile_stmt(s)
                 }
             }
-            ast::Stmt::ReturnStmt { token: _, rval } => {
+            ast::Stmt::ReturnStmt {
+                token: _,
+                span: _,
+                rval,
+            } => {
                 self.compiler_expr(rval);
+                // nothing is left to do in this function after the call
+                if self.scope_index > 0 && matches!(rval.as_ref(), ast::Expr::CallExpr { .. }) {
+                    self.replace_last_call_with_tail_call();
+                }
                 self.emit(Opcode::ReturnValue, None);
             }
-            ast::Stmt::ShowStmt { token: _, value } => {
+            ast::Stmt::ShowStmt {
+                token: _,
+                span: _,
+                value,
+            } => {
                 //println!("{:?}" , value);
                 for v in value.iter() {
                     self.compiler_expr(v)
@@ -144,20 +227,30 @@ impl Compiler {
                 self.emit(Opcode::Show, Some(&vec![value.len()]));
             } // _ => {}
         }
+        self.span = outer;
     }
 
     pub fn compiler_expr(&mut self, expr: &ast::Expr) {
+        let outer = std::mem::replace(&mut self.span, expr.span());
         match expr {
-            ast::Expr::IdentExpr { token: _, value } => {
+            ast::Expr::IdentExpr {
+                token: _,
+                span: _,
+                value,
+            } => {
                 let sm = self.sym_resolve(value);
 
                 if let Ok(s) = sm {
                     self.load_symbol(&s);
                 } else {
-                    panic!("undefined variable {value}");
+                    panic!("{}: undefined variable {value}", self.location());
                 }
             }
-            ast::Expr::StringExpr { token, value } => {
+            ast::Expr::StringExpr {
+                token,
+                span: _,
+                value,
+            } => {
                 let sl = Rc::new(Object::String {
                     token: Some(token.to_owned()),
                     value: value.to_string(),
@@ -166,8 +259,24 @@ impl Compiler {
                 //println!("{con}");
                 self.emit(Opcode::Const, Some(&vec![con]));
             }
+            ast::Expr::InterpExpr {
+                token: _,
+                span: _,
+                parts,
+            } => {
+                for p in parts {
+                    self.compiler_expr(p);
+                    if !matches!(p.as_ref(), ast::Expr::StringExpr { .. }) {
+                        self.emit(Opcode::ToString, None);
+                    }
+                }
+                if parts.len() != 1 {
+                    self.emit(Opcode::Concat, Some(&vec![parts.len()]));
+                }
+            }
             ast::Expr::NumExpr {
                 token,
+                span: _,
                 value,
                 is_int: _,
             } => {
@@ -178,13 +287,21 @@ impl Compiler {
                 let con = self.add_const(num);
                 self.emit(Opcode::Const, Some(&vec![con]));
             }
-            ast::Expr::ArrayExpr { token: _, elems } => {
+            ast::Expr::ArrayExpr {
+                token: _,
+                span: _,
+                elems,
+            } => {
                 for el in elems {
                     self.compiler_expr(el)
                 }
                 self.emit(Opcode::Array, Some(&vec![elems.len()]));
             }
-            ast::Expr::BoolExpr { token: _, value } => {
+            ast::Expr::BoolExpr {
+                token: _,
+                span: _,
+                value,
+            } => {
                 if *value {
                     self.emit(Opcode::True, None);
                 } else {
@@ -193,17 +310,20 @@ impl Compiler {
             }
             ast::Expr::InfixExpr {
                 token: _,
+                span: _,
                 left,
                 op,
                 right,
             } => self.compile_infix_expr(left, right, op),
             ast::Expr::PrefixExpr {
                 token: _,
+                span: _,
                 op,
                 right,
             } => self.compiler_prefix_expr(right, op),
             ast::Expr::IfExpr {
                 token: _,
+                span: _,
                 cond,
                 trueblock,
                 elseblock,
@@ -236,6 +356,7 @@ impl Compiler {
 
             ast::Expr::WhileExpr {
                 token: _,
+                span: _,
                 cond,
                 stmts,
             } => {
@@ -260,7 +381,11 @@ impl Compiler {
                 //let after_con_pos = self.emit(, operands)
             }
 
-            ast::Expr::HashExpr { token: _, pairs } => {
+            ast::Expr::HashExpr {
+                token: _,
+                span: _,
+                pairs,
+            } => {
                 let mut p = pairs.clone();
                 p.sort_by_key(|(k, _)| k.to_string());
                 for (k, v) in &p {
@@ -271,6 +396,7 @@ impl Compiler {
             }
             ast::Expr::IndexExpr {
                 token: _,
+                span: _,
                 left,
                 index,
             } => {
@@ -299,25 +425,40 @@ impl Compiler {
                 }
                 let free_syms = self.sym_free_syms();
                 let num_locals = self.symtab.borrow().numdef;
+                let lines = std::mem::take(&mut self.scopes[self.scope_index].lines);
                 let ins = self.leave_scope();
                 let ins_len = ins.ins.len();
 
-                for s in &free_syms {
-                    self.load_symbol(s);
+                // a local still being defined is captured once it is set,
+                // so that a closure can refer to the value it is part of
+                let mut deferred = Vec::new();
+                for (i, s) in free_syms.iter().enumerate() {
+                    if self.capture_later(s) {
+                        self.emit(Opcode::Null, None);
+                        deferred.push(vec![i, s.index]);
+                    } else {
+                        self.load_symbol(s);
+                    }
                 }
 
                 let cmp_fn = Rc::new(Object::Compfunc(Rc::new(CompFunc {
+                    name: f.name.clone(),
                     fnin: Rc::new(ins),
                     num_locals,
                     num_params: fun_params.len(),
                     in_len: ins_len,
+                    lines: Rc::new(lines),
                 })));
                 let con = self.add_const(cmp_fn);
                 self.emit(Opcode::Closure, Some(&vec![con, free_syms.len()]));
+                for operands in &deferred {
+                    self.emit(Opcode::DeferFree, Some(operands));
+                }
             }
 
             ast::Expr::CallExpr {
                 token: _,
+                span: _,
                 func,
                 args,
             } => {
@@ -330,6 +471,23 @@ impl Compiler {
 
             _ => {}
         }
+        self.span = outer;
+    }
+
+    /// Whether `sym` is a local of the current function whose `dhori` is
+    /// being compiled; if so, it is noted as captured
+    fn capture_later(&mut self, sym: &Symbol) -> bool {
+        if sym.scope != symtab::Scope::Local {
+            return false;
+        }
+        let scope = &mut self.scopes[self.scope_index];
+        match scope.defining.iter_mut().find(|(i, _)| *i == sym.index) {
+            Some((_, captured)) => {
+                *captured = true;
+                true
+            }
+            None => false,
+        }
     }
 
     fn load_symbol(&mut self, sym: &Symbol) {
@@ -338,6 +496,7 @@ impl Compiler {
             symtab::Scope::Local => self.emit(Opcode::GetLocal, Some(&vec![sym.index])),
             symtab::Scope::Free => self.emit(Opcode::GetFree, Some(&vec![sym.index])),
             symtab::Scope::Func => self.emit(Opcode::CurrentClosure, None),
+            symtab::Scope::Builtin => self.emit(Opcode::GetBuiltin, Some(&vec![sym.index])),
         };
     }
 
@@ -348,16 +507,32 @@ impl Compiler {
         self.scopes[self.scope_index].last_ins.opcode = Opcode::ReturnValue;
     }
 
+    fn replace_last_call_with_tail_call(&mut self) {
+        let lastpos = self.scopes[self.scope_index].last_ins.pos;
+
+        self.current_ins_mut().ins[lastpos] = Opcode::TailCall as u8;
+        self.scopes[self.scope_index].last_ins.opcode = Opcode::TailCall;
+    }
+
     pub fn compiler_prefix_expr(&mut self, right: &ast::Expr, op: &Token) {
         self.compiler_expr(right);
 
         match op.ttype {
             TokenType::BANG => self.emit(Opcode::Bang, None),
             TokenType::Minus => self.emit(Opcode::Minus, None),
-            _ => panic!("prefix unknonw operator -> {} ", op.literal),
+            _ => panic!(
+                "{}: prefix unknonw operator -> {} ",
+                self.location(),
+                op.literal
+            ),
         };
     }
 
+    /// `line:col` of the node being compiled
+    fn location(&self) -> String {
+        format!("{}:{}", self.span.line, self.span.col)
+    }
+
     pub fn replace_ins(&mut self, pos: usize, new_ins: Vec<u8>) {
         let mut i = 0;
         while i < new_ins.len() {
@@ -384,7 +559,7 @@ impl Compiler {
             TokenType::GT => self.emit(Opcode::GT, None),
             TokenType::EqEq => self.emit(Opcode::Equal, None),
             TokenType::NotEq => self.emit(Opcode::NotEqual, None),
-            _ => panic!("unknown operator -> {}", op.literal),
+            _ => panic!("{}: unknown operator -> {}", self.location(), op.literal),
         };
     }
 
@@ -409,6 +584,8 @@ impl Compiler {
         }
 
         let pos = self.add_inst(Instructions { ins });
+        let span = self.span;
+        self.scopes[self.scope_index].lines.add(pos, span);
 
         self.set_last_ins(op, pos);
 
@@ -439,12 +616,26 @@ impl Compiler {
         let new = &old.ins[..last.pos];
 
         self.scopes[self.scope_index].ins.ins = new.to_vec();
+        self.scopes[self.scope_index].lines.truncate(last.pos);
         self.scopes[self.scope_index].last_ins = prev;
     }
 
+    /// Index of `obj` in the constant pool, added if it is not there yet.
+    /// Equal numbers (an int never equals a float), strings and functions
+    /// share one entry, so every occurrence of a string literal is the same
+    /// `Rc` and compares equal by pointer. A function that is a duplicate of
+    /// an earlier one keeps the earlier one's line table.
     fn add_const(&mut self, obj: Rc<Object>) -> usize {
+        let key = ConstKey::of(&obj);
+        if let Some(i) = key.as_ref().and_then(|k| self.const_index.get(k)) {
+            return *i;
+        }
         self.constants.push(obj);
-        self.constants.len() - 1
+        let index = self.constants.len() - 1;
+        if let Some(key) = key {
+            self.const_index.insert(key, index);
+        }
+        index
     }
 
     pub fn add_inst(&mut self, ins: Instructions) -> usize {
@@ -460,8 +651,10 @@ impl Compiler {
     pub fn enter_scope(&mut self) {
         let scope = CompScope {
             ins: code::Instructions::new(),
+            lines: LineTable::new(),
             last_ins: EmittedIns::new(),
             prev_ins: EmittedIns::new(),
+            defining: Vec::new(),
         };
 
         //        self.symtab = Rc::new(
@@ -489,6 +682,7 @@ impl Compiler {
         Bytecode {
             instructions: Rc::new(self.current_ins().clone()),
             constants: self.constants.clone(),
+            lines: Rc::new(self.scopes[self.scope_index].lines.clone()),
         }
     }
 }
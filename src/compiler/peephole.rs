@@ -0,0 +1,202 @@
+//! Peephole pass over compiled code, run after the compiler unless `-O0`
+//! is given. Short runs of instructions are replaced with one that does
+//! the same in a single dispatch:
+//!
+//! | Code                                        | Becomes                 |
+//! |---------------------------------------------|-------------------------|
+//! | `GetLocal a; Const c; Add; SetLocal b`      | `IncLocal a c b`        |
+//! | `GT; JumpNotTruthy t`                       | `JumpNotGT t`           |
+//! | `Equal; JumpNotTruthy t`                    | `JumpNotEqual t`        |
+//! | `NotEqual; JumpNotTruthy t`                 | `JumpEqual t`           |
+//! | `Const c` of the integer 0 or 1             | `ConstZero`, `ConstOne` |
+//! | `True; JumpNotTruthy t`                     | nothing                 |
+//! | `False; JumpNotTruthy t`                    | `Jump t`                |
+//! | `Jump t` to the next instruction            | nothing                 |
+//!
+//! A run is only replaced if no jump lands inside it. Jump targets and line
+//! tables are moved to the new offsets, and the pass repeats until nothing
+//! changes.
+
+use std::{collections::HashSet, rc::Rc};
+
+use super::code::{decode, get_def, make_ins, Bytecode, Decoded, Instructions, LineTable, Opcode};
+use crate::{
+    obj::{CompFunc, Object},
+    token::NumberToken,
+};
+
+/// What a run of instructions is replaced with
+enum Rewrite {
+    /// The first `n` instructions become this one
+    Fuse(usize, Opcode, Vec<usize>),
+    /// The first `n` instructions are dropped
+    Drop(usize),
+}
+
+fn int_const(constants: &[Rc<Object>], index: usize) -> Option<i64> {
+    match constants.get(index).map(AsRef::as_ref) {
+        Some(Object::Number {
+            value: NumberToken::Int(i),
+            ..
+        }) => Some(*i),
+        _ => None,
+    }
+}
+
+fn is_number_const(constants: &[Rc<Object>], index: usize) -> bool {
+    matches!(
+        constants.get(index).map(AsRef::as_ref),
+        Some(Object::Number { .. })
+    )
+}
+
+/// The rewrite of the code starting at `code[0]`, if there is one
+fn rewrite(code: &[Decoded], constants: &[Rc<Object>]) -> Option<Rewrite> {
+    use Opcode::*;
+
+    let ops: Vec<Opcode> = code.iter().take(4).map(|d| d.op).collect();
+    let arg = |i: usize| code[i].operands[0];
+    match ops.as_slice() {
+        [GetLocal, Const, Add, SetLocal, ..] if is_number_const(constants, arg(1)) => {
+            Some(Rewrite::Fuse(4, IncLocal, vec![arg(0), arg(1), arg(3)]))
+        }
+        [GT, JumpNotTruthy, ..] => Some(Rewrite::Fuse(2, JumpNotGT, vec![arg(1)])),
+        [Equal, JumpNotTruthy, ..] => Some(Rewrite::Fuse(2, JumpNotEqual, vec![arg(1)])),
+        [NotEqual, JumpNotTruthy, ..] => Some(Rewrite::Fuse(2, JumpEqual, vec![arg(1)])),
+        [True, JumpNotTruthy, ..] => Some(Rewrite::Drop(2)),
+        [False, JumpNotTruthy, ..] => Some(Rewrite::Fuse(2, Jump, vec![arg(1)])),
+        [Jump, ..] if arg(0) == code[0].pos + 3 => Some(Rewrite::Drop(1)),
+        [Const, ..] => match int_const(constants, arg(0)) {
+            Some(0) => Some(Rewrite::Fuse(1, ConstZero, vec![])),
+            Some(1) => Some(Rewrite::Fuse(1, ConstOne, vec![])),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// One pass over `ins`; `None` if there was nothing to rewrite
+fn pass(
+    ins: &Instructions,
+    lines: &LineTable,
+    constants: &[Rc<Object>],
+) -> Option<(Instructions, LineTable)> {
+    let code = decode(ins);
+    let targets: HashSet<usize> = code
+        .iter()
+        .filter(|d| d.op.is_jump())
+        .map(|d| d.operands[0])
+        .collect();
+
+    // new offset of every old instruction offset; the ones inside a fused
+    // run map to the run, dropped ones to whatever comes next
+    let mut moved = vec![0; ins.ins.len() + 1];
+    // old offsets inside fused runs, whose line table entries go
+    let mut inner = HashSet::new();
+    let mut dropped = HashSet::new();
+    let mut out: Vec<(Opcode, Vec<usize>)> = Vec::new();
+    let mut new_len = 0;
+    let mut changed = false;
+
+    let mut i = 0;
+    while i < code.len() {
+        let found = rewrite(&code[i..], constants).filter(|r| {
+            let n = match r {
+                Rewrite::Fuse(n, ..) | Rewrite::Drop(n) => *n,
+            };
+            code[i + 1..i + n].iter().all(|d| !targets.contains(&d.pos))
+        });
+        changed |= found.is_some();
+        let (n, replacement) = match found {
+            Some(Rewrite::Fuse(n, op, operands)) => (n, Some((op, operands))),
+            Some(Rewrite::Drop(n)) => (n, None),
+            None => (1, Some((code[i].op, code[i].operands.clone()))),
+        };
+
+        for d in &code[i..i + n] {
+            moved[d.pos] = new_len;
+        }
+        let run = code[i..i + n].iter().map(|d| d.pos);
+        match replacement {
+            Some((op, operands)) => {
+                inner.extend(run.skip(1));
+                new_len += 1 + get_def(&op).op_width.iter().sum::<i64>() as usize;
+                out.push((op, operands));
+            }
+            None => dropped.extend(run),
+        }
+        i += n;
+    }
+    if !changed {
+        return None;
+    }
+    moved[ins.ins.len()] = new_len;
+
+    let mut new_ins = Instructions::new();
+    for (op, mut operands) in out {
+        if op.is_jump() {
+            operands[0] = moved[operands[0]];
+        }
+        new_ins.add_ins(make_ins(op, &operands));
+    }
+
+    // an entry for a kept instruction wins over the entries of dropped
+    // ones that now share its offset
+    let mut new_lines = LineTable::new();
+    let mut last: Option<(usize, bool)> = None;
+    for (pos, span) in lines.entries() {
+        if inner.contains(pos) {
+            continue;
+        }
+        let (to, kept) = (moved[*pos], !dropped.contains(pos));
+        if last == Some((to, true)) && !kept {
+            continue;
+        }
+        new_lines.add(to, *span);
+        last = Some((to, kept));
+    }
+
+    Some((new_ins, new_lines))
+}
+
+/// `ins` and its line table with the rewrites in the module docs applied
+pub fn optimize_instructions(
+    ins: &Instructions,
+    lines: &LineTable,
+    constants: &[Rc<Object>],
+) -> (Instructions, LineTable) {
+    let mut ins = ins.clone();
+    let mut lines = lines.clone();
+    while let Some((i, l)) = pass(&ins, &lines, constants) {
+        ins = i;
+        lines = l;
+    }
+    (ins, lines)
+}
+
+/// `bc` with every function and the top level rewritten
+pub fn peephole(bc: Bytecode) -> Bytecode {
+    let constants: Vec<Rc<Object>> = bc
+        .constants
+        .iter()
+        .map(|c| match c.as_ref() {
+            Object::Compfunc(f) => {
+                let (fnin, lines) = optimize_instructions(&f.fnin, &f.lines, &bc.constants);
+                Rc::new(Object::Compfunc(Rc::new(CompFunc {
+                    in_len: fnin.ins.len(),
+                    fnin: Rc::new(fnin),
+                    lines: Rc::new(lines),
+                    ..f.as_ref().clone()
+                })))
+            }
+            _ => c.clone(),
+        })
+        .collect();
+    let (instructions, lines) = optimize_instructions(&bc.instructions, &bc.lines, &constants);
+
+    Bytecode {
+        instructions: Rc::new(instructions),
+        constants,
+        lines: Rc::new(lines),
+    }
+}
@@ -7,6 +7,7 @@ pub enum Scope {
     Local,
     Free,
     Func,
+    Builtin,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -89,6 +90,16 @@ impl Table {
         s
     }
 
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Rc<Symbol> {
+        let s = Rc::new(Symbol {
+            name: name.to_string(),
+            index,
+            scope: Scope::Builtin,
+        });
+        self.store.insert(name.to_string(), s.clone());
+        s
+    }
+
     pub fn define_free(&mut self, org: Rc<Symbol>) -> Rc<Symbol> {
         self.free_syms.push(org.clone());
         let sm = Rc::new(Symbol {
@@ -109,7 +120,7 @@ impl Table {
                 return Err(false);
             }
             let unwrapped_obx = obx.unwrap();
-            if unwrapped_obx.scope == Scope::Global {
+            if unwrapped_obx.scope == Scope::Global || unwrapped_obx.scope == Scope::Builtin {
                 return Ok(unwrapped_obx);
             }
 
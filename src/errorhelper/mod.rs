@@ -1,4 +1,4 @@
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ParserError {
@@ -30,8 +30,34 @@ impl ErrorHelper {
     }
 
     pub fn show_error(&self, token: &Token) -> String {
+        if token.span.line > 0 {
+            return self.show_span(token.span);
+        }
         let line: String =
             self.source.split('\n').collect::<Vec<&str>>()[token.lineno - 1].to_string();
         format!("{} |{}", token.lineno, line)
     }
+
+    /// The first source line of `span`, with the part of it that `span`
+    /// covers underlined
+    pub fn show_span(&self, span: Span) -> String {
+        let line = self
+            .source
+            .split('\n')
+            .nth(span.line.saturating_sub(1))
+            .unwrap_or_default()
+            .trim_end_matches('\r');
+        let width = if span.end_line == span.line {
+            span.end_col.saturating_sub(span.col)
+        } else {
+            line.chars().count() + 1 - span.col
+        };
+        let gutter = span.line.to_string();
+        format!(
+            "{gutter} |{line}\n{} |{}{}",
+            " ".repeat(gutter.len()),
+            " ".repeat(span.col.saturating_sub(1)),
+            "^".repeat(width.max(1))
+        )
+    }
 }
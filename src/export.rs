@@ -0,0 +1,383 @@
+//! Machine-readable views of each stage of the compiler, as JSON, for
+//! `pras tokens --json`, `pras ast --json` and `pras disasm --json`.
+//!
+//! # Schema (version 1)
+//!
+//! Every document is an object with `"version": 1` first. Fields are only
+//! ever added within a version; renaming or removing one bumps it.
+//!
+//! A **span** is
+//! `{"start", "end", "char_start", "char_end", "line", "col", "end_line", "end_col"}`:
+//! byte offsets, char offsets and 1-based line/column, the ends exclusive
+//! (see `token::Span`).
+//!
+//! ## Tokens
+//!
+//! `{"version", "tokens": [token]}`, up to and including the `Eof` token,
+//! where a token is `{"kind", "literal", "span"}`. `kind` is the name of
+//! the `TokenType` (`"Let"`, `"Ident"`, `"Number"`, ...); `literal` is the
+//! normalised text (keywords and digits in any spelling lex the same).
+//!
+//! ## AST
+//!
+//! `{"version", "program": {"kind": "Program", "body": [stmt]}}`. Every
+//! node is an object with `"kind"` (the `Stmt` / `Expr` variant name) and
+//! `"span"` first, then:
+//!
+//! | kind           | fields                                             |
+//! |----------------|----------------------------------------------------|
+//! | `LetStmt`      | `name` (identifier), `value`, `doc` (string/null)  |
+//! | `ReturnStmt`   | `value`                                            |
+//! | `ShowStmt`     | `args` [expr]                                      |
+//! | `BlockStmt`    | `body` [stmt]                                      |
+//! | `ExprStmt`     | `expr`                                             |
+//! | `NumExpr`      | `value` (number), `int` (bool)                     |
+//! | `IdentExpr`    | `name`                                             |
+//! | `BoolExpr`     | `value`                                            |
+//! | `StringExpr`   | `value`                                            |
+//! | `InterpExpr`   | `parts` [expr]                                     |
+//! | `Break`        |                                                    |
+//! | `PrefixExpr`   | `op`, `right`                                      |
+//! | `InfixExpr`    | `left`, `op`, `right`                              |
+//! | `ArrayExpr`    | `elems` [expr]                                     |
+//! | `IndexExpr`    | `left`, `index`                                    |
+//! | `IncludeExpr`  | `file`                                             |
+//! | `IfExpr`       | `cond`, `then` (block), `else` (block/null)        |
+//! | `WhileExpr`    | `cond`, `body` (block)                             |
+//! | `FuncExpr`     | `name` (string/null), `params` [identifier], `body`, `doc` |
+//! | `CallExpr`     | `func`, `args` [expr]                              |
+//! | `HashExpr`     | `pairs` [`{"key", "value"}`]                       |
+//! | `NullExpr`     |                                                    |
+//! | `ErrExpr`      | `msg`                                              |
+//!
+//! An identifier is `{"kind": "Identifier", "span", "name", "module"}`,
+//! `module` being true for `include`d names. `op` is the operator in
+//! English: `+ - * / % ! < <= > >= == != and or`.
+//!
+//! ## Bytecode
+//!
+//! `{"version", "instructions": [instruction], "constants": [constant]}`,
+//! where an instruction is `{"offset", "op", "operands": [number], "span"}`.
+//! `op` is the opcode name as `get_def` spells it (`"OpConst"`), `span` is
+//! the source the instruction was compiled from, or null.
+//!
+//! A constant is `{"index", "type", ...}`:
+//!
+//! | type       | fields                                                  |
+//! |------------|---------------------------------------------------------|
+//! | `number`   | `value`, `int` (bool)                                   |
+//! | `string`   | `value`                                                 |
+//! | `function` | `params`, `locals`, `instructions` [instruction], `name` (string/null) |
+//! | `object`   | `value`, anything else as `show` prints it              |
+
+use crate::{
+    ast::{Expr, FuncExpr, Identifier, Program, Stmt},
+    compiler::code::{get_def, read_operands, u8_to_op, Bytecode, Instructions, LineTable},
+    json::Json,
+    lexer::{Lexer, LexerError},
+    obj::Object,
+    token::{NumberToken, Span, Token, TokenType},
+};
+
+pub const SCHEMA_VERSION: usize = 1;
+
+fn document<'k>(pairs: impl IntoIterator<Item = (&'k str, Json)>) -> Json {
+    Json::object(
+        [("version", SCHEMA_VERSION.into())]
+            .into_iter()
+            .chain(pairs),
+    )
+}
+
+pub fn span_json(span: Span) -> Json {
+    Json::object([
+        ("start", span.start.into()),
+        ("end", span.end.into()),
+        ("char_start", span.char_start.into()),
+        ("char_end", span.char_end.into()),
+        ("line", span.line.into()),
+        ("col", span.col.into()),
+        ("end_line", span.end_line.into()),
+        ("end_col", span.end_col.into()),
+    ])
+}
+
+pub fn token_json(token: &Token) -> Json {
+    Json::object([
+        ("kind", format!("{:?}", token.ttype).into()),
+        ("literal", token.literal.as_str().into()),
+        ("span", span_json(token.span)),
+    ])
+}
+
+/// Every token of the lexer's input, see the module docs
+pub fn tokens_json(mut lexer: Lexer) -> Result<Json, LexerError> {
+    let mut tokens = Vec::new();
+    loop {
+        let tok = lexer.next_token()?;
+        tokens.push(token_json(&tok));
+        if tok.ttype == TokenType::Eof {
+            return Ok(document([("tokens", tokens.into())]));
+        }
+    }
+}
+
+fn op_name(op: &Token) -> Json {
+    let name = match op.ttype {
+        TokenType::Plus => "+",
+        TokenType::Minus => "-",
+        TokenType::Mul => "*",
+        TokenType::Div => "/",
+        TokenType::MOD => "%",
+        TokenType::BANG => "!",
+        TokenType::LT => "<",
+        TokenType::LTE => "<=",
+        TokenType::GT => ">",
+        TokenType::GTE => ">=",
+        TokenType::EqEq => "==",
+        TokenType::NotEq => "!=",
+        TokenType::And => "and",
+        TokenType::Or => "or",
+        _ => return op.literal.as_str().into(),
+    };
+    name.into()
+}
+
+fn number(value: &NumberToken) -> Json {
+    value.get_as_f64().into()
+}
+
+fn node<'k>(kind: &str, span: Span, fields: impl IntoIterator<Item = (&'k str, Json)>) -> Json {
+    Json::object(
+        [("kind", kind.into()), ("span", span_json(span))]
+            .into_iter()
+            .chain(fields),
+    )
+}
+
+fn exprs(list: &[std::rc::Rc<Expr>]) -> Json {
+    list.iter().map(|e| expr_json(e)).collect::<Vec<_>>().into()
+}
+
+pub fn ident_json(ident: &Identifier) -> Json {
+    node(
+        "Identifier",
+        ident.token.span,
+        [
+            ("name", ident.name.as_str().into()),
+            ("module", ident.is_mod.into()),
+        ],
+    )
+}
+
+fn func_json(f: &FuncExpr) -> Json {
+    node(
+        "FuncExpr",
+        f.span,
+        [
+            (
+                "name",
+                (!f.name.is_empty()).then_some(f.name.as_str()).into(),
+            ),
+            (
+                "params",
+                f.params.iter().map(ident_json).collect::<Vec<_>>().into(),
+            ),
+            ("body", stmt_json(&f.body)),
+            ("doc", f.doc.clone().into()),
+        ],
+    )
+}
+
+pub fn expr_json(expr: &Expr) -> Json {
+    let span = expr.span();
+    match expr {
+        Expr::NumExpr { value, is_int, .. } => node(
+            "NumExpr",
+            span,
+            [("value", number(value)), ("int", (*is_int).into())],
+        ),
+        Expr::IdentExpr { value, .. } => node("IdentExpr", span, [("name", value.as_str().into())]),
+        Expr::BoolExpr { value, .. } => node("BoolExpr", span, [("value", (*value).into())]),
+        Expr::StringExpr { value, .. } => {
+            node("StringExpr", span, [("value", value.as_str().into())])
+        }
+        Expr::InterpExpr { parts, .. } => node("InterpExpr", span, [("parts", exprs(parts))]),
+        Expr::Break { .. } => node("Break", span, []),
+        Expr::PrefixExpr { op, right, .. } => node(
+            "PrefixExpr",
+            span,
+            [("op", op_name(op)), ("right", expr_json(right))],
+        ),
+        Expr::InfixExpr {
+            left, op, right, ..
+        } => node(
+            "InfixExpr",
+            span,
+            [
+                ("left", expr_json(left)),
+                ("op", op_name(op)),
+                ("right", expr_json(right)),
+            ],
+        ),
+        Expr::ArrayExpr { elems, .. } => node("ArrayExpr", span, [("elems", exprs(elems))]),
+        Expr::IndexExpr { left, index, .. } => node(
+            "IndexExpr",
+            span,
+            [("left", expr_json(left)), ("index", expr_json(index))],
+        ),
+        Expr::IncludeExpr { filename, .. } => {
+            node("IncludeExpr", span, [("file", expr_json(filename))])
+        }
+        Expr::IfExpr {
+            cond,
+            trueblock,
+            elseblock,
+            ..
+        } => node(
+            "IfExpr",
+            span,
+            [
+                ("cond", expr_json(cond)),
+                ("then", stmt_json(trueblock)),
+                ("else", elseblock.as_deref().map(stmt_json).into()),
+            ],
+        ),
+        Expr::WhileExpr { cond, stmts, .. } => node(
+            "WhileExpr",
+            span,
+            [("cond", expr_json(cond)), ("body", stmt_json(stmts))],
+        ),
+        Expr::FuncExpr(f) => func_json(f),
+        Expr::CallExpr { func, args, .. } => node(
+            "CallExpr",
+            span,
+            [("func", expr_json(func)), ("args", exprs(args))],
+        ),
+        Expr::HashExpr { pairs, .. } => node(
+            "HashExpr",
+            span,
+            [(
+                "pairs",
+                pairs
+                    .iter()
+                    .map(|(k, v)| Json::object([("key", expr_json(k)), ("value", expr_json(v))]))
+                    .collect::<Vec<_>>()
+                    .into(),
+            )],
+        ),
+        Expr::NullExpr => node("NullExpr", span, []),
+        Expr::ErrExpr(e) => node("ErrExpr", span, [("msg", e.msg.as_str().into())]),
+    }
+}
+
+pub fn stmt_json(stmt: &Stmt) -> Json {
+    let span = stmt.span();
+    match stmt {
+        Stmt::LetStmt {
+            name, value, doc, ..
+        } => node(
+            "LetStmt",
+            span,
+            [
+                ("name", ident_json(name)),
+                ("value", expr_json(value)),
+                ("doc", doc.clone().into()),
+            ],
+        ),
+        Stmt::ReturnStmt { rval, .. } => node("ReturnStmt", span, [("value", expr_json(rval))]),
+        Stmt::ShowStmt { value, .. } => node("ShowStmt", span, [("args", exprs(value))]),
+        Stmt::BlockStmt { stmts, .. } => node(
+            "BlockStmt",
+            span,
+            [(
+                "body",
+                stmts
+                    .iter()
+                    .map(|s| stmt_json(s))
+                    .collect::<Vec<_>>()
+                    .into(),
+            )],
+        ),
+        Stmt::ExprStmt { expr, .. } => node("ExprStmt", span, [("expr", expr_json(expr))]),
+    }
+}
+
+/// The whole tree, see the module docs
+pub fn program_json(prog: &Program) -> Json {
+    let body: Vec<Json> = prog.stmts.iter().map(|s| stmt_json(s)).collect();
+    document([(
+        "program",
+        Json::object([("kind", "Program".into()), ("body", body.into())]),
+    )])
+}
+
+/// Decoded instructions, each with the span `lines` has for it
+pub fn instructions_json(ins: &Instructions, lines: &LineTable) -> Json {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < ins.ins.len() {
+        let def = get_def(&u8_to_op(ins.ins[i]));
+        let (operands, width) = read_operands(&def, ins.ins[i + 1..].to_vec());
+        out.push(Json::object([
+            ("offset", i.into()),
+            ("op", def.name.as_str().into()),
+            (
+                "operands",
+                operands
+                    .into_iter()
+                    .map(Json::from)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            ("span", lines.lookup(i).map(span_json).into()),
+        ]));
+        i += 1 + width;
+    }
+    out.into()
+}
+
+pub fn constant_json(index: usize, obj: &Object) -> Json {
+    let fields: Vec<(&str, Json)> = match obj {
+        Object::Number { value, .. } => vec![
+            ("type", "number".into()),
+            ("value", number(value)),
+            ("int", value.is_int().into()),
+        ],
+        Object::String { value, .. } => {
+            vec![("type", "string".into()), ("value", value.as_str().into())]
+        }
+        Object::Compfunc(f) => vec![
+            ("type", "function".into()),
+            ("params", f.num_params.into()),
+            ("locals", f.num_locals.into()),
+            ("instructions", instructions_json(&f.fnin, &f.lines)),
+            (
+                "name",
+                (!f.name.is_empty()).then_some(f.name.as_str()).into(),
+            ),
+        ],
+        other => vec![
+            ("type", "object".into()),
+            ("value", other.to_string().into()),
+        ],
+    };
+    Json::object([("index", index.into())].into_iter().chain(fields))
+}
+
+/// Instructions and constant pool, see the module docs
+pub fn bytecode_json(bc: &Bytecode) -> Json {
+    let constants: Vec<Json> = bc
+        .constants
+        .iter()
+        .enumerate()
+        .map(|(i, c)| constant_json(i, c))
+        .collect();
+    document([
+        (
+            "instructions",
+            instructions_json(&bc.instructions, &bc.lines),
+        ),
+        ("constants", constants.into()),
+    ])
+}
@@ -0,0 +1,656 @@
+//! Pretty-printer from `ast::Program` back to source (`pras fmt`).
+//!
+//! The AST has no comments and no `tahole`/`nahole`/`sesh` tokens, so the
+//! source is also lexed losslessly: comments and blank lines are put back
+//! between statements, and the missing keywords are spelled in the same style
+//! (English, romanised or native) as the keyword that opened their block.
+//! Literals are copied from the source as written.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{
+    ast::{Expr, FuncExpr, Program, Stmt},
+    errorhelper::ParserError,
+    langpack::LangPack,
+    lexer::{Lexer, LexerError},
+    parser::{get_precedences, Parser, P_CALL, P_INDEX, P_PREFIX},
+    token::{LosslessToken, Token, TokenType, TriviaKind},
+    translit::{keyword_spelling, KeywordStyle},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FmtOptions {
+    /// Lists (call arguments, arrays, hashes) longer than this are wrapped
+    pub width: usize,
+    /// Spaces per block level
+    pub indent: usize,
+}
+
+impl Default for FmtOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FmtError {
+    Lexer(LexerError),
+    Parser(Vec<ParserError>),
+}
+
+impl Display for FmtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lexer(e) => write!(f, "{e}"),
+            Self::Parser(errs) => {
+                for (i, e) in errs.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    match &e.token {
+                        Some(t) => write!(f, "{}:{}: {}", t.lineno, t.colno, e.msg)?,
+                        None => write!(f, "{}", e.msg)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A comment (or `##` doc comment) from the source
+#[derive(Debug)]
+struct Comment {
+    start: usize,
+    text: String,
+    /// Written after code on the same line
+    trailing: bool,
+    /// Preceded by a blank line
+    blank_before: bool,
+}
+
+/// Where the blocks opened by an `if`, `while` or `fn` end, by the byte
+/// offset of the opening token
+#[derive(Debug, Default, Clone, Copy)]
+struct Closers {
+    else_at: Option<usize>,
+    end_at: Option<usize>,
+}
+
+struct Printer<'p> {
+    opts: FmtOptions,
+    pack: &'p LangPack,
+    src: &'p str,
+    out: String,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    /// Byte offsets of tokens that have a blank line before them
+    blank_before: HashSet<usize>,
+    closers: HashMap<usize, Closers>,
+    /// The last line written opens a block; no blank line goes after it
+    after_open: bool,
+    /// Writing an expression on one line; blocks can not be written
+    flat: bool,
+    flat_failed: bool,
+}
+
+/// Format `src`, using `pack` for keywords (or the pack chosen by the source's
+/// pragma if `None`)
+pub fn format_source(
+    src: &str,
+    pack: Option<Arc<LangPack>>,
+    opts: FmtOptions,
+) -> Result<String, FmtError> {
+    let lexer = || match &pack {
+        Some(p) => Lexer::with_pack(src, p.clone()),
+        None => Lexer::new(src),
+    };
+
+    let tokens = lexer().lossless_tokens().map_err(FmtError::Lexer)?;
+    let lx = lexer();
+    let pack = lx.pack().clone();
+    let mut parser = Parser::new(lx);
+    let program = parser
+        .parse_program()
+        .map_err(|_| FmtError::Parser(parser.errors.clone()))?;
+
+    let mut p = Printer::new(src, &pack, opts, &tokens);
+    p.program(&program);
+    Ok(p.out)
+}
+
+impl<'p> Printer<'p> {
+    fn new(src: &'p str, pack: &'p LangPack, opts: FmtOptions, tokens: &[LosslessToken]) -> Self {
+        let mut comments = Vec::new();
+        let mut blank_before = HashSet::new();
+        let mut closers: HashMap<usize, Closers> = HashMap::new();
+        let mut open: Vec<usize> = Vec::new();
+        let mut prev: Option<&Token> = None;
+
+        for lt in tokens {
+            // every token's leading trivia starts at the beginning of a line
+            let mut blank = false;
+            let mut line_has_content = false;
+            for t in &lt.leading {
+                match t.kind {
+                    TriviaKind::Newline if !line_has_content => blank = true,
+                    TriviaKind::Newline => line_has_content = false,
+                    TriviaKind::Whitespace => {}
+                    TriviaKind::Comment | TriviaKind::BlockComment => {
+                        comments.push(Comment {
+                            start: t.span.start,
+                            text: t.text.trim_end().to_string(),
+                            trailing: false,
+                            blank_before: blank,
+                        });
+                        blank = false;
+                        line_has_content = true;
+                    }
+                }
+            }
+
+            let tok = &lt.token;
+            if blank {
+                blank_before.insert(tok.span.start);
+            }
+            if tok.ttype == TokenType::DocComment {
+                comments.push(Comment {
+                    start: tok.span.start,
+                    text: lt.text.trim_end().to_string(),
+                    trailing: false,
+                    blank_before: blank,
+                });
+            }
+
+            for t in &lt.trailing {
+                if matches!(t.kind, TriviaKind::Comment | TriviaKind::BlockComment) {
+                    comments.push(Comment {
+                        start: t.span.start,
+                        text: t.text.trim_end().to_string(),
+                        trailing: true,
+                        blank_before: false,
+                    });
+                }
+            }
+
+            match tok.ttype {
+                TokenType::If | TokenType::While => open.push(tok.span.start),
+                // a function literal starts at `ekti`
+                TokenType::Func => match prev {
+                    Some(p) if p.ttype == TokenType::One => open.push(p.span.start),
+                    _ => open.push(tok.span.start),
+                },
+                TokenType::Else => {
+                    if let Some(o) = open.last() {
+                        closers.entry(*o).or_default().else_at = Some(tok.span.start);
+                    }
+                }
+                TokenType::End => {
+                    if let Some(o) = open.pop() {
+                        closers.entry(o).or_default().end_at = Some(tok.span.start);
+                    }
+                }
+                _ => {}
+            }
+            if tok.ttype != TokenType::DocComment {
+                prev = Some(tok);
+            }
+        }
+
+        Printer {
+            opts,
+            pack,
+            src,
+            out: String::new(),
+            comments,
+            next_comment: 0,
+            blank_before,
+            closers,
+            after_open: false,
+            flat: false,
+            flat_failed: false,
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        self.stmts(&program.stmts, 0);
+        self.comments_before(usize::MAX, 0);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    //
+    // Layout helpers
+    //
+
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(indent * self.opts.indent));
+    }
+
+    /// Start the line of a statement or comment, keeping at most one blank
+    /// line before it if `blank`
+    fn start_line(&mut self, indent: usize, blank: bool) {
+        if self.out.is_empty() {
+            self.out.push_str(&" ".repeat(indent * self.opts.indent));
+        } else {
+            if blank && !self.after_open {
+                self.out.push('\n');
+            }
+            self.newline(indent);
+        }
+        self.after_open = false;
+    }
+
+    /// Source text of `tok`, or its literal for tokens that were not lexed
+    fn tok(&mut self, tok: &Token) {
+        match self.src.get(tok.span.start..tok.span.end) {
+            Some(text) if !text.is_empty() => self.out.push_str(text),
+            _ => self.out.push_str(&tok.literal),
+        }
+    }
+
+    /// Keyword `tt`, spelled in the same style as the keyword `like`
+    fn keyword(&mut self, tt: TokenType, like: &Token) {
+        let spelling = keyword_spelling(self.pack, &tt, KeywordStyle::of(like))
+            .or_else(|| LangPack::english_spelling(&tt))
+            .unwrap_or_default();
+        self.out.push_str(spelling);
+    }
+
+    fn closers(&self, opener: &Token) -> Closers {
+        self.closers
+            .get(&opener.span.start)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Write the comments that start before `offset`
+    fn comments_before(&mut self, offset: usize, indent: usize) {
+        while let Some(c) = self.comments.get(self.next_comment) {
+            if c.start >= offset {
+                break;
+            }
+            let (text, trailing, blank) = (c.text.clone(), c.trailing, c.blank_before);
+            self.next_comment += 1;
+
+            if trailing && !self.out.is_empty() {
+                self.out.push_str("  ");
+            } else {
+                self.start_line(indent, blank);
+            }
+            self.out.push_str(&text);
+        }
+    }
+
+    //
+    // Statements
+    //
+
+    /// Statements are only ended with `;` where the next one would
+    /// otherwise be read as part of them
+    fn stmts(&mut self, stmts: &[Rc<Stmt>], indent: usize) {
+        // end of the previous statement, if it ends in an expression
+        let mut open_end: Option<usize> = None;
+        for s in stmts {
+            let start = stmt_token(s).span.start;
+            self.comments_before(start, indent);
+            let blank = self.blank_before.contains(&start);
+            self.start_line(indent, blank);
+            let mark = self.out.len();
+            self.stmt(s, indent);
+
+            if let Some(end) = open_end {
+                if continues_expr(&self.out[mark..]) {
+                    self.out.insert(end, ';');
+                }
+            }
+            open_end = matches!(s.as_ref(), Stmt::LetStmt { .. } | Stmt::ExprStmt { .. })
+                .then_some(self.out.len());
+        }
+    }
+
+    /// Body of a block whose first line has been written; `end` is where
+    /// the keyword closing it is in the source
+    fn block(&mut self, block: &Stmt, indent: usize, end: Option<usize>) {
+        self.after_open = true;
+        if let Stmt::BlockStmt { stmts, .. } = block {
+            self.stmts(stmts, indent);
+        }
+        if let Some(end) = end {
+            self.comments_before(end, indent);
+        }
+        self.after_open = false;
+    }
+
+    fn stmt(&mut self, s: &Stmt, indent: usize) {
+        match s {
+            Stmt::LetStmt {
+                token,
+                span: _,
+                name,
+                value,
+                doc: _,
+            } => {
+                self.tok(token);
+                self.out.push(' ');
+                self.out.push_str(&name.name);
+                self.out.push_str(" = ");
+                self.expr(value, indent);
+            }
+            Stmt::ReturnStmt {
+                token,
+                span: _,
+                rval,
+            } => {
+                self.tok(token);
+                self.out.push('(');
+                self.expr(rval, indent);
+                self.out.push(')');
+            }
+            Stmt::ShowStmt {
+                token,
+                span: _,
+                value,
+            } => {
+                self.tok(token);
+                self.list("(", value, ")", indent, |p, e, i| p.expr(e, i));
+            }
+            Stmt::BlockStmt {
+                token: _,
+                span: _,
+                stmts,
+            } => self.stmts(stmts, indent),
+            Stmt::ExprStmt {
+                token: _,
+                span: _,
+                expr,
+            } => self.expr(expr, indent),
+        }
+    }
+
+    //
+    // Expressions
+    //
+
+    /// Write `items` between `open` and `close` on one line if they fit in
+    /// the line width, else one item per line
+    fn list<T>(
+        &mut self,
+        open: &str,
+        items: &[T],
+        close: &str,
+        indent: usize,
+        item: impl Fn(&mut Self, &T, usize),
+    ) {
+        let mark = self.out.len();
+        let (was_flat, was_failed) = (self.flat, self.flat_failed);
+
+        self.flat = true;
+        self.flat_failed = false;
+        self.out.push_str(open);
+        for (i, it) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            item(self, it, indent);
+        }
+        self.out.push_str(close);
+        let fits = !self.flat_failed && (was_flat || self.column() <= self.opts.width);
+        self.flat = was_flat;
+        self.flat_failed = was_failed || (was_flat && !fits);
+
+        if fits || was_flat || items.is_empty() {
+            return;
+        }
+
+        self.out.truncate(mark);
+        self.out.push_str(open);
+        for (i, it) in items.iter().enumerate() {
+            self.newline(indent + 1);
+            item(self, it, indent + 1);
+            if i + 1 < items.len() {
+                self.out.push(',');
+            }
+        }
+        self.newline(indent);
+        self.out.push_str(close);
+    }
+
+    /// Write `e` as an operand of an operator with precedence `prec`,
+    /// in parentheses if it binds looser
+    fn operand(&mut self, e: &Expr, prec: usize, right: bool, indent: usize) {
+        let parens = match e {
+            Expr::InfixExpr { op, .. } => {
+                let p = get_precedences(&op.ttype);
+                p < prec || (right && p == prec)
+            }
+            Expr::PrefixExpr { .. } => prec > P_PREFIX,
+            _ => false,
+        };
+
+        if parens {
+            self.out.push('(');
+        }
+        self.expr(e, indent);
+        if parens {
+            self.out.push(')');
+        }
+    }
+
+    fn expr(&mut self, e: &Expr, indent: usize) {
+        match e {
+            Expr::NumExpr { token, .. }
+            | Expr::StringExpr { token, .. }
+            | Expr::BoolExpr { token, .. }
+            | Expr::Break { token, .. } => self.tok(token),
+            Expr::IdentExpr {
+                token: _,
+                span: _,
+                value,
+            } => self.out.push_str(value),
+            Expr::InterpExpr {
+                token,
+                span: _,
+                parts,
+            } => self.interp(token, parts, indent),
+            Expr::PrefixExpr {
+                token: _,
+                span: _,
+                op,
+                right,
+            } => {
+                self.tok(op);
+                self.operand(right, P_PREFIX, false, indent);
+            }
+            Expr::InfixExpr {
+                token: _,
+                span: _,
+                left,
+                op,
+                right,
+            } => {
+                let prec = get_precedences(&op.ttype);
+                self.operand(left, prec, false, indent);
+                self.out.push(' ');
+                self.tok(op);
+                self.out.push(' ');
+                self.operand(right, prec, true, indent);
+            }
+            Expr::ArrayExpr {
+                token: _,
+                span: _,
+                elems,
+            } => self.list("[", elems, "]", indent, |p, e, i| p.expr(e, i)),
+            Expr::IndexExpr {
+                token: _,
+                span: _,
+                left,
+                index,
+            } => {
+                self.operand(left, P_INDEX, false, indent);
+                self.out.push('[');
+                self.expr(index, indent);
+                self.out.push(']');
+            }
+            Expr::CallExpr {
+                token: _,
+                span: _,
+                func,
+                args,
+            } => {
+                self.operand(func, P_CALL, false, indent);
+                self.list("(", args, ")", indent, |p, e, i| p.expr(e, i));
+            }
+            Expr::HashExpr {
+                token: _,
+                span: _,
+                pairs,
+            } => self.list("{", pairs, "}", indent, |p, (k, v), i| {
+                p.expr(k, i);
+                p.out.push_str(": ");
+                p.expr(v, i);
+            }),
+            Expr::IncludeExpr {
+                token,
+                span: _,
+                filename,
+            } => {
+                self.tok(token);
+                self.out.push('(');
+                self.expr(filename, indent);
+                self.out.push(')');
+            }
+            Expr::IfExpr { .. } | Expr::WhileExpr { .. } | Expr::FuncExpr(..) if self.flat => {
+                self.flat_failed = true;
+            }
+            Expr::IfExpr {
+                token,
+                span: _,
+                cond,
+                trueblock,
+                elseblock,
+            } => {
+                let closers = self.closers(token);
+                self.tok(token);
+                self.out.push_str(" (");
+                self.expr(cond, indent);
+                self.out.push_str(") ");
+                self.keyword(TokenType::Then, token);
+                self.block(trueblock, indent + 1, closers.else_at);
+                self.newline(indent);
+                self.keyword(TokenType::Else, token);
+                if let Some(eb) = elseblock {
+                    self.block(eb, indent + 1, closers.end_at);
+                }
+                self.newline(indent);
+                self.keyword(TokenType::End, token);
+            }
+            Expr::WhileExpr {
+                token,
+                span: _,
+                cond,
+                stmts,
+            } => {
+                let closers = self.closers(token);
+                self.tok(token);
+                self.out.push(' ');
+                self.expr(cond, indent);
+                self.out.push(':');
+                self.block(stmts, indent + 1, closers.end_at);
+                self.newline(indent);
+                self.keyword(TokenType::End, token);
+            }
+            Expr::FuncExpr(f) => self.func(f, indent),
+            Expr::NullExpr | Expr::ErrExpr(_) => {}
+        }
+    }
+
+    fn func(&mut self, f: &FuncExpr, indent: usize) {
+        let closers = self.closers(&f.token);
+        self.tok(&f.token);
+        self.out.push(' ');
+        self.keyword(TokenType::Func, &f.token);
+        self.out.push('(');
+        for (i, p) in f.params.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&p.name);
+        }
+        self.out.push(')');
+        self.block(&f.body, indent + 1, closers.end_at);
+        self.newline(indent);
+        self.keyword(TokenType::End, &f.token);
+    }
+
+    /// `"text {expr} text"`: the text parts are copied from the source
+    /// (with their `}`/`{` delimiters), the expressions are formatted
+    fn interp(&mut self, start: &Token, parts: &[Rc<Expr>], indent: usize) {
+        let open = self.src[start.span.start..start.span.end].trim_start_matches('r');
+        let quote = if open.starts_with("\"\"\"") {
+            "\"\"\""
+        } else {
+            "\""
+        };
+
+        self.tok(start);
+        let mut after_expr = false;
+        for part in parts {
+            match part.as_ref() {
+                Expr::StringExpr { token, .. } if token.ttype == TokenType::InterpStart => {}
+                Expr::StringExpr { token, .. }
+                    if matches!(token.ttype, TokenType::InterpMid | TokenType::InterpEnd) =>
+                {
+                    self.tok(token);
+                    after_expr = false;
+                }
+                e => {
+                    if after_expr {
+                        self.out.push_str("}{");
+                    }
+                    let was_flat = self.flat;
+                    self.flat = true;
+                    self.expr(e, indent);
+                    self.flat = was_flat;
+                    after_expr = true;
+                }
+            }
+        }
+        if after_expr {
+            self.out.push('}');
+            self.out.push_str(quote);
+        }
+    }
+}
+
+/// Whether a statement written as `text` would be read as going on with an
+/// expression right before it: as a call, an index or a subtraction
+fn continues_expr(text: &str) -> bool {
+    text.starts_with(['(', '[', '-'])
+}
+
+/// First token of a statement
+fn stmt_token(s: &Stmt) -> &Token {
+    match s {
+        Stmt::LetStmt { token, .. }
+        | Stmt::ReturnStmt { token, .. }
+        | Stmt::ShowStmt { token, .. }
+        | Stmt::BlockStmt { token, .. }
+        | Stmt::ExprStmt { token, .. } => token,
+    }
+}
@@ -0,0 +1,327 @@
+//! Keyword language packs.
+//!
+//! A pack maps native spellings of the keywords to their token types and
+//! selects the script that identifiers and digits are read from. Packs are
+//! plain text (see `packs/*.txt`), so new languages can be added without
+//! touching the lexer, either by shipping them here or by loading a file at
+//! runtime with [`LangPack::load`].
+//!
+//! A source file chooses its pack with a pragma comment in its header:
+//!
+//! ```text
+//! # lang: hi
+//! माना x = 10;
+//! ```
+
+use std::{collections::HashMap, fmt::Display, fs, sync::Arc, sync::OnceLock};
+
+use crate::{
+    token::TokenType,
+    unicode::{is_joiner, is_mark, nfc},
+};
+
+/// Pack used when neither the caller nor the source file picks one
+pub const DEFAULT_PACK: &str = "bn";
+
+const BUILTIN_DEFS: &[&str] = &[
+    include_str!("packs/bn.txt"),
+    include_str!("packs/as.txt"),
+    include_str!("packs/hi.txt"),
+];
+
+/// English spelling of every keyword; these are accepted by all packs and
+/// name the keywords in pack definitions
+const KEYWORDS: &[(&str, TokenType)] = &[
+    ("include", TokenType::Include),
+    ("one", TokenType::One),
+    ("then", TokenType::Then),
+    ("and", TokenType::And),
+    ("or", TokenType::Or),
+    ("fn", TokenType::Func),
+    ("let", TokenType::Let),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+    ("if", TokenType::If),
+    ("else", TokenType::Else),
+    ("return", TokenType::Return),
+    ("while", TokenType::While),
+    ("show", TokenType::Show),
+    ("end", TokenType::End),
+    ("break", TokenType::Break),
+];
+
+/// A Unicode block identifiers can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Script {
+    pub name: &'static str,
+    pub start: char,
+    pub end: char,
+    /// The script's own digit zero; digits one to nine follow it
+    pub zero: char,
+}
+
+pub const BENGALI: Script = Script {
+    name: "bengali",
+    start: '\u{0980}',
+    end: '\u{09fe}',
+    zero: '\u{09e6}',
+};
+
+pub const DEVANAGARI: Script = Script {
+    name: "devanagari",
+    start: '\u{0900}',
+    end: '\u{097f}',
+    zero: '\u{0966}',
+};
+
+const SCRIPTS: &[Script] = &[BENGALI, DEVANAGARI];
+
+impl Script {
+    pub fn by_name(name: &str) -> Option<Script> {
+        SCRIPTS.iter().find(|s| s.name == name).copied()
+    }
+
+    pub const fn contains(&self, c: char) -> bool {
+        c >= self.start && c <= self.end
+    }
+
+    /// Value of `c` if it is one of this script's digits
+    pub fn digit(&self, c: char) -> Option<u32> {
+        let d = (c as u32).checked_sub(self.zero as u32)?;
+        if d < 10 {
+            Some(d)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LangPackError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Display for LangPackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.msg)
+        } else {
+            write!(f, "line {}: {}", self.line, self.msg)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LangPack {
+    pub name: String,
+    pub script: Script,
+    keywords: HashMap<String, TokenType>,
+    /// Native spelling of each keyword (the first one listed in the
+    /// definition that is written in the pack's script)
+    native: HashMap<TokenType, String>,
+    /// Romanised spelling of each keyword (the first ASCII one listed)
+    roman: HashMap<TokenType, String>,
+}
+
+impl Default for LangPack {
+    fn default() -> Self {
+        LangPack::builtin(DEFAULT_PACK)
+            .map(|p| (*p).clone())
+            .expect("default language pack is missing")
+    }
+}
+
+impl LangPack {
+    /// Parse a pack definition.
+    ///
+    /// The format is line based: `name = <id>`, `script = <script>` and one
+    /// `<english keyword> = <spelling> <spelling>...` line per keyword.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(def: &str) -> Result<LangPack, LangPackError> {
+        let mut name = None;
+        let mut script = None;
+        let mut keywords: HashMap<String, TokenType> = KEYWORDS
+            .iter()
+            .map(|(kw, tt)| (kw.to_string(), *tt))
+            .collect();
+        let mut native = HashMap::new();
+        let mut roman = HashMap::new();
+
+        for (i, line) in def.lines().enumerate() {
+            let err = |msg: String| LangPackError { line: i + 1, msg };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(err(format!("expected `key = value`, found `{line}`")));
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "name" => name = Some(value.to_string()),
+                "script" => {
+                    script = Some(
+                        Script::by_name(value)
+                            .ok_or_else(|| err(format!("unknown script `{value}`")))?,
+                    )
+                }
+                _ => {
+                    let Some((_, tt)) = KEYWORDS.iter().find(|(kw, _)| *kw == key) else {
+                        return Err(err(format!("unknown keyword `{key}`")));
+                    };
+                    for spelling in value.split_whitespace() {
+                        let spelling = nfc(spelling);
+                        let spelling = spelling.as_ref();
+                        if let Some(prev) = keywords.get(spelling) {
+                            if prev != tt {
+                                return Err(err(format!(
+                                    "`{spelling}` is already a spelling of {prev:?}"
+                                )));
+                            }
+                        }
+                        keywords.insert(spelling.to_string(), *tt);
+                        let spellings = if spelling.is_ascii() {
+                            &mut roman
+                        } else {
+                            &mut native
+                        };
+                        spellings.entry(*tt).or_insert_with(|| spelling.to_string());
+                    }
+                }
+            }
+        }
+
+        let err = |msg: &str| LangPackError {
+            line: 0,
+            msg: msg.to_string(),
+        };
+        Ok(LangPack {
+            name: name.ok_or_else(|| err("language pack has no `name`"))?,
+            script: script.ok_or_else(|| err("language pack has no `script`"))?,
+            keywords,
+            native,
+            roman,
+        })
+    }
+
+    /// One of the packs shipped with the interpreter
+    pub fn builtin(name: &str) -> Option<Arc<LangPack>> {
+        static PACKS: OnceLock<Vec<Arc<LangPack>>> = OnceLock::new();
+        PACKS
+            .get_or_init(|| {
+                BUILTIN_DEFS
+                    .iter()
+                    .map(|def| {
+                        Arc::new(LangPack::parse(def).expect("invalid builtin language pack"))
+                    })
+                    .collect()
+            })
+            .iter()
+            .find(|p| p.name == name)
+            .cloned()
+    }
+
+    /// Names of the packs shipped with the interpreter
+    pub fn builtin_names() -> Vec<String> {
+        BUILTIN_DEFS
+            .iter()
+            .filter_map(|def| LangPack::parse(def).ok())
+            .map(|p| p.name)
+            .collect()
+    }
+
+    /// A builtin pack by name, or else a pack definition file at `path`
+    pub fn load(name_or_path: &str) -> Result<Arc<LangPack>, LangPackError> {
+        if let Some(pack) = LangPack::builtin(name_or_path) {
+            return Ok(pack);
+        }
+        let def = fs::read_to_string(name_or_path).map_err(|e| LangPackError {
+            line: 0,
+            msg: format!("unknown language pack `{name_or_path}` ({e})"),
+        })?;
+        LangPack::parse(&def).map(Arc::new)
+    }
+
+    pub fn lookup(&self, id: &str) -> Option<TokenType> {
+        self.keywords.get(nfc(id).as_ref()).copied()
+    }
+
+    /// Every keyword spelling the pack accepts, English ones included
+    pub fn spellings(&self) -> impl Iterator<Item = (&str, TokenType)> {
+        self.keywords.iter().map(|(s, tt)| (s.as_str(), *tt))
+    }
+
+    /// Spelling of `tt` in the pack's own script, if it has one
+    pub fn native_spelling(&self, tt: &TokenType) -> Option<&str> {
+        self.native.get(tt).map(|s| s.as_str())
+    }
+
+    /// Romanised spelling of `tt` (e.g. Banglish), if it has one
+    pub fn roman_spelling(&self, tt: &TokenType) -> Option<&str> {
+        self.roman.get(tt).map(|s| s.as_str())
+    }
+
+    /// English spelling of `tt`, if it is a keyword
+    pub fn english_spelling(tt: &TokenType) -> Option<&'static str> {
+        KEYWORDS.iter().find(|(_, t)| t == tt).map(|(kw, _)| *kw)
+    }
+
+    /// Can `c` start an identifier: an ASCII letter or a letter of the
+    /// pack's script (not a digit or a combining mark)
+    pub fn is_ident_start(&self, c: char) -> bool {
+        c.is_ascii_alphabetic()
+            || (self.script.contains(c) && self.script.digit(c).is_none() && !is_mark(c))
+    }
+
+    /// Can `c` continue an identifier; after the first character digits,
+    /// `_`, combining marks and ZWJ/ZWNJ are allowed too
+    pub fn is_ident_char(&self, c: char) -> bool {
+        c.is_ascii_alphanumeric()
+            || c == '_'
+            || self.script.contains(c)
+            || is_mark(c)
+            || is_joiner(c)
+    }
+
+    /// Is `c` an ASCII digit or a digit of the pack's script
+    pub fn is_digit(&self, c: char) -> bool {
+        c.is_ascii_digit() || self.script.digit(c).is_some()
+    }
+
+    /// Digit value of an ASCII or native digit (or a hex letter) in `radix`
+    pub fn digit_value(&self, c: char, radix: u32) -> Option<u32> {
+        match self.script.digit(c) {
+            Some(d) if d < radix => Some(d),
+            Some(_) => None,
+            None => c.to_digit(radix),
+        }
+    }
+}
+
+/// Find a `# lang: <pack>` (or `# bhasha: <pack>`) pragma in the comment
+/// lines at the top of `src`. Returns the pack name and its line number.
+pub fn find_pragma(src: &str) -> Option<(&str, usize)> {
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#!") {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix('#') else {
+            break;
+        };
+        if comment.starts_with('#') || comment.starts_with('[') {
+            continue;
+        }
+        let comment = comment.trim();
+        let value = comment
+            .strip_prefix("lang:")
+            .or_else(|| comment.strip_prefix("bhasha:"));
+        if let Some(value) = value {
+            return Some((value.trim(), i + 1));
+        }
+    }
+    None
+}
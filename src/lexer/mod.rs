@@ -1,16 +1,17 @@
 use crate::{
-    bn::{is_bn_char, is_bn_num, parse_bn_num},
     errorhelper::ErrorHelper,
-    token::{lookup_ident, Token, TokenType},
+    langpack::{find_pragma, LangPack, DEFAULT_PACK},
+    token::{LosslessToken, Span, Token, TokenType, Trivia, TriviaKind},
+    unicode::nfc,
 };
-use std::rc::Rc;
+use std::{fmt::Display, ops::Range, rc::Rc, sync::Arc};
 
 fn charlist_to_string(charlist: &[char]) -> String {
     String::from_iter(charlist.iter())
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Lexer<'a> {
     input: &'a str,
     charlist: Vec<char>,
@@ -20,29 +21,120 @@ pub struct Lexer<'a> {
     colno: usize,
     ch: char,
     pub eh: ErrorHelper,
+    /// Open string interpolations, innermost last
+    interps: Vec<Interp>,
+    /// Keyword spellings and the script identifiers are written in
+    pack: Arc<LangPack>,
+    /// Byte offset of each char in `charlist`, plus the input length
+    offsets: Vec<usize>,
+    /// Position in `charlist` where each line starts
+    line_starts: Vec<usize>,
+    /// Position in `charlist` where the last token started
+    start: usize,
+    /// Record trivia in `trivia` while skipping it
+    lossless: bool,
+    trivia: Vec<(TriviaKind, Range<usize>)>,
+    /// Error found while setting up the lexer (an unknown pragma pack),
+    /// reported by the first `next_token` call
+    pending: Option<LexerError>,
 }
 
+/// State of a `{...}` interpolation inside a string literal
+#[derive(Debug, Default, Clone, Copy)]
+struct Interp {
+    /// Number of unclosed `{` inside the interpolated expression (hash literals)
+    depth: usize,
+    /// The string being interpolated is triple-quoted
+    triple: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LexerError {
-    pub token: Option<Token>,
+    /// The malformed token, boxed to keep `Result<Token, LexerError>` small
+    pub token: Option<Box<Token>>,
     pub msg: String,
+    /// Position of the offending character
+    pub lineno: usize,
+    pub colno: usize,
+}
+
+impl Display for LexerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.lineno, self.colno, self.msg)
+    }
 }
 
 impl<'a> Lexer<'a> {
+    /// Lexer using the language pack named by the `# lang: ...` pragma in
+    /// the file header, or the default (Bengali) pack if there is none
     pub fn new(inp: &'a str) -> Self {
+        let default = || LangPack::builtin(DEFAULT_PACK).unwrap_or_default();
+        let Some((name, lineno)) = find_pragma(inp) else {
+            return Self::with_pack(inp, default());
+        };
+
+        match LangPack::builtin(name) {
+            Some(pack) => Self::with_pack(inp, pack),
+            None => {
+                let mut lexer = Self::with_pack(inp, default());
+                lexer.pending = Some(LexerError {
+                    token: None,
+                    msg: format!("unknown language pack `{name}`"),
+                    lineno,
+                    colno: 1,
+                });
+                lexer
+            }
+        }
+    }
+
+    /// Lexer using `pack`, ignoring any pragma in the source
+    pub fn with_pack(inp: &'a str, pack: Arc<LangPack>) -> Self {
+        let offsets = inp
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(inp.len()))
+            .collect();
+        let charlist: Vec<char> = inp.chars().collect();
+        let line_starts = std::iter::once(0)
+            .chain(
+                charlist
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
         let mut lexer = Lexer {
             input: inp,
-            charlist: inp.chars().collect(),
+            charlist,
             pos: 0,
             read_pos: 0,
             lineno: 1,
             colno: 0,
-            ..Default::default()
+            ch: '\0',
+            eh: ErrorHelper::new(inp),
+            interps: Vec::new(),
+            pack,
+            offsets,
+            line_starts,
+            start: 0,
+            lossless: false,
+            trivia: Vec::new(),
+            pending: None,
         };
-        lexer.eh = ErrorHelper::new(inp);
         lexer.read_char();
         lexer
     }
 
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    pub fn pack(&self) -> &Arc<LangPack> {
+        &self.pack
+    }
+
     pub fn is_at_eof(&self) -> bool {
         self.pos >= self.charlist.len()
     }
@@ -55,70 +147,345 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn read_number(&mut self) -> Option<Token> {
-        //println!("{:?}" , self.input.chars());
-        let cur_pos = self.pos;
-        let colno = self.colno;
-        let lineno = self.lineno;
-        while self.ch.is_ascii_digit() || is_bn_num(self.ch) {
+    fn number_error(&self, msg: String) -> LexerError {
+        LexerError {
+            token: None,
+            msg,
+            lineno: self.lineno,
+            colno: self.colno,
+        }
+    }
+
+    /// Read a run of digits (in `radix`) and `_` separators;
+    /// returns the digits with separators removed, or an error for a misplaced `_`
+    fn read_digits(&mut self, radix: u32) -> Result<String, LexerError> {
+        let mut digits = String::new();
+        let mut last_sep = false;
+
+        loop {
+            let d = self.pack.digit_value(self.ch, radix);
+            if d.is_none() && self.ch != '_' {
+                break;
+            }
+            if let Some(d) = d.and_then(|d| char::from_digit(d, radix)) {
+                digits.push(d);
+                last_sep = false;
+            } else {
+                if digits.is_empty() || last_sep {
+                    return Err(self.number_error(
+                        "digit separator `_` must be placed between digits".to_string(),
+                    ));
+                }
+                last_sep = true;
+            }
             self.read_char();
         }
 
-        if self.ch == '.' {
+        if last_sep {
+            return Err(
+                self.number_error("digit separator `_` must be placed between digits".to_string())
+            );
+        }
+
+        Ok(digits)
+    }
+
+    /// Anything that can still belong to a number literal;
+    /// if one of these follows a literal, the literal is malformed
+    fn is_number_tail(&self, c: char) -> bool {
+        c.is_alphanumeric() || self.pack.is_ident_char(c) || c == '_' || c == '.'
+    }
+
+    fn read_number(&mut self) -> Result<Token, LexerError> {
+        let start = self.pos;
+        let colno = self.colno;
+        let lineno = self.lineno;
+
+        let radix = if self.pack.digit_value(self.ch, 10) == Some(0) {
+            match self.peek() {
+                'x' | 'X' => 16,
+                'b' | 'B' => 2,
+                'o' | 'O' => 8,
+                _ => 10,
+            }
+        } else {
+            10
+        };
+
+        let literal = if radix != 10 {
             self.read_char();
-            while self.ch.is_ascii_digit() || is_bn_num(self.ch) {
+            self.read_char();
+            let digits = self.read_digits(radix)?;
+            if digits.is_empty() {
+                return Err(self.number_error(format!(
+                    "expected digits after `{}`",
+                    charlist_to_string(&self.charlist[start..self.pos])
+                )));
+            }
+            if self.is_number_tail(self.ch) {
+                return Err(self.number_error(format!(
+                    "invalid digit `{}` in base {radix} number literal",
+                    self.ch
+                )));
+            }
+            match i64::from_str_radix(&digits, radix) {
+                Ok(v) => v.to_string(),
+                Err(_) => return Err(self.number_error("number literal is too large".to_string())),
+            }
+        } else {
+            let mut literal = self.read_digits(10)?;
+
+            if self.ch == '.' {
                 self.read_char();
+                let frac = self.read_digits(10)?;
+                if frac.is_empty() {
+                    return Err(
+                        self.number_error("expected digits after decimal point".to_string())
+                    );
+                }
+                literal.push('.');
+                literal.push_str(&frac);
             }
-        }
 
-        let raw_number: String =
-            parse_bn_num(&charlist_to_string(&self.charlist[cur_pos..self.pos]));
+            if self.ch == 'e' || self.ch == 'E' {
+                self.read_char();
+                literal.push('e');
+                if self.ch == '+' || self.ch == '-' {
+                    literal.push(self.ch);
+                    self.read_char();
+                }
+                let exp = self.read_digits(10)?;
+                if exp.is_empty() {
+                    return Err(self.number_error("expected digits in exponent".to_string()));
+                }
+                literal.push_str(&exp);
+            }
 
-        if raw_number.is_ascii() {
-            Some(Token::new(TokenType::Number, raw_number, colno, lineno))
-        } else {
-            None
-        }
+            if self.is_number_tail(self.ch) {
+                return Err(
+                    self.number_error(format!("invalid character `{}` in number literal", self.ch))
+                );
+            }
+            literal
+        };
+
+        Ok(Token::new(TokenType::Number, literal, colno, lineno))
     }
+
     fn read_identifier(&mut self) -> String {
         let pos = self.pos;
-        while !self.is_at_eof() && (self.ch.is_ascii_alphabetic() || is_bn_char(self.ch)) {
+        while !self.is_at_eof() && self.pack.is_ident_char(self.ch) {
             self.read_char()
         }
 
         if self.ch == '.' {
             self.read_char();
-            while !self.is_at_eof() && (self.ch.is_ascii_alphabetic() || is_bn_char(self.ch)) {
+            while !self.is_at_eof() && self.pack.is_ident_char(self.ch) {
                 self.read_char()
             }
         }
 
-        charlist_to_string(&self.charlist[pos..self.pos])
+        // `য়` and `য` + nukta are the same identifier
+        nfc(&charlist_to_string(&self.charlist[pos..self.pos])).into_owned()
     }
 
-    fn read_string(&mut self) -> Token {
-        let pos = self.pos + 1;
+    fn peek_nth(&self, n: usize) -> char {
+        self.charlist
+            .get(self.read_pos + n)
+            .copied()
+            .unwrap_or('\0')
+    }
+
+    fn is_triple_quote(&self) -> bool {
+        self.ch == '"' && self.peek() == '"' && self.peek_nth(1) == '"'
+    }
+
+    fn string_error(&self, msg: String, lineno: usize, colno: usize) -> LexerError {
+        LexerError {
+            token: None,
+            msg,
+            lineno,
+            colno,
+        }
+    }
+
+    /// Read the escape sequence starting at the current `\`
+    fn read_escape(&mut self) -> Result<char, LexerError> {
+        let (lineno, colno) = (self.lineno, self.colno);
+        self.read_char();
+
+        let c = match self.ch {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => {
+                self.read_char();
+                if self.ch != '{' {
+                    return Err(self.string_error(
+                        "expected `{` in unicode escape; use `\\u{XXXX}`".to_string(),
+                        lineno,
+                        colno,
+                    ));
+                }
+                self.read_char();
+                let mut hex = String::new();
+                while self.ch.is_ascii_hexdigit() {
+                    hex.push(self.ch);
+                    self.read_char();
+                }
+                if self.ch != '}' || hex.is_empty() || hex.len() > 6 {
+                    return Err(self.string_error(
+                        "invalid unicode escape; use `\\u{XXXX}` with 1 to 6 hex digits"
+                            .to_string(),
+                        lineno,
+                        colno,
+                    ));
+                }
+                let cp = u32::from_str_radix(&hex, 16).unwrap_or(u32::MAX);
+                match char::from_u32(cp) {
+                    Some(c) => c,
+                    None => {
+                        return Err(self.string_error(
+                            format!("`\\u{{{hex}}}` is not a valid unicode code point"),
+                            lineno,
+                            colno,
+                        ))
+                    }
+                }
+            }
+            '\0' if self.is_at_eof() => {
+                return Err(self.string_error(
+                    "unterminated escape sequence".to_string(),
+                    lineno,
+                    colno,
+                ))
+            }
+            other => {
+                return Err(self.string_error(
+                    format!("unknown escape sequence `\\{other}`"),
+                    lineno,
+                    colno,
+                ))
+            }
+        };
+        self.read_char();
+        Ok(c)
+    }
+
+    /// Read a string literal starting at the current `"` (or `r"` for raw strings);
+    /// `"..."` and `"""..."""` understand escape sequences, raw strings (`r"..."`) do not.
+    fn read_string(&mut self, raw: bool) -> Result<Token, LexerError> {
         let colno = self.colno;
         let lineno = self.lineno;
+        if raw {
+            self.read_char();
+        }
+        let triple = self.is_triple_quote();
 
-        loop {
+        self.read_char();
+        if triple {
+            self.read_char();
             self.read_char();
-            if self.ch == '"' || self.ch == '\0' {
+        }
+
+        self.read_string_part(raw, triple, true, colno, lineno)
+    }
+
+    /// Continue a string literal after the `}` closing an interpolation
+    fn continue_string(&mut self) -> Result<Token, LexerError> {
+        let colno = self.colno;
+        let lineno = self.lineno;
+        let interp = self.interps.pop().unwrap_or_default();
+        self.read_char();
+
+        self.read_string_part(false, interp.triple, false, colno, lineno)
+    }
+
+    /// Read the contents of a string literal up to the closing quote or an
+    /// interpolation `{`. The token type tells which part of the literal it is:
+    /// a plain `String`, or `InterpStart` / `InterpMid` / `InterpEnd` for strings with
+    /// `{expr}` in them; `{{` and `}}` are literal braces.
+    /// If the literal has a bad escape sequence, the rest of it is still consumed
+    fn read_string_part(
+        &mut self,
+        raw: bool,
+        triple: bool,
+        first: bool,
+        colno: usize,
+        lineno: usize,
+    ) -> Result<Token, LexerError> {
+        let mut value = String::new();
+        let mut ttype = if first {
+            TokenType::String
+        } else {
+            TokenType::InterpEnd
+        };
+        let mut error: Option<LexerError> = None;
+
+        loop {
+            if self.is_at_eof() {
+                return Err(self.string_error(
+                    "unterminated string literal".to_string(),
+                    lineno,
+                    colno,
+                ));
+            }
+
+            if triple && self.is_triple_quote() {
+                self.read_char();
+                self.read_char();
+                self.read_char();
+                break;
+            } else if !triple && self.ch == '"' {
+                self.read_char();
                 break;
             }
-        }
 
-        let slit = &self.charlist[pos..self.pos];
+            if !raw && self.ch == '{' && self.peek() != '{' {
+                self.read_char();
+                self.interps.push(Interp { depth: 0, triple });
+                ttype = if first {
+                    TokenType::InterpStart
+                } else {
+                    TokenType::InterpMid
+                };
+                break;
+            } else if !raw && (self.ch == '{' || self.ch == '}') && self.peek() == self.ch {
+                value.push(self.ch);
+                self.read_char();
+                self.read_char();
+            } else if self.ch == '\\' && !raw {
+                match self.read_escape() {
+                    Ok(c) => value.push(c),
+                    Err(e) => {
+                        if error.is_none() {
+                            error = Some(e);
+                        }
+                    }
+                }
+            } else {
+                value.push(self.ch);
+                self.read_char();
+            }
+        }
 
-        Token {
-            ttype: TokenType::String,
-            literal: charlist_to_string(slit),
-            colno,
-            lineno,
+        if let Some(e) = error {
+            return Err(e);
         }
+
+        Ok(Token::new(ttype, value, colno, lineno))
     }
 
     pub fn read_char(&mut self) {
+        if self.ch == '\n' {
+            self.lineno += 1;
+            self.colno = 0;
+        }
+
         if self.read_pos >= self.charlist.len() {
             self.ch = '\0';
         } else {
@@ -130,31 +497,225 @@ impl<'a> Lexer<'a> {
         self.colno += 1;
     }
 
-    fn skip_whitespaces(&mut self) {
-        while self.ch.is_whitespace() {
-            if self.ch == '\n' {
-                self.lineno += 1;
-            }
+    fn skip_comment(&mut self) {
+        while self.ch != '\n' && !self.is_at_eof() {
             self.read_char();
         }
     }
 
-    fn skip_comment(&mut self) {
+    /// Skip a `#[ ... ]#` block comment; block comments can be nested
+    fn skip_block_comment(&mut self) -> Result<(), LexerError> {
+        let (colno, lineno) = (self.colno, self.lineno);
+        let mut depth = 0;
+
         loop {
-            if self.peek() == '\n' || self.peek() == '\0' {
-                break;
+            if self.is_at_eof() {
+                return Err(LexerError {
+                    token: Some(Box::new(Token::new(
+                        TokenType::Illegal,
+                        "#[".to_string(),
+                        colno,
+                        lineno,
+                    ))),
+                    msg: "unterminated block comment".to_string(),
+                    lineno,
+                    colno,
+                });
             }
 
+            if self.ch == '#' && self.peek() == '[' {
+                depth += 1;
+                self.read_char();
+            } else if self.ch == ']' && self.peek() == '#' {
+                depth -= 1;
+                self.read_char();
+                if depth == 0 {
+                    self.read_char();
+                    return Ok(());
+                }
+            }
             self.read_char();
         }
+    }
+
+    fn is_newline(&self) -> bool {
+        self.ch == '\n' || (self.ch == '\r' && self.peek() == '\n')
+    }
+
+    /// Skip whitespace and comments (`# ...` and `#[ ... ]#`) before the next token;
+    /// doc comments (`## ...`) are not skipped, they are tokens.
+    /// With `trailing`, stop after the first line break.
+    fn skip_trivia(&mut self, trailing: bool) -> Result<(), LexerError> {
+        loop {
+            let start = self.pos;
+            let kind = if self.is_newline() {
+                if self.ch == '\r' {
+                    self.read_char();
+                }
+                self.read_char();
+                TriviaKind::Newline
+            } else if self.ch.is_whitespace() {
+                while self.ch.is_whitespace() && !self.is_newline() {
+                    self.read_char();
+                }
+                TriviaKind::Whitespace
+            } else if self.ch == '#' && self.peek() == '[' {
+                self.skip_block_comment()?;
+                TriviaKind::BlockComment
+            } else if self.ch == '#' && self.peek() != '#' {
+                self.skip_comment();
+                TriviaKind::Comment
+            } else {
+                return Ok(());
+            };
+
+            if self.lossless {
+                self.trivia.push((kind, start..self.pos));
+            }
+            if trailing && kind == TriviaKind::Newline {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Read a `## ...` doc comment line, the literal is the text after `## `
+    fn read_doc_comment(&mut self) -> Token {
+        let (colno, lineno) = (self.colno, self.lineno);
+        self.read_char();
         self.read_char();
-        self.skip_whitespaces();
+        if self.ch == ' ' {
+            self.read_char();
+        }
+
+        let start = self.pos;
+        self.skip_comment();
+        let text = charlist_to_string(&self.charlist[start..self.pos]);
+
+        Token::new(
+            TokenType::DocComment,
+            text.trim_end().to_string(),
+            colno,
+            lineno,
+        )
+    }
+
+    fn read_string_token(&mut self, raw: bool) -> Result<Token, LexerError> {
+        let (start, colno, lineno) = (self.pos, self.colno, self.lineno);
+        let res = if self.ch == '}' {
+            self.continue_string()
+        } else {
+            self.read_string(raw)
+        };
+        res.map_err(|mut e| {
+            e.token = Some(Box::new(Token::new(
+                TokenType::Illegal,
+                charlist_to_string(&self.charlist[start..self.pos]),
+                colno,
+                lineno,
+            )));
+            e
+        })
+    }
+
+    /// 1-based line and column of the char at `pos`
+    fn line_col(&self, pos: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&s| s <= pos);
+        (line, pos - self.line_starts[line - 1] + 1)
+    }
+
+    fn span(&self, range: Range<usize>) -> Span {
+        let end = range.end.min(self.charlist.len());
+        let start = range.start.min(end);
+        let (line, col) = self.line_col(start);
+        let (end_line, end_col) = self.line_col(end);
+        Span {
+            start: self.offsets[start],
+            end: self.offsets[end],
+            char_start: start,
+            char_end: end,
+            line,
+            col,
+            end_line,
+            end_col,
+        }
     }
 
     pub fn next_token(&mut self) -> Result<Rc<Token>, LexerError> {
-        self.skip_whitespaces();
+        if let Some(e) = self.pending.take() {
+            return Err(e);
+        }
+        self.skip_trivia(false)?;
+        self.start = self.pos;
+
+        match self.read_token() {
+            Ok(mut tok) => {
+                tok.span = self.span(self.start..self.pos);
+                Ok(Rc::new(tok))
+            }
+            Err(mut e) => {
+                if let Some(tok) = e.token.as_mut() {
+                    tok.span = self.span(self.start..self.pos);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Like `next_token`, but the token comes with its source text and the
+    /// whitespace and comments around it, see `LosslessToken`
+    pub fn next_lossless_token(&mut self) -> Result<LosslessToken<'a>, LexerError> {
+        self.lossless = true;
+        self.trivia.clear();
+
+        let token = self.next_token()?;
+        let leading = self.take_trivia();
+        if token.ttype != TokenType::Eof {
+            self.skip_trivia(true)?;
+        }
+        let trailing = self.take_trivia();
+
+        Ok(LosslessToken {
+            text: &self.input[token.span.start..token.span.end],
+            token,
+            leading,
+            trailing,
+        })
+    }
+
+    /// Every token of the input, up to and including `Eof`, see `next_lossless_token`
+    pub fn lossless_tokens(mut self) -> Result<Vec<LosslessToken<'a>>, LexerError> {
+        let mut tokens = Vec::new();
+        loop {
+            let tok = self.next_lossless_token()?;
+            let eof = tok.token.ttype == TokenType::Eof;
+            tokens.push(tok);
+            if eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn take_trivia(&mut self) -> Vec<Trivia<'a>> {
+        let trivia = std::mem::take(&mut self.trivia);
+        trivia
+            .into_iter()
+            .map(|(kind, range)| {
+                let span = self.span(range);
+                Trivia {
+                    kind,
+                    text: &self.input[span.start..span.end],
+                    span,
+                }
+            })
+            .collect()
+    }
+
+    fn read_token(&mut self) -> Result<Token, LexerError> {
         if self.ch == '#' {
-            self.skip_comment();
+            return Ok(self.read_doc_comment());
+        }
+        if self.ch == '}' && self.interps.last().is_some_and(|i| i.depth == 0) {
+            return self.read_string_token(false);
         }
         let result: Token;
         match self.ch {
@@ -264,6 +825,9 @@ impl<'a> Lexer<'a> {
                 )
             }
             '{' => {
+                if let Some(i) = self.interps.last_mut() {
+                    i.depth += 1;
+                }
                 result = Token::new(
                     TokenType::Lbrace,
                     self.ch.to_string(),
@@ -272,6 +836,9 @@ impl<'a> Lexer<'a> {
                 )
             }
             '}' => {
+                if let Some(i) = self.interps.last_mut() {
+                    i.depth -= 1;
+                }
                 result = Token::new(
                     TokenType::Rbrace,
                     self.ch.to_string(),
@@ -318,7 +885,7 @@ impl<'a> Lexer<'a> {
                     )
                 }
             }
-            '"' => result = self.read_string(),
+            '"' => return self.read_string_token(false),
 
             ':' => {
                 result = Token::new(
@@ -336,28 +903,36 @@ impl<'a> Lexer<'a> {
             }
 
             _ => {
-                if self.ch.is_ascii_digit() || is_bn_num(self.ch) {
-                    let raw_number = self.read_number();
-                    if let Some(n) = raw_number {
-                        return Ok(Rc::new(n));
-                    } else {
-                        return Err(LexerError {
-                            token: None,
-                            msg: format!("Invalid number literal -> {raw_number:?}"),
-                        });
-                    }
-                } else if (self.ch.is_ascii_alphabetic() || is_bn_char(self.ch))
-                    && !is_bn_num(self.ch)
-                {
+                if self.pack.is_digit(self.ch) {
+                    let (start, colno, lineno) = (self.pos, self.colno, self.lineno);
+                    return match self.read_number() {
+                        Ok(n) => Ok(n),
+                        Err(mut e) => {
+                            // skip rest of the malformed literal
+                            while self.is_number_tail(self.ch) {
+                                self.read_char();
+                            }
+                            e.token = Some(Box::new(Token::new(
+                                TokenType::Illegal,
+                                charlist_to_string(&self.charlist[start..self.pos]),
+                                colno,
+                                lineno,
+                            )));
+                            Err(e)
+                        }
+                    };
+                } else if self.ch == 'r' && self.peek() == '"' {
+                    return self.read_string_token(true);
+                } else if self.pack.is_ident_start(self.ch) {
                     let colno = self.colno;
                     let lineno = self.lineno;
                     let id = self.read_identifier();
 
-                    if let Some(kw) = lookup_ident(id.as_str()) {
-                        return Ok(Rc::new(Token::new(kw, id, colno, lineno)));
+                    if let Some(kw) = self.pack.lookup(id.as_str()) {
+                        return Ok(Token::new(kw, id, colno, lineno));
                     }
 
-                    return Ok(Rc::new(Token::new(TokenType::Ident, id, colno, lineno)));
+                    return Ok(Token::new(TokenType::Ident, id, colno, lineno));
                 } else {
                     result = Token::new(
                         TokenType::Illegal,
@@ -370,6 +945,6 @@ impl<'a> Lexer<'a> {
             }
         };
         self.read_char();
-        Ok(Rc::new(result))
+        Ok(result)
     }
 }
@@ -2,8 +2,16 @@ pub mod ast;
 pub mod bn;
 pub mod compiler;
 pub mod errorhelper;
+pub mod export;
+pub mod formatter;
+pub mod json;
+pub mod langpack;
 pub mod lexer;
+pub mod lsp;
 pub mod obj;
+pub mod optimizer;
 pub mod parser;
 pub mod token;
+pub mod translit;
+pub mod unicode;
 pub mod vm;
@@ -0,0 +1,381 @@
+//! What the language server knows about one document: its errors, the
+//! bindings it defines and every place they are used.
+//!
+//! Names are resolved with the compiler's own `symtab::Table`, walking the
+//! program the way `Compiler` does, so anything reported as undefined here
+//! is a name the compiler would reject too.
+
+use std::{collections::HashMap, rc::Rc, sync::Arc};
+
+use crate::{
+    ast::{
+        visit::{walk_expr, walk_stmt},
+        Expr, FuncExpr, Program, Stmt, Visitor,
+    },
+    compiler::symtab::{Scope, Table},
+    errorhelper::ParserError,
+    langpack::LangPack,
+    lexer::Lexer,
+    obj::builtins::BUILTINS,
+    parser::Parser,
+    token::{Span, Token, TokenType},
+};
+
+/// Kind of value a binding holds, as far as it can be told without running
+/// the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Number,
+    String,
+    Bool,
+    Array,
+    Hash,
+    Function { params: Vec<String> },
+    Unknown,
+}
+
+/// Where a binding is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Global,
+    Local,
+    Param,
+    Builtin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub name: String,
+    pub kind: Kind,
+    pub role: Role,
+    /// The defining identifier; empty for builtins
+    pub span: Span,
+    /// The whole definition: the `let` statement, or the parameter
+    pub range: Span,
+    pub doc: Option<String>,
+    /// Innermost named function the binding is defined in
+    pub parent: Option<usize>,
+}
+
+/// A use (or the definition) of a binding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub span: Span,
+    pub binding: usize,
+    /// How the compiler reaches the binding from here
+    pub scope: Scope,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub msg: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub pack: Arc<LangPack>,
+    pub bindings: Vec<Binding>,
+    pub refs: Vec<Reference>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Kind {
+    pub fn describe(&self) -> String {
+        match self {
+            Kind::Number => "number".to_string(),
+            Kind::String => "string".to_string(),
+            Kind::Bool => "bool".to_string(),
+            Kind::Array => "array".to_string(),
+            Kind::Hash => "hash".to_string(),
+            Kind::Function { params } => format!("function({})", params.join(", ")),
+            Kind::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+/// Span of the `colno`th char (1-based) of line `lineno`, for tokens made
+/// without one
+fn span_at(src: &str, lineno: usize, colno: usize) -> Span {
+    let line_start: usize = src
+        .split_inclusive('\n')
+        .take(lineno.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let char_start = src[..line_start].chars().count() + colno.saturating_sub(1);
+    let start = src
+        .char_indices()
+        .nth(char_start)
+        .map_or(src.len(), |(i, _)| i);
+    Span {
+        start,
+        end: start,
+        char_start,
+        char_end: char_start,
+        line: lineno,
+        col: colno,
+        end_line: lineno,
+        end_col: colno,
+    }
+}
+
+fn error_span(src: &str, err: &ParserError) -> Span {
+    match &err.token {
+        Some(t) if t.span != Span::EMPTY || t.lineno == 0 => t.span,
+        Some(t) => span_at(src, t.lineno, t.colno),
+        None => Span::EMPTY,
+    }
+}
+
+/// One function (or the top level) being walked
+struct Frame {
+    table: Table,
+    names: HashMap<String, usize>,
+    parent: Option<usize>,
+}
+
+struct Resolver<'a> {
+    frames: Vec<Frame>,
+    out: &'a mut Analysis,
+}
+
+impl Resolver<'_> {
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no scope")
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.frames
+            .iter()
+            .rev()
+            .find_map(|f| f.names.get(name).copied())
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        (span, range): (Span, Span),
+        kind: Kind,
+        role: Role,
+        doc: Option<String>,
+    ) {
+        let sym = self.frame().table.define(name);
+        let binding = self.out.bindings.len();
+        let parent = self.frame().parent;
+        self.out.bindings.push(Binding {
+            name: name.to_string(),
+            kind,
+            role,
+            span,
+            range,
+            doc,
+            parent,
+        });
+        self.frame().names.insert(name.to_string(), binding);
+        self.out.refs.push(Reference {
+            span,
+            binding,
+            scope: sym.scope.clone(),
+        });
+    }
+
+    fn resolve(&mut self, name: &str, token: &Token) {
+        // `module.name` comes from an include
+        if name.contains('.') {
+            return;
+        }
+        let found = self
+            .frame()
+            .table
+            .resolve(name.to_string())
+            .ok()
+            .zip(self.lookup(name));
+        match found {
+            Some((sym, binding)) => self.out.refs.push(Reference {
+                span: token.span,
+                binding,
+                scope: sym.scope.clone(),
+            }),
+            None => self.out.diagnostics.push(Diagnostic {
+                span: token.span,
+                severity: Severity::Error,
+                msg: format!("undefined variable {name}"),
+            }),
+        }
+    }
+
+    fn infer(&self, expr: &Expr) -> Kind {
+        match expr {
+            Expr::NumExpr { .. } => Kind::Number,
+            Expr::StringExpr { .. } | Expr::InterpExpr { .. } => Kind::String,
+            Expr::BoolExpr { .. } => Kind::Bool,
+            Expr::ArrayExpr { .. } => Kind::Array,
+            Expr::HashExpr { .. } => Kind::Hash,
+            Expr::FuncExpr(f) => Kind::Function {
+                params: f.params.iter().map(|p| p.name.clone()).collect(),
+            },
+            Expr::IdentExpr { value, .. } => self
+                .lookup(value)
+                .map_or(Kind::Unknown, |b| self.out.bindings[b].kind.clone()),
+            Expr::PrefixExpr { op, right, .. } => match op.ttype {
+                TokenType::BANG => Kind::Bool,
+                _ => self.infer(right),
+            },
+            Expr::InfixExpr {
+                left, op, right, ..
+            } => match op.ttype {
+                TokenType::EqEq
+                | TokenType::NotEq
+                | TokenType::LT
+                | TokenType::LTE
+                | TokenType::GT
+                | TokenType::GTE
+                | TokenType::And
+                | TokenType::Or => Kind::Bool,
+                _ => match (self.infer(left), self.infer(right)) {
+                    (Kind::String, _) | (_, Kind::String) if op.ttype == TokenType::Plus => {
+                        Kind::String
+                    }
+                    (Kind::Number, Kind::Number) => Kind::Number,
+                    _ => Kind::Unknown,
+                },
+            },
+            _ => Kind::Unknown,
+        }
+    }
+}
+
+impl Visitor for Resolver<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::LetStmt {
+            name, value, doc, ..
+        } = stmt
+        else {
+            return walk_stmt(self, stmt);
+        };
+        let kind = self.infer(value);
+        let role = if self.frames.len() == 1 {
+            Role::Global
+        } else {
+            Role::Local
+        };
+        self.define(
+            &name.name,
+            (name.token.span, stmt.span()),
+            kind,
+            role,
+            doc.clone(),
+        );
+        self.visit_expr(value);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::IdentExpr {
+                token,
+                span: _,
+                value,
+            } => self.resolve(value, token),
+            _ => walk_expr(self, expr),
+        }
+    }
+
+    fn visit_func(&mut self, f: &FuncExpr) {
+        let own = (!f.name.is_empty()).then(|| self.lookup(&f.name)).flatten();
+        let outer = Rc::new(self.frame().table.clone());
+        let parent = own.or(self.frame().parent);
+        self.frames.push(Frame {
+            table: Table::new_enclosed_noref(outer),
+            names: HashMap::new(),
+            parent,
+        });
+        if let Some(own) = own {
+            self.frame().table.define_func(f.name.clone());
+            self.frame().names.insert(f.name.clone(), own);
+        }
+        for p in f.params.iter() {
+            let span = p.token.span;
+            self.define(&p.name, (span, span), Kind::Unknown, Role::Param, None);
+        }
+        self.visit_stmt(&f.body);
+        self.frames.pop();
+    }
+}
+
+impl Analysis {
+    /// Parse and resolve `src`. The language pack is the one given, else the
+    /// one named by the file's pragma.
+    pub fn new(src: &str, pack: Option<Arc<LangPack>>) -> Analysis {
+        let lexer = match pack {
+            Some(pack) => Lexer::with_pack(src, pack),
+            None => Lexer::new(src),
+        };
+        let pack = lexer.pack().clone();
+        let mut parser = Parser::new(lexer);
+        let prog = parser.parse_program_partial();
+
+        let mut out = Analysis {
+            pack,
+            bindings: Vec::new(),
+            refs: Vec::new(),
+            diagnostics: parser
+                .errors
+                .iter()
+                .map(|e| Diagnostic {
+                    span: error_span(src, e),
+                    severity: Severity::Error,
+                    msg: e.msg.clone(),
+                })
+                .collect(),
+        };
+        out.resolve(&prog);
+        out
+    }
+
+    fn resolve(&mut self, prog: &Program) {
+        let mut table = Table::new();
+        let mut names = HashMap::new();
+        for (i, b) in BUILTINS.iter().enumerate() {
+            table.define_builtin(i, b.name);
+            names.insert(b.name.to_string(), self.bindings.len());
+            self.bindings.push(Binding {
+                name: b.name.to_string(),
+                kind: Kind::Function { params: Vec::new() },
+                role: Role::Builtin,
+                span: Span::EMPTY,
+                range: Span::EMPTY,
+                doc: None,
+                parent: None,
+            });
+        }
+
+        let mut r = Resolver {
+            frames: vec![Frame {
+                table,
+                names,
+                parent: None,
+            }],
+            out: self,
+        };
+        r.visit_program(prog);
+    }
+
+    /// Reference at byte offset `offset`
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.refs
+            .iter()
+            .find(|r| r.span.start <= offset && offset <= r.span.end)
+    }
+
+    /// Every use of `binding`, its definition included
+    pub fn references(&self, binding: usize) -> impl Iterator<Item = &Reference> {
+        self.refs.iter().filter(move |r| r.binding == binding)
+    }
+}
@@ -0,0 +1,532 @@
+//! Language server for pras, spoken over stdio (`pras lsp`).
+//!
+//! Implements the parts of the Language Server Protocol editors use most:
+//! diagnostics, go to definition, hover, document symbols, keyword
+//! completion and rename. Documents are synced in full on every change and
+//! re-analysed from scratch; see [`analysis`].
+
+pub mod analysis;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    compiler::symtab::Scope,
+    json::Json,
+    langpack::LangPack,
+    token::{Span, TokenType},
+};
+use analysis::{Analysis, Binding, Kind, Role, Severity};
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// Line starts of a document, to turn byte offsets into LSP positions
+/// (zero-based line and UTF-16 column) and back
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex { starts }
+    }
+
+    /// `(line, character)` of byte offset `offset`
+    pub fn position(&self, text: &str, offset: usize) -> (usize, usize) {
+        let offset = offset.min(text.len());
+        let line = self.starts.partition_point(|&s| s <= offset) - 1;
+        let col = text[self.starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        (line, col)
+    }
+
+    /// Byte offset of `(line, character)`, clamped to the end of the line
+    pub fn offset(&self, text: &str, line: usize, character: usize) -> usize {
+        let Some(&start) = self.starts.get(line) else {
+            return text.len();
+        };
+        let end = self.starts.get(line + 1).map_or(text.len(), |&e| e - 1);
+        let mut units = 0;
+        for (i, c) in text[start..end].char_indices() {
+            if units >= character {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+}
+
+struct Document {
+    text: String,
+    lines: LineIndex,
+    analysis: Analysis,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        Document {
+            lines: LineIndex::new(&text),
+            analysis: Analysis::new(&text, None),
+            text,
+        }
+    }
+
+    fn position(&self, offset: usize) -> Json {
+        let (line, character) = self.lines.position(&self.text, offset);
+        Json::object([("line", line.into()), ("character", character.into())])
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::object([
+            ("start", self.position(span.start)),
+            ("end", self.position(span.end)),
+        ])
+    }
+
+    /// Byte offset of the `position` in request params
+    fn offset(&self, params: &Json) -> Option<usize> {
+        let line = params.path(&["position", "line"])?.as_usize()?;
+        let character = params.path(&["position", "character"])?.as_usize()?;
+        Some(self.lines.offset(&self.text, line, character))
+    }
+}
+
+/// Error response to a request
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    msg: String,
+}
+
+impl RpcError {
+    fn new(code: i64, msg: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            msg: msg.into(),
+        }
+    }
+}
+
+/// State of the server between messages
+#[derive(Default)]
+pub struct Server {
+    docs: HashMap<String, Document>,
+    initialized: bool,
+    shutdown: bool,
+    exit_code: Option<i32>,
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn response(id: Json, result: Result<Json, RpcError>) -> Json {
+    let body = match result {
+        Ok(v) => ("result", v),
+        Err(e) => (
+            "error",
+            Json::object([("code", e.code.into()), ("message", e.msg.into())]),
+        ),
+    };
+    Json::object([("jsonrpc", "2.0".into()), ("id", id), body])
+}
+
+/// LSP `SymbolKind` of a binding
+fn symbol_kind(kind: &Kind) -> usize {
+    match kind {
+        Kind::Function { .. } => 12,
+        Kind::String => 15,
+        Kind::Number => 16,
+        Kind::Bool => 17,
+        Kind::Array => 18,
+        Kind::Hash => 19,
+        Kind::Unknown => 13,
+    }
+}
+
+fn hover_text(b: &Binding, scope: &Scope) -> String {
+    let signature = match (&b.kind, b.role) {
+        (_, Role::Builtin) => format!("{}(...)", b.name),
+        (Kind::Function { params }, _) => format!("{}({})", b.name, params.join(", ")),
+        (kind, _) => format!("{}: {}", b.name, kind.describe()),
+    };
+    let role = match (b.role, scope) {
+        (Role::Builtin, _) => "builtin function",
+        (_, Scope::Free) => "captured from an enclosing function",
+        (_, Scope::Func) => "the function itself",
+        (Role::Global, _) => "global",
+        (Role::Local, _) => "local",
+        (Role::Param, _) => "parameter",
+    };
+    let mut text = format!("```pras\n{signature}\n```\n{role}");
+    if let Some(doc) = &b.doc {
+        text.push_str("\n\n---\n\n");
+        text.push_str(doc);
+    }
+    text
+}
+
+fn is_identifier(pack: &LangPack, name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| pack.is_ident_start(c))
+        && chars.all(|c| pack.is_ident_char(c))
+        && pack.lookup(name).is_none()
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exit code once the client sent `exit`: 0 after a `shutdown`, 1 if not
+    pub const fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Handle one message from the client, returning the messages (the
+    /// response, diagnostics) to send back
+    pub fn handle(&mut self, msg: &Json) -> Vec<Json> {
+        let method = msg.get("method").and_then(Json::as_str);
+        let params = msg.get("params").unwrap_or(&Json::Null);
+        let Some(method) = method else {
+            // a response to a request we never send, or garbage
+            return match msg.get("id") {
+                Some(id) if msg.get("result").is_none() && msg.get("error").is_none() => {
+                    vec![response(
+                        id.clone(),
+                        Err(RpcError::new(INVALID_REQUEST, "missing method")),
+                    )]
+                }
+                _ => Vec::new(),
+            };
+        };
+
+        let Some(id) = msg.get("id") else {
+            return self.notify(method, params);
+        };
+        let result = if self.shutdown {
+            Err(RpcError::new(INVALID_REQUEST, "server is shutting down"))
+        } else if !self.initialized && method != "initialize" {
+            Err(RpcError::new(
+                SERVER_NOT_INITIALIZED,
+                "server is not initialized",
+            ))
+        } else {
+            self.request(method, params)
+        };
+        vec![response(id.clone(), result)]
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .map(String::from);
+        match (method, uri) {
+            ("exit", _) => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                Vec::new()
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params
+                    .path(&["textDocument", "text"])
+                    .and_then(Json::as_str)
+                    .unwrap_or_default();
+                self.open(uri, text.to_string())
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // full sync: the last change holds the whole text
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|c| c.last())
+                    .and_then(|c| c.get("text"))
+                    .and_then(Json::as_str);
+                match text {
+                    Some(text) => self.open(uri, text.to_string()),
+                    None => Vec::new(),
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.docs.remove(&uri);
+                vec![notification(
+                    "textDocument/publishDiagnostics",
+                    Json::object([("uri", uri.into()), ("diagnostics", Json::Array(vec![]))]),
+                )]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn open(&mut self, uri: String, text: String) -> Vec<Json> {
+        let doc = Document::new(text);
+        let diagnostics = doc
+            .analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                let severity: usize = match d.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                };
+                Json::object([
+                    ("range", doc.range(d.span)),
+                    ("severity", severity.into()),
+                    ("source", "pras".into()),
+                    ("message", d.msg.as_str().into()),
+                ])
+            })
+            .collect();
+        self.docs.insert(uri.clone(), doc);
+        vec![notification(
+            "textDocument/publishDiagnostics",
+            Json::object([
+                ("uri", uri.into()),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        )]
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, RpcError> {
+        if method == "initialize" {
+            self.initialized = true;
+            return Ok(Self::capabilities());
+        }
+        if method == "shutdown" {
+            self.shutdown = true;
+            return Ok(Json::Null);
+        }
+
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing textDocument.uri"))?;
+        let doc = self
+            .docs
+            .get(uri)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("unknown document {uri}")))?;
+
+        match method {
+            "textDocument/documentSymbol" => Ok(Self::symbols(doc, None)),
+            "textDocument/completion" => Ok(Self::completion(doc)),
+            "textDocument/definition" | "textDocument/hover" | "textDocument/rename" => {
+                let offset = doc
+                    .offset(params)
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing position"))?;
+                let Some(r) = doc.analysis.reference_at(offset) else {
+                    return match method {
+                        "textDocument/rename" => {
+                            Err(RpcError::new(INVALID_PARAMS, "nothing to rename here"))
+                        }
+                        _ => Ok(Json::Null),
+                    };
+                };
+                let b = &doc.analysis.bindings[r.binding];
+                match method {
+                    "textDocument/definition" if b.role == Role::Builtin => Ok(Json::Null),
+                    "textDocument/definition" => Ok(Json::object([
+                        ("uri", uri.into()),
+                        ("range", doc.range(b.span)),
+                    ])),
+                    "textDocument/hover" => Ok(Json::object([
+                        (
+                            "contents",
+                            Json::object([
+                                ("kind", "markdown".into()),
+                                ("value", hover_text(b, &r.scope).into()),
+                            ]),
+                        ),
+                        ("range", doc.range(r.span)),
+                    ])),
+                    _ => Self::rename(doc, uri, r.binding, params),
+                }
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unsupported method {method}"),
+            )),
+        }
+    }
+
+    fn capabilities() -> Json {
+        Json::object([
+            (
+                "capabilities",
+                Json::object([
+                    // full document sync
+                    ("textDocumentSync", 1usize.into()),
+                    ("definitionProvider", true.into()),
+                    ("hoverProvider", true.into()),
+                    ("documentSymbolProvider", true.into()),
+                    ("completionProvider", Json::object([])),
+                    ("renameProvider", true.into()),
+                ]),
+            ),
+            (
+                "serverInfo",
+                Json::object([
+                    ("name", "pras".into()),
+                    ("version", env!("CARGO_PKG_VERSION").into()),
+                ]),
+            ),
+        ])
+    }
+
+    /// Bindings defined directly in function `parent` (or at the top level),
+    /// as nested `DocumentSymbol`s
+    fn symbols(doc: &Document, parent: Option<usize>) -> Json {
+        let bindings = &doc.analysis.bindings;
+        let symbols = bindings
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.parent == parent && matches!(b.role, Role::Global | Role::Local))
+            .map(|(i, b)| {
+                Json::object([
+                    ("name", b.name.as_str().into()),
+                    ("detail", b.kind.describe().into()),
+                    ("kind", symbol_kind(&b.kind).into()),
+                    ("range", doc.range(b.range)),
+                    ("selectionRange", doc.range(b.span)),
+                    ("children", Self::symbols(doc, Some(i))),
+                ])
+            })
+            .collect();
+        Json::Array(symbols)
+    }
+
+    /// Every keyword spelling the document's language pack accepts, then
+    /// the builtins and global bindings
+    fn completion(doc: &Document) -> Json {
+        let pack = &doc.analysis.pack;
+        let mut keywords: Vec<(&str, TokenType)> = pack.spellings().collect();
+        keywords.sort_by_key(|(s, tt)| (LangPack::english_spelling(tt), *s));
+
+        let keywords = keywords.into_iter().map(|(spelling, tt)| {
+            Json::object([
+                ("label", spelling.into()),
+                // CompletionItemKind.Keyword
+                ("kind", 14usize.into()),
+                ("detail", LangPack::english_spelling(&tt).into()),
+            ])
+        });
+        let names = doc
+            .analysis
+            .bindings
+            .iter()
+            .filter(|b| matches!(b.role, Role::Builtin | Role::Global))
+            .map(|b| {
+                let kind: usize = match b.kind {
+                    // Function, Variable
+                    Kind::Function { .. } => 3,
+                    _ => 6,
+                };
+                Json::object([
+                    ("label", b.name.as_str().into()),
+                    ("kind", kind.into()),
+                    ("detail", b.kind.describe().into()),
+                ])
+            });
+        Json::Array(keywords.chain(names).collect())
+    }
+
+    fn rename(doc: &Document, uri: &str, binding: usize, params: &Json) -> Result<Json, RpcError> {
+        let new_name = params
+            .get("newName")
+            .and_then(Json::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing newName"))?;
+        let b = &doc.analysis.bindings[binding];
+        if b.role == Role::Builtin {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("cannot rename builtin {}", b.name),
+            ));
+        }
+        if !is_identifier(&doc.analysis.pack, new_name) {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("`{new_name}` is not a valid name"),
+            ));
+        }
+
+        let edits = doc
+            .analysis
+            .references(binding)
+            .map(|r| Json::object([("range", doc.range(r.span)), ("newText", new_name.into())]))
+            .collect();
+        Ok(Json::object([(
+            "changes",
+            Json::Object(vec![(uri.to_string(), Json::Array(edits))]),
+        )]))
+    }
+}
+
+/// Read one `Content-Length` framed message; `None` at the end of input
+pub fn read_message(r: &mut impl BufRead) -> io::Result<Option<Result<Json, String>>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(len) = len else {
+        return Ok(Some(Err("missing Content-Length header".to_string())));
+    };
+
+    let mut body = vec![0; len];
+    r.read_exact(&mut body)?;
+    let body =
+        String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some(Json::parse(&body).map_err(|e| e.to_string())))
+}
+
+pub fn write_message(w: &mut impl Write, msg: &Json) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(w, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    w.flush()
+}
+
+/// Serve one client until it sends `exit` or closes the input. Returns the
+/// exit code.
+pub fn run(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+    let mut server = Server::new();
+    while let Some(msg) = read_message(input)? {
+        let replies = match msg {
+            Ok(msg) => server.handle(&msg),
+            Err(e) => vec![response(Json::Null, Err(RpcError::new(PARSE_ERROR, e)))],
+        };
+        for reply in &replies {
+            write_message(output, reply)?;
+        }
+        if let Some(code) = server.exit_code() {
+            return Ok(code);
+        }
+    }
+    Ok(1)
+}
@@ -1,131 +1,437 @@
-use std::process::exit;
+use std::{
+    env, fs, io,
+    process::exit,
+    time::{Duration, Instant},
+};
 
+use pras::bn::{is_bn_source, NumeralStyle};
+use pras::compiler::disasm::disassemble;
+use pras::compiler::peephole::peephole;
 use pras::compiler::Compiler;
-//use pras::{compiler::Compiler, lexer::Lexer, parser::Parser, vm::Vm};
+use pras::errorhelper::ErrorHelper;
+use pras::export::{bytecode_json, program_json, tokens_json};
+use pras::formatter::{format_source, FmtOptions};
+use pras::langpack::LangPack;
 use pras::lexer::Lexer;
+use pras::lsp;
+use pras::optimizer::optimize;
 use pras::parser::Parser;
+use pras::token::TokenType;
+use pras::translit::{translit, DigitStyle, KeywordStyle, TranslitOptions};
 use pras::vm::Vm;
 
-fn main() {
-    let fib = "
-    dhori fib = ekti kaj(x)
-        jodi (x == 0) tahole
-            ferao(0)
-        nahole 
-            jodi (x==1) tahole
-                ferao(1)
-            nahole 
-                ferao(fib(x-1) + fib(x-2))
-            sesh 
-        sesh 
-    sesh
-    dekhao(fib(10))
-        ";
-
-    let fib_x = " 
-    dhori fib = ekti kaj(x)
-        dhori i = 0
-        dhori x = 0
-        dhori y = 1
-        dhori z = 0
-
-        while i < x 
-            dhori z = x + y
-            dhori x = y 
-            dhori z = z
-            dhori i = i + 1 
-        sesh 
-    sesh
-    
-    fib(10)";
-
-    let y = "
-        dhori a = ekti kaj() dhori a = 1; ferao(a) sesh; dekhao(a())
-    ";
-
-    let yy = "
-        dhori i = 0
-        while 10 > i:
-
-            dekhao(i);
-            dhori i = i + 1;
-
-        sesh
-    ";
-
-    let x = "dhori addr = ekti kaj(a,b)
-            ekti kaj(c)
-                ferao(a + b + c)
-            sesh
-          sesh
-          let newaddr = addr(1,2)
-          addr(8)
-          ";
-
-    //let a = Lexer::new("ekti kaj(x) x+1 sesh(2)");
-    //let a = Lexer::new("ekti kaj(x) 100 + x * 9 sesh(9) ");
-    let a = Lexer::new(x);
-    let mut parser = Parser::new(a);
-    let parsed_program = parser.parse_program();
-
-    //println!("{parsed_program:?}");
-    //println!("{}", parsed_program.expect());
-    if let Ok(ast) = parsed_program {
-        println!("pX->{ast}");
-        let mut comp = Compiler::new();
-        comp.compile(ast);
-        println!("{}", comp.bytecode().instructions);
-
-        for item in &comp.bytecode().constants {
-            println!("{}", item)
+const USAGE: &str = "usage: pras [run] <file> [-O0] [--digits=en|bn|auto] [--lang=<pack>]
+                  [--fuel=<n>] [--timeout=<ms>] [--memory=<mb>]
+       pras translit <file> --to=en|banglish|bn [--digits=en|bn] [--lang=<pack>]
+       pras fmt <file>... [--check | --write] [--width=<n>] [--lang=<pack>]
+       pras tokens <file> [--json] [--lang=<pack>]
+       pras ast <file> [--json] [--lang=<pack>]
+       pras disasm <file> [-O0] [--json] [--lang=<pack>]
+       pras lsp        start a language server on stdin/stdout
+
+options:
+    -O0             compile the program as written, without folding constants,
+                    removing dead code or fusing instructions
+    --lang=<pack>   keyword language pack: bn, as, hi or a pack definition file
+                    (default: the `# lang: ...` pragma in the file, else bn)
+    --digits=en     print numbers with 0-9 (default)
+    --digits=bn     print numbers with ০-৯
+    --digits=auto   print numbers with ০-৯ if the program is written in Bengali script
+    --fuel=<n>      stop the program after it runs n instructions
+    --timeout=<ms>  stop the program after it runs for ms milliseconds
+    --memory=<mb>   stop the program if its strings, arrays, hashes and functions
+                    take more than mb megabytes
+
+translit options:
+    --to=en         rewrite keywords in English (`let`, `if`, ...)
+    --to=banglish   rewrite keywords in the romanised spelling (`dhori`, `jodi`, ...)
+    --to=bn         rewrite keywords in the language's script (`ধরি`, `যদি`, ...)
+    --digits=en|bn  also rewrite decimal number literals with 0-9 or the script's digits
+
+tokens, ast and disasm options:
+    --json          print JSON instead of text (schema: see src/export.rs)
+
+fmt options:
+    --check         do not print, exit with 1 if a file is not formatted
+    --write         rewrite the files in place instead of printing them
+    --width=<n>     wrap argument lists longer than n characters (default 80)
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DigitsOpt {
+    En,
+    Bn,
+    Auto,
+}
+
+fn read_source(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_else(|e| {
+        eprintln!("failed to read {filename} -> {e}");
+        exit(1);
+    })
+}
+
+fn parse_digits(opt: &str) -> DigitsOpt {
+    match opt {
+        "en" | "ascii" => DigitsOpt::En,
+        "bn" | "bengali" => DigitsOpt::Bn,
+        "auto" => DigitsOpt::Auto,
+        _ => {
+            eprintln!("unknown value for --digits -> {opt}\n\n{USAGE}");
+            exit(1);
         }
+    }
+}
 
-        //exit(0);
+fn parse_count(opt: &str, value: &str) -> u64 {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("{opt} needs a whole number -> {value}\n\n{USAGE}");
+        exit(1);
+    })
+}
 
-        let mut v = Vm::new(comp.bytecode());
-        v.run();
-        //println!("{:?} -> {:?}" , v.constants , v.stack);
-        println!("{:?}", v.last_pop());
-        //println!("{:?}" , v.top_stack());
-    } else {
-        //for err in &parser.errors {
-        //    println!("Err => {}", err.msg);
-        //}
+fn make_lexer<'a>(src: &'a str, lang: Option<&str>) -> Lexer<'a> {
+    match lang {
+        Some(l) => match LangPack::load(l) {
+            Ok(pack) => Lexer::with_pack(src, pack),
+            Err(e) => {
+                eprintln!("failed to load language pack -> {e}");
+                exit(1);
+            }
+        },
+        None => Lexer::new(src),
+    }
+}
+
+/// Whether to optimise (no `-O0`) and the other arguments
+fn split_opt_level(args: &[String]) -> (bool, Vec<String>) {
+    let mut optimize = true;
+    let mut rest = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-O0" => optimize = false,
+            "-O1" => optimize = true,
+            _ => rest.push(arg.clone()),
+        }
+    }
+    (optimize, rest)
+}
+
+fn cmd_run(args: &[String]) {
+    let (opt, args) = split_opt_level(args);
+    let mut filename: Option<&str> = None;
+    let mut digits = DigitsOpt::En;
+    let mut lang: Option<&str> = None;
+    let mut fuel: Option<u64> = None;
+    let mut timeout: Option<u64> = None;
+    let mut memory: Option<u64> = None;
+
+    for arg in &args {
+        if let Some(d) = arg.strip_prefix("--digits=") {
+            digits = parse_digits(d);
+        } else if let Some(n) = arg.strip_prefix("--fuel=") {
+            fuel = Some(parse_count("--fuel", n));
+        } else if let Some(ms) = arg.strip_prefix("--timeout=") {
+            timeout = Some(parse_count("--timeout", ms));
+        } else if let Some(mb) = arg.strip_prefix("--memory=") {
+            memory = Some(parse_count("--memory", mb));
+        } else if let Some(l) = arg.strip_prefix("--lang=") {
+            lang = Some(l);
+        } else if filename.is_none() && !arg.starts_with("--") {
+            filename = Some(arg);
+        } else {
+            eprintln!("unknown argument -> {arg}\n\n{USAGE}");
+            exit(1);
+        }
+    }
+
+    let Some(filename) = filename else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+
+    let src = read_source(filename);
+    let numerals = match digits {
+        DigitsOpt::En => NumeralStyle::Ascii,
+        DigitsOpt::Bn => NumeralStyle::Bengali,
+        DigitsOpt::Auto if is_bn_source(&src) => NumeralStyle::Bengali,
+        DigitsOpt::Auto => NumeralStyle::Ascii,
+    };
+
+    let mut parser = Parser::new(make_lexer(&src, lang));
+    let Ok(ast) = parser.parse_program() else {
         parser.print_errorrs();
-        //panic!("please fix parser errors");
         exit(1);
+    };
+
+    let ast = if opt { optimize(ast) } else { ast };
+    let mut comp = Compiler::new();
+    let bc = comp.compile(ast);
+    let bc = if opt { peephole(bc) } else { bc };
+
+    let mut v = Vm::new(bc);
+    v.set_numerals(numerals);
+    v.set_fuel(fuel);
+    v.set_memory_limit(memory.map(|mb| (mb as usize).saturating_mul(1 << 20)));
+    v.set_deadline(timeout.map(|ms| Instant::now() + Duration::from_millis(ms)));
+    if let Err(e) = v.run() {
+        if let Some(span) = e.span {
+            eprintln!("{}", ErrorHelper::new(&src).show_span(span));
+        }
+        eprintln!("{e}");
+        exit(1);
+    }
+}
+
+fn cmd_translit(args: &[String]) {
+    let mut filename: Option<&str> = None;
+    let mut keywords: Option<KeywordStyle> = None;
+    let mut digits: Option<DigitStyle> = None;
+    let mut lang: Option<&str> = None;
+
+    for arg in args {
+        if let Some(to) = arg.strip_prefix("--to=") {
+            keywords = Some(match to {
+                "en" | "english" => KeywordStyle::English,
+                "banglish" | "roman" => KeywordStyle::Roman,
+                "bn" | "native" => KeywordStyle::Native,
+                _ => {
+                    eprintln!("unknown value for --to -> {to}\n\n{USAGE}");
+                    exit(1);
+                }
+            });
+        } else if let Some(d) = arg.strip_prefix("--digits=") {
+            digits = Some(match parse_digits(d) {
+                DigitsOpt::En => DigitStyle::Ascii,
+                DigitsOpt::Bn => DigitStyle::Native,
+                DigitsOpt::Auto => {
+                    eprintln!("--digits=auto is not supported by translit\n\n{USAGE}");
+                    exit(1);
+                }
+            });
+        } else if let Some(l) = arg.strip_prefix("--lang=") {
+            lang = Some(l);
+        } else if filename.is_none() && !arg.starts_with("--") {
+            filename = Some(arg);
+        } else {
+            eprintln!("unknown argument -> {arg}\n\n{USAGE}");
+            exit(1);
+        }
     }
 
-    //  for item in &c.constants{
-    //     println!("con->{item}");
-    //}
-    //let mut v = Vm::new(c);
-    //v.run();
-    //println!("result->{}", v.last_pop());
+    let (Some(filename), Some(keywords)) = (filename, keywords) else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+
+    let src = read_source(filename);
+    let opts = TranslitOptions { keywords, digits };
+    match translit(make_lexer(&src, lang), opts) {
+        Ok(out) => print!("{out}"),
+        Err(e) => {
+            eprintln!("{filename}:{e}");
+            exit(1);
+        }
+    }
 }
 
-/*
-use std::{rc::Rc, cell::RefCell};
-const SS : usize = 10;
-fn main() {
-    let stack : Rc<RefCell<Vec<Rc<RefCell<String>>>>> = Rc::new(RefCell::new(Vec::with_capacity(SS)));
-    let x = ["a" , "b" , "c"];
+fn cmd_fmt(args: &[String]) {
+    let mut filenames: Vec<&str> = Vec::new();
+    let mut check = false;
+    let mut write = false;
+    let mut lang: Option<&str> = None;
+    let mut opts = FmtOptions::default();
 
-    for item in x{
-        (stack.borrow_mut()).push(Rc::new(RefCell::new(item.to_string())))
+    for arg in args {
+        if arg == "--check" {
+            check = true;
+        } else if arg == "--write" {
+            write = true;
+        } else if let Some(w) = arg.strip_prefix("--width=") {
+            opts.width = w.parse().unwrap_or_else(|_| {
+                eprintln!("invalid value for --width -> {w}\n\n{USAGE}");
+                exit(1);
+            });
+        } else if let Some(l) = arg.strip_prefix("--lang=") {
+            lang = Some(l);
+        } else if !arg.starts_with("--") {
+            filenames.push(arg);
+        } else {
+            eprintln!("unknown argument -> {arg}\n\n{USAGE}");
+            exit(1);
+        }
+    }
+
+    if filenames.is_empty() || (check && write) {
+        eprintln!("{USAGE}");
+        exit(1);
+    }
+
+    let pack = lang.map(|l| {
+        LangPack::load(l).unwrap_or_else(|e| {
+            eprintln!("failed to load language pack -> {e}");
+            exit(1);
+        })
+    });
+
+    let mut unformatted = false;
+    for filename in filenames {
+        let src = read_source(filename);
+        let out = match format_source(&src, pack.clone(), opts) {
+            Ok(out) => out,
+            Err(e) => {
+                eprintln!("{filename}: failed to parse\n{e}");
+                exit(1);
+            }
+        };
+
+        if check {
+            if out != src {
+                println!("would reformat {filename}");
+                unformatted = true;
+            }
+        } else if write {
+            if out != src {
+                fs::write(filename, out).unwrap_or_else(|e| {
+                    eprintln!("failed to write {filename} -> {e}");
+                    exit(1);
+                });
+            }
+        } else {
+            print!("{out}");
+        }
+    }
+
+    if unformatted {
+        exit(1);
+    }
+}
+
+/// Arguments of `tokens`, `ast` and `disasm`: the file, `--json` and `--lang`
+fn parse_stage_args(args: &[String]) -> (&str, bool, Option<&str>) {
+    let mut filename: Option<&str> = None;
+    let mut json = false;
+    let mut lang: Option<&str> = None;
+
+    for arg in args {
+        if arg == "--json" {
+            json = true;
+        } else if let Some(l) = arg.strip_prefix("--lang=") {
+            lang = Some(l);
+        } else if filename.is_none() && !arg.starts_with("--") {
+            filename = Some(arg);
+        } else {
+            eprintln!("unknown argument -> {arg}\n\n{USAGE}");
+            exit(1);
+        }
+    }
+
+    let Some(filename) = filename else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+    (filename, json, lang)
+}
+
+fn parse_or_exit(src: &str, lang: Option<&str>) -> pras::ast::Program {
+    let mut parser = Parser::new(make_lexer(src, lang));
+    let Ok(ast) = parser.parse_program() else {
+        parser.print_errorrs();
+        exit(1);
+    };
+    ast
+}
+
+fn cmd_tokens(args: &[String]) {
+    let (filename, json, lang) = parse_stage_args(args);
+    let src = read_source(filename);
+    let mut lexer = make_lexer(&src, lang);
+
+    if json {
+        match tokens_json(lexer) {
+            Ok(out) => println!("{out:#}"),
+            Err(e) => {
+                eprintln!("{filename}:{e}");
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    loop {
+        match lexer.next_token() {
+            Ok(tok) => {
+                println!(
+                    "{}:{} {:?} {}",
+                    tok.span.line, tok.span.col, tok.ttype, tok.literal
+                );
+                if tok.ttype == TokenType::Eof {
+                    break;
+                }
+            }
+            Err(e) => {
+                eprintln!("{filename}:{e}");
+                exit(1);
+            }
+        }
+    }
+}
+
+fn cmd_ast(args: &[String]) {
+    let (filename, json, lang) = parse_stage_args(args);
+    let src = read_source(filename);
+    let ast = parse_or_exit(&src, lang);
+
+    if json {
+        println!("{:#}", program_json(&ast));
+    } else {
+        for stmt in &ast.stmts {
+            println!("{stmt}");
+        }
     }
+}
 
+fn cmd_disasm(args: &[String]) {
+    let (opt, args) = split_opt_level(args);
+    let (filename, json, lang) = parse_stage_args(&args);
+    let src = read_source(filename);
+    let ast = parse_or_exit(&src, lang);
+    let ast = if opt { optimize(ast) } else { ast };
+    let bc = Compiler::new().compile(ast);
+    let bc = if opt { peephole(bc) } else { bc };
 
-    unsafe{
+    if json {
+        println!("{:#}", bytecode_json(&bc));
+        return;
+    }
 
-    let y = &stack.borrow_mut()[1];
-        let z = y.as_ptr();
-        *z= "l".to_string();
-            //Rc::new(RefCell::new("l".to_string()));
+    print!("{}", disassemble(&bc, Some(&src)));
+}
 
+fn cmd_lsp() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match lsp::run(&mut stdin.lock(), &mut stdout.lock()) {
+        Ok(code) => exit(code),
+        Err(e) => {
+            eprintln!("pras lsp: {e}");
+            exit(1);
+        }
     }
+}
 
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    println!("{:?}" , stack);
+    match args.first().map(|a| a.as_str()) {
+        None | Some("-h") | Some("--help") | Some("help") => print!("{USAGE}"),
+        Some("run") => cmd_run(&args[1..]),
+        Some("translit") => cmd_translit(&args[1..]),
+        Some("fmt") => cmd_fmt(&args[1..]),
+        Some("tokens") => cmd_tokens(&args[1..]),
+        Some("ast") => cmd_ast(&args[1..]),
+        Some("disasm") => cmd_disasm(&args[1..]),
+        Some("lsp") => cmd_lsp(),
+        Some(_) => cmd_run(&args),
+    }
 }
-*/
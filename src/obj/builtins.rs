@@ -0,0 +1,60 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::bn::{to_bn_num, NumeralStyle};
+
+use super::Object;
+
+pub type BuiltinFn = fn(&[Rc<Object>]) -> Rc<Object>;
+
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+}
+
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BUILTIN({})", self.name)
+    }
+}
+
+/// Builtin functions; the compiler defines them in this order,
+/// so the position here is the operand of `OpGetBuiltin`
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "bangla",
+        func: bi_bangla,
+    },
+    Builtin {
+        name: "বাংলা",
+        func: bi_bangla,
+    },
+];
+
+pub fn get_builtin(index: usize) -> Option<&'static Builtin> {
+    BUILTINS.get(index)
+}
+
+fn new_error(msg: String) -> Rc<Object> {
+    Rc::new(Object::Error {
+        token: None,
+        value: msg,
+    })
+}
+
+/// `bangla(x)` -> `x` as a string with numbers written in Bengali digits
+fn bi_bangla(args: &[Rc<Object>]) -> Rc<Object> {
+    if args.len() != 1 {
+        return new_error(format!(
+            "wrong number of arguments for bangla| W=>1 G=>{}",
+            args.len()
+        ));
+    }
+
+    let value = match args[0].as_ref() {
+        Object::String { token: _, value } => to_bn_num(value),
+        obj => obj.render(NumeralStyle::Bengali),
+    };
+
+    Rc::new(Object::String { token: None, value })
+}
@@ -80,7 +80,7 @@ impl Envmap {
     }
 
     pub fn get_default(&mut self) -> Result<&mut Env, bool> {
-        if let Some(e) = self.envs.get_mut(&DEFKEY.to_string()) {
+        if let Some(e) = self.envs.get_mut(DEFKEY) {
             return Ok(e);
         }
         Err(false)
@@ -1,18 +1,21 @@
 use std::{
+    cell::RefCell,
     collections::{hash_map::DefaultHasher, HashMap},
     fmt::Display,
     hash::{Hash, Hasher},
     rc::Rc,
 };
 
+pub mod builtins;
 pub mod env;
 use crate::{
     ast,
-    compiler::code::Instructions,
+    bn::{render_number, NumeralStyle},
+    compiler::code::{Instructions, LineTable},
     token::{self, Token},
 };
 
-use self::env::Env;
+use self::{builtins::Builtin, env::Env};
 
 pub const HASH_OBJ: u8 = 0;
 pub const NUMBER_OBJ: u8 = 1;
@@ -28,6 +31,7 @@ pub const INCLUDE_OBJ: u8 = 10;
 pub const SHOW_OBJ: u8 = 11;
 pub const COMPILED_FUNC_OBJ: u8 = 12;
 pub const CLOSURE_OBJ: u8 = 13;
+pub const BUILTIN_OBJ: u8 = 14;
 
 #[derive(Debug, Clone)]
 pub enum Object {
@@ -82,41 +86,22 @@ pub enum Object {
     Compfunc(Rc<CompFunc>),
 
     Closure(Rc<Closure>),
+
+    Builtin(Rc<Builtin>),
 }
 
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut result = String::new();
-        match self {
-            Self::Number { token: _, value } => result.push_str(&value.to_string()),
-            Self::String { token: _, value } => result.push_str(value),
-            Self::Bool { token: _, value } => result.push_str(&value.to_string()),
-            Self::Array { token: _, value } => {
-                for item in value.iter() {
-                    result.push_str(&(item.to_string() + " "))
-                }
-            }
-            Self::Null => result.push_str("null"),
-            Self::Hash { token: _, pairs } => {
-                //println!("{:?}" , pairs);
-                //for p in pairs.values(){
-                for (_, v) in pairs.iter() {
-                    result.push_str(format!("{v}, ").as_str())
-                    //result.push_str(format!("{}:{},", p.key , p.value).as_str())
-                }
-            }
-            Self::Closure(cl) => result = cl.to_string(),
-            Self::Compfunc(cf) => result = cf.to_string(),
-            _ => {}
-        }
-        write!(f, "{result}")
+        write!(f, "{}", self.render(NumeralStyle::Ascii))
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Default, Clone, PartialEq, Eq)]
 pub struct Closure {
     pub fun: Rc<CompFunc>,
-    pub frees: Vec<Rc<Object>>,
+    /// Set when the closure is made, except for the local being defined,
+    /// which goes in once it is set (`OpFillFrees`)
+    pub frees: RefCell<Vec<Rc<Object>>>,
 }
 
 impl Closure {
@@ -124,26 +109,43 @@ impl Closure {
         let l = fnin.ins.len();
         Self {
             fun: CompFunc::new(fnin, l).into(),
-            frees: Vec::new(),
+            frees: RefCell::new(Vec::new()),
         }
     }
 
     pub const fn new_from_cfn(fun: Rc<CompFunc>) -> Self {
-        Self { fun, frees: vec![] }
+        Self {
+            fun,
+            frees: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+// the frees can lead back to the closure itself
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure")
+            .field("fun", &self.fun)
+            .field("frees", &self.frees.borrow().len())
+            .finish()
     }
 }
 
 impl Display for Closure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "CL({}->{})", self.fun, self.frees.len())
+        write!(f, "CL({}->{})", self.fun, self.frees.borrow().len())
     }
 }
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CompFunc {
+    /// Name the function was bound to with `dhori`; empty if anonymous
+    pub name: String,
     pub fnin: Rc<Instructions>,
     pub num_locals: usize,
     pub num_params: usize,
     pub in_len: usize,
+    /// Source spans of `fnin`
+    pub lines: Rc<LineTable>,
 }
 
 impl Display for CompFunc {
@@ -155,10 +157,12 @@ impl Display for CompFunc {
 impl Default for CompFunc {
     fn default() -> Self {
         Self {
+            name: String::new(),
             fnin: Rc::new(Instructions::new()),
             num_locals: 0,
             num_params: 0,
             in_len: 0,
+            lines: Rc::new(LineTable::new()),
         }
     }
 }
@@ -166,10 +170,12 @@ impl Default for CompFunc {
 impl CompFunc {
     pub fn new(fnin: Rc<Instructions>, fn_len: usize) -> Self {
         Self {
+            name: String::new(),
             fnin,
             num_locals: 0,
             num_params: 0,
             in_len: fn_len,
+            lines: Rc::new(LineTable::new()),
         }
     }
 }
@@ -198,11 +204,52 @@ pub struct HashPair {
 
 impl Display for HashPair {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.key, self.value)
+        write!(f, "{}", self.render(NumeralStyle::Ascii))
+    }
+}
+
+impl HashPair {
+    pub fn render(&self, numerals: NumeralStyle) -> String {
+        format!(
+            "{}:{}",
+            self.key.render(numerals),
+            self.value.render(numerals)
+        )
     }
 }
 
 impl Object {
+    /// String representation of the object as shown to the user,
+    /// numbers (also inside arrays and hashes) are written with `numerals`
+    pub fn render(&self, numerals: NumeralStyle) -> String {
+        let mut result = String::new();
+        match self {
+            Self::Number { token: _, value } => result.push_str(&render_number(value, numerals)),
+            Self::String { token: _, value } => result.push_str(value),
+            Self::Bool { token: _, value } => result.push_str(&value.to_string()),
+            Self::Array { token: _, value } => {
+                for item in value.iter() {
+                    result.push_str(&(item.render(numerals) + " "))
+                }
+            }
+            Self::Null => result.push_str("null"),
+            Self::Hash { token: _, pairs } => {
+                //println!("{:?}" , pairs);
+                //for p in pairs.values(){
+                for (_, v) in pairs.iter() {
+                    result.push_str(&v.render(numerals));
+                    result.push_str(", ");
+                    //result.push_str(format!("{}:{},", p.key , p.value).as_str())
+                }
+            }
+            Self::Closure(cl) => result = cl.to_string(),
+            Self::Compfunc(cf) => result = cf.to_string(),
+            Self::Builtin(b) => result = b.to_string(),
+            _ => {}
+        }
+        result
+    }
+
     pub const fn hashable(&self) -> bool {
         matches!(
             self,
@@ -240,6 +287,7 @@ impl Object {
             Self::Error { .. } => ERR_OBJ,
             Self::Compfunc { .. } => COMPILED_FUNC_OBJ,
             Self::Closure { .. } => CLOSURE_OBJ,
+            Self::Builtin { .. } => BUILTIN_OBJ,
         }
     }
 }
@@ -260,7 +308,21 @@ impl Hash for Object {
 
 impl PartialEq for Object {
     fn eq(&self, other: &Self) -> bool {
-        self == other
+        match (self, other) {
+            (Self::Number { value: l, .. }, Self::Number { value: r, .. }) => l == r,
+            (Self::Bool { value: l, .. }, Self::Bool { value: r, .. }) => l == r,
+            (Self::String { value: l, .. }, Self::String { value: r, .. }) => l == r,
+            (Self::Array { value: l, .. }, Self::Array { value: r, .. }) => l == r,
+            (Self::Hash { pairs: l, .. }, Self::Hash { pairs: r, .. }) => l == r,
+            (Self::Null, Self::Null) => true,
+            (Self::ReturnValue { value: l, .. }, Self::ReturnValue { value: r, .. })
+            | (Self::Break { value: l, .. }, Self::Break { value: r, .. }) => l == r,
+            (Self::Error { value: l, .. }, Self::Error { value: r, .. }) => l == r,
+            (Self::Compfunc(l), Self::Compfunc(r)) => l == r,
+            (Self::Closure(l), Self::Closure(r)) => Rc::ptr_eq(l, r),
+            (Self::Builtin(l), Self::Builtin(r)) => l.name == r.name,
+            _ => false,
+        }
     }
 }
 
@@ -0,0 +1,326 @@
+//! Optimisations on the AST, run between the parser and the compiler
+//! unless `-O0` is given.
+//!
+//! - Operators applied to literals are computed here, the way the VM
+//!   would: `60 * 60 * 24` compiles to one constant, `"a" + "b"` to one
+//!   string and `!sotti` to `mittha`.
+//! - An `if` or `while` whose condition is a literal loses the block that
+//!   can never run; an `if` left with a single expression becomes that
+//!   expression.
+//! - Statements after `ferao` in a block are dropped.
+//!
+//! Nothing that could fail at run time is folded (`1 / 0`, overflow,
+//! operators the compiler does not support), so the program's behaviour,
+//! errors included, is the same with and without this pass. Code that
+//! defines names is only dropped after a `ferao` that ends a function
+//! body, as elsewhere the compiler may resolve a later use of the name
+//! whether or not the code defining it runs.
+
+use std::rc::Rc;
+
+use crate::{
+    ast::{
+        visit::{fold_expr, fold_func, fold_stmt, walk_stmt},
+        Expr, Fold, FuncExpr, Program, Stmt, Visitor,
+    },
+    obj::Object,
+    token::{NumberToken, Span, Token, TokenType},
+};
+
+/// The `Fold` that does the work; see the module docs
+#[derive(Debug, Default)]
+pub struct Optimizer;
+
+/// Finds `dhori` statements outside of nested functions
+#[derive(Default)]
+struct DefinesNames(bool);
+
+impl Visitor for DefinesNames {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if matches!(stmt, Stmt::LetStmt { .. }) {
+            self.0 = true;
+        }
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_func(&mut self, _: &FuncExpr) {}
+}
+
+/// The value of a literal, as the VM would have it
+fn literal(expr: &Expr) -> Option<Object> {
+    match expr {
+        Expr::NumExpr { value, .. } => Some(Object::Number {
+            token: None,
+            value: value.clone(),
+        }),
+        Expr::StringExpr { value, .. } => Some(Object::String {
+            token: None,
+            value: value.clone(),
+        }),
+        Expr::BoolExpr { value, .. } => Some(Object::Bool {
+            token: None,
+            value: *value,
+        }),
+        _ => None,
+    }
+}
+
+/// `l op r` for numbers; `None` where the VM would panic
+fn arith(op: TokenType, l: &NumberToken, r: &NumberToken) -> Option<NumberToken> {
+    if let (NumberToken::Int(a), NumberToken::Int(b)) = (l, r) {
+        let fits = match op {
+            TokenType::Plus => a.checked_add(*b),
+            TokenType::Minus => a.checked_sub(*b).and(b.checked_neg()),
+            TokenType::Mul => a.checked_mul(*b),
+            TokenType::Div => a.checked_div(*b),
+            TokenType::MOD => a.checked_rem(*b),
+            _ => None,
+        };
+        fits?;
+    }
+    let (l, r) = (l.clone(), r.clone());
+    match op {
+        TokenType::Plus => Some(l + r),
+        TokenType::Minus => Some(l - r),
+        TokenType::Mul => Some(l * r),
+        TokenType::Div => Some(l / r),
+        TokenType::MOD => Some(l % r),
+        _ => None,
+    }
+}
+
+fn num_expr(token: Rc<Token>, span: Span, value: NumberToken) -> Rc<Expr> {
+    Rc::new(Expr::NumExpr {
+        token,
+        span,
+        is_int: value.is_int(),
+        value,
+    })
+}
+
+fn bool_expr(token: Rc<Token>, span: Span, value: bool) -> Rc<Expr> {
+    Rc::new(Expr::BoolExpr { token, span, value })
+}
+
+fn empty_block(like: &Stmt) -> Rc<Stmt> {
+    let (token, span) = match like {
+        Stmt::BlockStmt { token, span, .. } => (token.clone(), span.start_point()),
+        _ => (Rc::new(Token::dummy()), like.span().start_point()),
+    };
+    Rc::new(Stmt::BlockStmt {
+        token,
+        span,
+        stmts: Vec::new(),
+    })
+}
+
+fn defines_names(stmt: &Stmt) -> bool {
+    let mut v = DefinesNames::default();
+    v.visit_stmt(stmt);
+    v.0
+}
+
+/// The only expression in `block`, if that is all it has
+fn only_expr(block: &Stmt) -> Option<Rc<Expr>> {
+    match block {
+        Stmt::BlockStmt { stmts, .. } => match stmts.as_slice() {
+            [s] => match s.as_ref() {
+                Stmt::ExprStmt { expr, .. } => Some(expr.clone()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the VM would take a branch on `cond`
+const fn is_truthy(cond: &Object) -> bool {
+    !matches!(cond, Object::Bool { value: false, .. } | Object::Null)
+}
+
+impl Optimizer {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    fn fold_prefix(
+        &self,
+        token: Rc<Token>,
+        span: Span,
+        op: &Token,
+        right: &Expr,
+    ) -> Option<Rc<Expr>> {
+        match (op.ttype, literal(right)?) {
+            (TokenType::Minus, Object::Number { value, .. }) => {
+                if let NumberToken::Int(i) = value {
+                    i.checked_neg()?;
+                }
+                Some(num_expr(token, span, value.make_neg()))
+            }
+            (TokenType::BANG, Object::Bool { value, .. }) => Some(bool_expr(token, span, !value)),
+            (TokenType::BANG, _) => Some(bool_expr(token, span, false)),
+            _ => None,
+        }
+    }
+
+    fn fold_infix(
+        &self,
+        token: Rc<Token>,
+        span: Span,
+        left: &Expr,
+        op: &Token,
+        right: &Expr,
+    ) -> Option<Rc<Expr>> {
+        let (l, r) = (literal(left)?, literal(right)?);
+        match (op.ttype, &l, &r) {
+            (TokenType::EqEq, ..) => Some(bool_expr(token, span, l == r)),
+            (TokenType::NotEq, ..) => Some(bool_expr(token, span, l != r)),
+            (TokenType::GT, Object::Number { value: a, .. }, Object::Number { value: b, .. }) => {
+                Some(bool_expr(token, span, a > b))
+            }
+            (ttype, Object::Number { value: a, .. }, Object::Number { value: b, .. }) => {
+                Some(num_expr(token, span, arith(ttype, a, b)?))
+            }
+            (TokenType::Plus, Object::String { value: a, .. }, Object::String { value: b, .. }) => {
+                Some(Rc::new(Expr::StringExpr {
+                    token,
+                    span,
+                    value: format!("{a}{b}"),
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// A block that never runs, emptied if that is safe
+    fn drop_block(&self, block: Rc<Stmt>) -> Rc<Stmt> {
+        if defines_names(&block) {
+            block
+        } else {
+            empty_block(&block)
+        }
+    }
+
+    fn fold_if(&self, expr: Rc<Expr>) -> Rc<Expr> {
+        let Expr::IfExpr {
+            token,
+            span,
+            cond,
+            trueblock,
+            elseblock,
+        } = expr.as_ref()
+        else {
+            return expr;
+        };
+        let Some(cond_value) = literal(cond) else {
+            return expr;
+        };
+
+        let truthy = is_truthy(&cond_value);
+        let (live, dead) = if truthy {
+            (Some(trueblock), elseblock.as_ref())
+        } else {
+            (elseblock.as_ref(), Some(trueblock))
+        };
+        if let Some(e) = live.and_then(|b| only_expr(b)) {
+            if dead.is_none_or(|d| !defines_names(d)) {
+                return e;
+            }
+        }
+
+        // an `if` without `nahole` is null when the condition is false, so
+        // a dead `nahole` block can go, a dead `tahole` block can only be
+        // emptied
+        Rc::new(Expr::IfExpr {
+            token: token.clone(),
+            span: *span,
+            cond: cond.clone(),
+            trueblock: if truthy {
+                trueblock.clone()
+            } else {
+                self.drop_block(trueblock.clone())
+            },
+            elseblock: elseblock
+                .as_ref()
+                .filter(|b| !truthy || defines_names(b))
+                .cloned(),
+        })
+    }
+}
+
+/// `block` without the statements after its first `ferao`. Unless
+/// `body`, the block is left as it is if they define names.
+fn cut_after_return(block: Rc<Stmt>, body: bool) -> Rc<Stmt> {
+    let Stmt::BlockStmt { token, span, stmts } = block.as_ref() else {
+        return block;
+    };
+    let Some(ret) = stmts
+        .iter()
+        .position(|s| matches!(s.as_ref(), Stmt::ReturnStmt { .. }))
+    else {
+        return block;
+    };
+    let unreachable = &stmts[ret + 1..];
+    if unreachable.is_empty() || (!body && unreachable.iter().any(|s| defines_names(s))) {
+        return block;
+    }
+    Rc::new(Stmt::BlockStmt {
+        token: token.clone(),
+        span: *span,
+        stmts: stmts[..=ret].to_vec(),
+    })
+}
+
+impl Fold for Optimizer {
+    fn fold_stmt(&mut self, stmt: Rc<Stmt>) -> Rc<Stmt> {
+        cut_after_return(fold_stmt(self, stmt), false)
+    }
+
+    fn fold_expr(&mut self, expr: Rc<Expr>) -> Rc<Expr> {
+        let expr = fold_expr(self, expr);
+        let folded = match expr.as_ref() {
+            Expr::PrefixExpr {
+                token,
+                span,
+                op,
+                right,
+            } => self.fold_prefix(token.clone(), *span, op, right),
+            Expr::InfixExpr {
+                token,
+                span,
+                left,
+                op,
+                right,
+            } => self.fold_infix(token.clone(), *span, left, op, right),
+            Expr::IfExpr { .. } => Some(self.fold_if(expr.clone())),
+            Expr::WhileExpr {
+                token,
+                span,
+                cond,
+                stmts,
+            } => match literal(cond) {
+                Some(c) if !is_truthy(&c) => Some(Rc::new(Expr::WhileExpr {
+                    token: token.clone(),
+                    span: *span,
+                    cond: cond.clone(),
+                    stmts: self.drop_block(stmts.clone()),
+                })),
+                _ => None,
+            },
+            _ => None,
+        };
+        folded.unwrap_or(expr)
+    }
+
+    fn fold_func(&mut self, func: FuncExpr) -> FuncExpr {
+        let mut func = fold_func(self, func);
+        func.body = cut_after_return(func.body, true);
+        func
+    }
+}
+
+/// `prog` with the optimisations in the module docs applied
+pub fn optimize(prog: Program) -> Program {
+    Optimizer::new().fold_program(prog)
+}
@@ -4,7 +4,7 @@ use crate::{
     ast::{self, Expr, Identifier, Program, Stmt},
     errorhelper::ParserError,
     lexer::Lexer,
-    token::{self, Token, TokenType},
+    token::{self, Span, Token, TokenType},
 };
 
 //#[allow(dead_code)]
@@ -14,9 +14,9 @@ const P_LOGIC: usize = 3;
 const P_LTGT: usize = 4;
 const P_SUM: usize = 5;
 const P_PROD: usize = 6;
-const P_PREFIX: usize = 7;
-const P_CALL: usize = 8;
-const P_INDEX: usize = 9;
+pub(crate) const P_PREFIX: usize = 7;
+pub(crate) const P_CALL: usize = 8;
+pub(crate) const P_INDEX: usize = 9;
 
 pub const fn get_precedences(tt: &TokenType) -> usize {
     match tt {
@@ -36,6 +36,9 @@ pub struct Parser<'pax> {
     lexer: Lexer<'pax>,
     curtok: Rc<Token>,
     peektok: Rc<Token>,
+    /// Doc comments (`## ...`) written right before `curtok` / `peektok`
+    curdoc: Option<String>,
+    peekdoc: Option<String>,
     pub errors: Vec<ParserError>,
 }
 
@@ -45,6 +48,8 @@ impl<'pax> Parser<'pax> {
             lexer,
             curtok: Rc::new(Token::dummy()),
             peektok: Rc::new(Token::dummy()),
+            curdoc: None,
+            peekdoc: None,
             errors: vec![],
         };
 
@@ -81,7 +86,13 @@ impl<'pax> Parser<'pax> {
     }
 
     fn next_token(&mut self) -> Rc<Token> {
-        if self.peektok.ttype == TokenType::Illegal {
+        // illegal tokens coming from lexer errors are already reported
+        let reported = self
+            .errors
+            .iter()
+            .rev()
+            .any(|e| e.token.as_ref() == Some(&self.peektok));
+        if self.peektok.ttype == TokenType::Illegal && !reported {
             self.errors.push(ParserError::new(
                 "Illegal token",
                 Some(&self.peektok),
@@ -91,11 +102,32 @@ impl<'pax> Parser<'pax> {
             //self.next_token();
         }
         self.curtok = self.peektok.clone();
-        if let Ok(nt) = self.lexer.next_token() {
-            self.peektok = nt;
-        } else {
-            panic!("lexer error->")
+        self.curdoc = self.peekdoc.take();
+
+        let mut doc_lines: Vec<String> = Vec::new();
+        loop {
+            match self.lexer.next_token() {
+                Ok(nt) if nt.ttype == TokenType::DocComment => doc_lines.push(nt.literal.clone()),
+                Ok(nt) => {
+                    self.peektok = nt;
+                    break;
+                }
+                Err(e) => {
+                    let tok = e.token.clone().map_or_else(
+                        || Token::new(TokenType::Illegal, String::new(), e.colno, e.lineno),
+                        |t| *t,
+                    );
+                    self.errors
+                        .push(ParserError::new(&e.msg, Some(&tok), Some(&tok.ttype)));
+                    self.peektok = Rc::new(tok);
+                    break;
+                }
+            }
         }
+        if !doc_lines.is_empty() {
+            self.peekdoc = Some(doc_lines.join("\n"));
+        }
+
         self.curtok.clone()
     }
 
@@ -148,7 +180,7 @@ impl<'pax> Parser<'pax> {
             self.next_token();
             true
         } else {
-            let errormsg = format!("Expected {:?} but got {:?}", tok, self.curtok);
+            let errormsg = format!("Expected {:?} but got {:?}", tok, self.curtok.ttype);
             self.errors.push(ParserError::new(
                 &errormsg,
                 Some(&self.curtok),
@@ -159,8 +191,7 @@ impl<'pax> Parser<'pax> {
     }
 
     fn peek_error(&mut self, tok: &TokenType) {
-        println!("{}", self.lexer.eh.show_error(&self.peektok));
-        let errmsg = format!("Expected {:?} but got {:?}", tok, self.peektok);
+        let errmsg = format!("Expected {:?} but got {:?}", tok, self.peektok.ttype);
         self.errors.push(ParserError::new(
             &errmsg,
             Some(&self.peektok),
@@ -177,6 +208,11 @@ impl<'pax> Parser<'pax> {
         self.next_token();
     }
 
+    /// Span from the start of `start` to the end of the current token
+    fn span_from(&self, start: &Token) -> Span {
+        start.span.to(self.curtok.span)
+    }
+
     fn skip_semicolon(&mut self) -> bool {
         if self.is_peektok(&TokenType::Semicolon) {
             self.next_token();
@@ -205,6 +241,15 @@ impl<'pax> Parser<'pax> {
         //Ok(Program { stmts: stms })
     }
 
+    /// Parse the whole input even if it has errors, recovering at the next
+    /// statement. Statements that failed to parse hold `ErrExpr`s and the
+    /// errors are left in `self.errors`.
+    pub fn parse_program_partial(&mut self) -> ast::Program {
+        Program {
+            stmts: self.parse_stmts(),
+        }
+    }
+
     //
     //
     // Parse Statments
@@ -239,6 +284,7 @@ impl<'pax> Parser<'pax> {
 
     fn parse_let_stmt(&mut self) -> Rc<Stmt> {
         let ctok = self.curtok.clone();
+        let doc = self.curdoc.take();
         //if !
         self.now(&TokenType::Let); // {
                                    //            return Err(self.last_error().to_owned());
@@ -264,6 +310,9 @@ impl<'pax> Parser<'pax> {
 
         if let Some(f) = func_val {
             f.name = id.name.clone();
+            if f.doc.is_none() {
+                f.doc = doc.clone();
+            }
 
             expr_val = Rc::new(Expr::FuncExpr(f.clone()))
         }
@@ -272,9 +321,11 @@ impl<'pax> Parser<'pax> {
 
         //Ok(
         Rc::new(ast::Stmt::LetStmt {
+            span: self.span_from(&ctok),
             token: ctok,
             name: id,
             value: expr_val,
+            doc,
         })
         //)
     }
@@ -296,7 +347,11 @@ impl<'pax> Parser<'pax> {
         //    };
 
         self.skip_semicolon();
-        Rc::new(ast::Stmt::ShowStmt { token: ctok, value })
+        Rc::new(ast::Stmt::ShowStmt {
+            span: self.span_from(&ctok),
+            token: ctok,
+            value,
+        })
     }
 
     fn parse_return_stmt(&mut self) -> Rc<ast::Stmt> {
@@ -318,7 +373,11 @@ impl<'pax> Parser<'pax> {
                                        //    return Err(self.last_error().to_owned());
 
         self.skip_semicolon();
-        Rc::new(Stmt::ReturnStmt { token: ctok, rval })
+        Rc::new(Stmt::ReturnStmt {
+            span: self.span_from(&ctok),
+            token: ctok,
+            rval,
+        })
     }
 
     fn parse_expr_stmt(&mut self) -> Rc<Stmt> {
@@ -327,11 +386,13 @@ impl<'pax> Parser<'pax> {
         //let Ok(expr) = raw_expr else{
         //    return Err(raw_expr.err().unwrap());
         //};
-        let ex = Rc::new(ast::Stmt::ExprStmt { token, expr });
-
         self.skip_semicolon();
 
-        ex
+        Rc::new(ast::Stmt::ExprStmt {
+            span: self.span_from(&token),
+            token,
+            expr,
+        })
     }
 
     fn parse_block_stms(&mut self, end: &TokenType) -> Rc<Stmt> {
@@ -347,7 +408,16 @@ impl<'pax> Parser<'pax> {
             //}
         }
 
-        Rc::new(Stmt::BlockStmt { token: ct, stmts })
+        let span = match (stmts.first(), stmts.last()) {
+            (Some(first), Some(last)) => first.span().to(last.span()),
+            // nothing between the keywords: an empty span where it would be
+            _ => ct.span.start_point(),
+        };
+        Rc::new(Stmt::BlockStmt {
+            token: ct,
+            span,
+            stmts,
+        })
     }
 
     //
@@ -361,6 +431,7 @@ impl<'pax> Parser<'pax> {
             TokenType::Ident => self.parse_identifier(),
             TokenType::Number => self.parse_number(),
             TokenType::String => self.parse_string_lit(),
+            TokenType::InterpStart => self.parse_interp_expr(),
             TokenType::True | TokenType::False => self.parse_bool(),
             TokenType::LSBracket => self.parse_array_expr(),
             TokenType::One => self.parse_func_expr(),
@@ -378,12 +449,18 @@ impl<'pax> Parser<'pax> {
                 //};
 
                 Rc::new(ast::Expr::PrefixExpr {
+                    span: token.span.to(right.span()),
                     token: token.clone(),
                     op: token,
                     right,
                 })
             }
             TokenType::Lbrace => self.parse_hash_expr(),
+            TokenType::Illegal => {
+                // already reported when the token was read
+                let err = ParserError::new("Illegal token", Some(&self.curtok), None);
+                Rc::new(self.err_to_expr(&err, false))
+            }
             _ => {
                 let err =
                     ParserError::new("Unknown Prefix; Unexpected Token", Some(&self.curtok), None);
@@ -394,7 +471,8 @@ impl<'pax> Parser<'pax> {
         }
     }
 
-    fn parse_infix_expr(&mut self, left: Rc<ast::Expr>) -> Result<Rc<ast::Expr>, ParserError> {
+    /// `None` if the current token is not an infix operator
+    fn parse_infix_expr(&mut self, left: Rc<ast::Expr>) -> Option<Rc<ast::Expr>> {
         match self.curtok.ttype {
             TokenType::Plus
             | TokenType::Minus
@@ -408,16 +486,12 @@ impl<'pax> Parser<'pax> {
             | TokenType::GTE
             | TokenType::And
             | TokenType::Or
-            | TokenType::MOD => Ok(self.parse_infix_op(left)),
+            | TokenType::MOD => Some(self.parse_infix_op(left)),
 
-            TokenType::Lparen => Ok(self.parse_call_expr(left)),
-            TokenType::LSBracket => Ok(self.parse_index_expr(left)),
+            TokenType::Lparen => Some(self.parse_call_expr(left)),
+            TokenType::LSBracket => Some(self.parse_index_expr(left)),
 
-            _ => Err(ParserError::new(
-                "Unknown Infix Operator",
-                Some(&self.curtok),
-                Some(&self.curtok.ttype),
-            )),
+            _ => None,
         }
     }
 
@@ -431,6 +505,7 @@ impl<'pax> Parser<'pax> {
         //};
 
         Rc::new(ast::Expr::InfixExpr {
+            span: left.span().to(right.span()),
             token: op.clone(),
             left,
             op,
@@ -449,7 +524,7 @@ impl<'pax> Parser<'pax> {
 
             //return infx;
 
-            if let Ok(infix_expr) = infx {
+            if let Some(infix_expr) = infx {
                 left_expr = infix_expr;
             } else {
                 return left_expr;
@@ -498,6 +573,7 @@ impl<'pax> Parser<'pax> {
         }
 
         Rc::new(ast::Expr::HashExpr {
+            span: self.span_from(&curtok),
             token: curtok,
             pairs: hash_pairs,
         })
@@ -517,6 +593,7 @@ impl<'pax> Parser<'pax> {
 
         self.next_token();
         Rc::new(ast::Expr::IndexExpr {
+            span: arr.span().to(self.curtok.span),
             token: curtok,
             left: arr,
             index,
@@ -529,20 +606,13 @@ impl<'pax> Parser<'pax> {
     fn parse_expr_list(&mut self, end: &TokenType) -> Vec<Rc<ast::Expr>> {
         let mut el: Vec<Rc<ast::Expr>> = Vec::new();
         //println!("CURTOK->{:?}" , self.curtok);
-        if !self.is_curtok(end) {
-            let expr = self.parse_expr(P_LOWEST);
-            //let Ok(expr) = raw_expr else{
-
-            //    return Err(raw_expr.err().unwrap());
-            //};
-
-            el.push(expr);
-        }
+        // empty list; leave `end` as the current token, like a non-empty list does
         if self.is_curtok(end) {
-            self.next_token();
             return el;
         }
 
+        el.push(self.parse_expr(P_LOWEST));
+
         while self.is_peektok(&TokenType::Comma) {
             self.next_token();
             self.next_token();
@@ -562,9 +632,15 @@ impl<'pax> Parser<'pax> {
     }
 
     fn parse_grouped_expr(&mut self) -> Rc<ast::Expr> {
+        let lparen = self.curtok.clone();
         self.next_token();
-        let exp = self.parse_expr(P_LOWEST);
+        let mut exp = self.parse_expr(P_LOWEST);
         self.next_token();
+        // the parentheses are part of the expression's source
+        let span = self.span_from(&lparen);
+        if let Some(s) = Rc::make_mut(&mut exp).span_mut() {
+            *s = span;
+        }
         exp
     }
 
@@ -612,6 +688,7 @@ impl<'pax> Parser<'pax> {
         }
 
         Rc::new(ast::Expr::IfExpr {
+            span: self.span_from(&curtok),
             token: curtok,
             cond,
             trueblock,
@@ -641,11 +718,17 @@ impl<'pax> Parser<'pax> {
         //    return Err(raw_loop_block.err().unwrap());
         //};
 
-        Rc::new(ast::Expr::WhileExpr { token, cond, stmts })
+        Rc::new(ast::Expr::WhileExpr {
+            span: self.span_from(&token),
+            token,
+            cond,
+            stmts,
+        })
     }
 
     fn parse_func_expr(&mut self) -> Rc<ast::Expr> {
         let ct = self.curtok.clone();
+        let doc = self.curdoc.take();
         self.expect(&TokenType::Func);
 
         // Current token is One/Ekti
@@ -678,9 +761,11 @@ impl<'pax> Parser<'pax> {
 
         Rc::new(ast::Expr::FuncExpr(ast::FuncExpr {
             name: String::from(""),
+            span: self.span_from(&ct),
             token: ct,
             params,
             body,
+            doc,
         }))
     }
 
@@ -745,7 +830,12 @@ impl<'pax> Parser<'pax> {
         //            return Err(raw_args.err().unwrap());
         //        };
 
-        Rc::new(ast::Expr::CallExpr { token, func, args })
+        Rc::new(ast::Expr::CallExpr {
+            span: func.span().to(self.curtok.span),
+            token,
+            func,
+            args,
+        })
     }
 
     fn parse_include_expr(&mut self) -> Rc<ast::Expr> {
@@ -764,11 +854,46 @@ impl<'pax> Parser<'pax> {
         }
 
         Rc::new(ast::Expr::IncludeExpr {
+            span: self.span_from(&curtok),
             token: curtok,
             filename,
         })
     }
 
+    fn parse_interp_expr(&mut self) -> Rc<ast::Expr> {
+        let token = self.curtok.clone();
+        let mut parts: Vec<Rc<ast::Expr>> = Vec::new();
+
+        if !token.literal.is_empty() {
+            parts.push(self.parse_string_lit());
+        }
+
+        loop {
+            self.next_token();
+            parts.push(self.parse_expr(P_LOWEST));
+
+            if self.is_peektok(&TokenType::InterpMid) {
+                self.next_token();
+            } else if !self.peek(&TokenType::InterpEnd) {
+                break;
+            }
+
+            if !self.curtok.literal.is_empty() {
+                parts.push(self.parse_string_lit());
+            }
+
+            if self.is_curtok(&TokenType::InterpEnd) {
+                break;
+            }
+        }
+
+        Rc::new(ast::Expr::InterpExpr {
+            span: self.span_from(&token),
+            token,
+            parts,
+        })
+    }
+
     fn parse_array_expr(&mut self) -> Rc<ast::Expr> {
         let token = self.curtok.clone();
         self.now(&TokenType::LSBracket);
@@ -782,7 +907,11 @@ impl<'pax> Parser<'pax> {
         //  return Err(raw_elms.err().unwrap());
         //};
         //self.next_token();
-        Rc::new(ast::Expr::ArrayExpr { token, elems })
+        Rc::new(ast::Expr::ArrayExpr {
+            span: self.span_from(&token),
+            token,
+            elems,
+        })
     }
 
     //
@@ -794,6 +923,7 @@ impl<'pax> Parser<'pax> {
     fn parse_identifier(&mut self) -> Rc<ast::Expr> {
         Rc::new(ast::Expr::IdentExpr {
             token: self.curtok.clone(),
+            span: self.curtok.span,
             value: self.curtok.literal.to_string(),
         })
     }
@@ -812,6 +942,7 @@ impl<'pax> Parser<'pax> {
     fn parse_string_lit(&mut self) -> Rc<ast::Expr> {
         Rc::new(ast::Expr::StringExpr {
             token: self.curtok.clone(),
+            span: self.curtok.span,
             value: self.curtok.literal.to_string(),
         })
     }
@@ -819,6 +950,7 @@ impl<'pax> Parser<'pax> {
     fn parse_bool(&mut self) -> Rc<ast::Expr> {
         Rc::new(ast::Expr::BoolExpr {
             token: self.curtok.clone(),
+            span: self.curtok.span,
             value: self.is_curtok(&TokenType::True),
         })
     }
@@ -826,6 +958,7 @@ impl<'pax> Parser<'pax> {
     fn parse_break(&mut self) -> Rc<ast::Expr> {
         Rc::new(ast::Expr::Break {
             token: self.curtok.clone(),
+            span: self.curtok.span,
             value: self.curtok.literal.to_string(),
         })
     }
@@ -835,11 +968,12 @@ impl<'pax> Parser<'pax> {
         let curtok_lit = curtok.literal.clone();
         //        let nl: Vec<&str> = curtok_lit.split('.').collect();
 
-        if curtok_lit.split('.').count() == 1 {
+        if !curtok_lit.contains(['.', 'e']) {
             let v = curtok_lit.parse::<i64>();
 
             if let Ok(num) = v {
                 Rc::new(ast::Expr::NumExpr {
+                    span: curtok.span,
                     token: curtok,
                     value: token::NumberToken::Int(num),
                     is_int: true,
@@ -859,6 +993,7 @@ impl<'pax> Parser<'pax> {
             let v = curtok_lit.parse::<f64>();
             if let Ok(num) = v {
                 Rc::new(ast::Expr::NumExpr {
+                    span: curtok.span,
                     token: curtok,
                     value: token::NumberToken::Float(num),
                     is_int: false,
@@ -4,14 +4,74 @@ use std::{
     fmt::Display,
     hash::{Hash, Hasher},
     ops::{Add, Div, Mul, Rem, Sub},
+    rc::Rc,
 };
 
+use crate::langpack::{LangPack, DEFAULT_PACK};
+
+/// Where a token, a piece of trivia or an AST node is in the source:
+/// `start..end` are byte offsets, `char_start..char_end` are char offsets.
+/// `line:col` is where it starts and `end_line:end_col` where it ends
+/// (exclusive), both 1-based like `Token::lineno` / `Token::colno`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub line: usize,
+    pub col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+impl Span {
+    pub const EMPTY: Span = Span {
+        start: 0,
+        end: 0,
+        char_start: 0,
+        char_end: 0,
+        line: 0,
+        col: 0,
+        end_line: 0,
+        end_col: 0,
+    };
+
+    /// From the start of `self` to the end of `other`
+    pub const fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            char_end: other.char_end,
+            end_line: other.end_line,
+            end_col: other.end_col,
+            ..self
+        }
+    }
+
+    /// Empty span where `self` starts
+    pub const fn start_point(self) -> Span {
+        Span {
+            end: self.start,
+            char_end: self.char_start,
+            end_line: self.line,
+            end_col: self.col,
+            ..self
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Token {
     pub ttype: TokenType,
     pub literal: String,
     pub colno: usize,
     pub lineno: usize,
+    /// Set by the lexer; tokens made elsewhere have an empty span
+    pub span: Span,
 }
 
 impl Default for Token {
@@ -21,6 +81,7 @@ impl Default for Token {
             literal: "".to_string(),
             colno: 0,
             lineno: 0,
+            span: Span::EMPTY,
         }
     }
 }
@@ -32,6 +93,7 @@ impl Token {
             literal,
             colno,
             lineno,
+            span: Span::EMPTY,
         }
     }
 
@@ -41,10 +103,58 @@ impl Token {
             literal: String::new(),
             colno: 0,
             lineno: 0,
+            span: Span::EMPTY,
         }
     }
 }
 
+/// Whitespace and comments; the parser never sees these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// Spaces and tabs (and any other whitespace but a line break)
+    Whitespace,
+    /// `\n` or `\r\n`
+    Newline,
+    /// `# ...`, up to the end of the line
+    Comment,
+    /// `#[ ... ]#`
+    BlockComment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// A token with its source text and the trivia around it, from
+/// `Lexer::next_lossless_token`. A token owns the trivia after it up to and
+/// including the end of its line; everything else before the next token is
+/// the next token's leading trivia. Concatenating the `Display` of every
+/// token up to `Eof` gives back the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessToken<'a> {
+    pub token: Rc<Token>,
+    /// Source text of the token as written, the literal may be normalised
+    pub text: &'a str,
+    pub leading: Vec<Trivia<'a>>,
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl Display for LosslessToken<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for t in &self.leading {
+            f.write_str(t.text)?;
+        }
+        f.write_str(self.text)?;
+        for t in &self.trailing {
+            f.write_str(t.text)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum NumberToken {
     Float(f64),
@@ -70,6 +180,9 @@ impl PartialEq for NumberToken {
 
 impl Eq for NumberToken {}
 
+// `>` in programs compares like floats do: NaN is neither greater nor
+// smaller than anything, unlike in the total order of `Ord`
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for NumberToken {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         let lval = self.get_as_f64();
@@ -262,33 +375,37 @@ impl From<usize> for NumberToken {
 pub enum TokenType {
     //Illegal; Unknown symbol
     Illegal,
-    Eof,       //End of file
-    Plus,      // +
-    Minus,     // -
-    String,    // "string"
-    Ident,     //let name = <>
-    Number,    //100 , 1.0 , 3.14 , -123
-    LSBracket, // Left Square Bracket [
-    RSBracket, // Right Square Bracket ]
-    Colon,     // :
-    Comment,   // Comment #
-    Eq,        // =
-    EqEq,      // ==
-    NotEq,     // !=
-    Mul,       // *
-    Div,       // /
-    MOD,       // %
-    BANG,      // !
-    LT,        // <
-    LTE,       // <=
-    GT,        // >
-    GTE,       // >=
-    Semicolon, // ;
-    Comma,     // ,
-    Lparen,    // (
-    Rparen,    // )
-    Lbrace,    // {
-    Rbrace,    // }
+    Eof,         //End of file
+    Plus,        // +
+    Minus,       // -
+    String,      // "string"
+    InterpStart, // "string {    (string with interpolation, up to the first `{`)
+    InterpMid,   // } string {  (string between two interpolations)
+    InterpEnd,   // } string"   (rest of the string after the last interpolation)
+    Ident,       //let name = <>
+    Number,      //100 , 1.0 , 3.14 , -123
+    LSBracket,   // Left Square Bracket [
+    RSBracket,   // Right Square Bracket ]
+    Colon,       // :
+    Comment,     // Comment #
+    DocComment,  // Doc comment ##
+    Eq,          // =
+    EqEq,        // ==
+    NotEq,       // !=
+    Mul,         // *
+    Div,         // /
+    MOD,         // %
+    BANG,        // !
+    LT,          // <
+    LTE,         // <=
+    GT,          // >
+    GTE,         // >=
+    Semicolon,   // ;
+    Comma,       // ,
+    Lparen,      // (
+    Rparen,      // )
+    Lbrace,      // {
+    Rbrace,      // }
 
     //Keywords
     Include, // Include Keyword
@@ -309,24 +426,7 @@ pub enum TokenType {
     Break,
 }
 
+/// Keyword spelled `id` in the default language pack (English, Banglish and Bengali)
 pub fn lookup_ident(id: &str) -> Option<TokenType> {
-    match id {
-        "include" | "anoyon" | "আনয়ন" => Some(TokenType::Include),
-        "one" | "ekti" | "একটি" => Some(TokenType::One),
-        "then" | "tahole" | "তাহলে" => Some(TokenType::Then),
-        "and" | "ebong" | "এবং" => Some(TokenType::And),
-        "or" | "ba" | "বা" => Some(TokenType::Or),
-        "fn" | "kaj" | "কাজ" => Some(TokenType::Func),
-        "let" | "dhori" | "ধরি" => Some(TokenType::Let),
-        "true" | "sotti" | "সত্যি" => Some(TokenType::True),
-        "false" | "mittha" | "মিথ্যা" => Some(TokenType::False),
-        "if" | "jodi" | "যদি" => Some(TokenType::If),
-        "else" | "nahole" | "নাহলে" => Some(TokenType::Else),
-        "return" | "ferao" | "ferau" | "ফেরাও" => Some(TokenType::Return),
-        "while" | "jotokhon" | "যতক্ষণ" => Some(TokenType::While),
-        "show" | "dekhao" | "dekhau" | "দেখাও" => Some(TokenType::Show),
-        "end" | "sesh" | "শেষ" => Some(TokenType::End),
-        "break" | "bhango" | "ভাঙো" => Some(TokenType::Break),
-        _ => None,
-    }
+    LangPack::builtin(DEFAULT_PACK).and_then(|p| p.lookup(id))
 }
@@ -0,0 +1,122 @@
+//! Rewrite a program's keywords into one spelling style.
+//!
+//! Only keyword tokens (and, if asked, the digits of decimal number
+//! literals) are touched; everything between them, comments and whitespace included, is
+//! copied from the source as is.
+
+use crate::{
+    langpack::{LangPack, Script},
+    lexer::{Lexer, LexerError},
+    token::{Token, TokenType},
+};
+
+/// Spelling that keywords are rewritten into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordStyle {
+    /// `let`, `if`, ...
+    English,
+    /// The pack's romanised spelling: `dhori`, `jodi`, ... for Bengali
+    Roman,
+    /// The pack's own script: `ধরি`, `যদি`, ... for Bengali
+    Native,
+}
+
+/// Digits that number literals are rewritten with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigitStyle {
+    /// `0-9`
+    Ascii,
+    /// The digits of the pack's script: `০-৯` for Bengali
+    Native,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranslitOptions {
+    pub keywords: KeywordStyle,
+    /// Leave number literals alone if `None`
+    pub digits: Option<DigitStyle>,
+}
+
+impl KeywordStyle {
+    /// Style a keyword is written in
+    pub fn of(keyword: &Token) -> KeywordStyle {
+        if LangPack::english_spelling(&keyword.ttype) == Some(keyword.literal.as_str()) {
+            KeywordStyle::English
+        } else if keyword.literal.is_ascii() {
+            KeywordStyle::Roman
+        } else {
+            KeywordStyle::Native
+        }
+    }
+}
+
+/// Spelling of the keyword `tt` in `style`, if the pack has one
+pub fn keyword_spelling<'p>(
+    pack: &'p LangPack,
+    tt: &TokenType,
+    style: KeywordStyle,
+) -> Option<&'p str> {
+    match style {
+        KeywordStyle::English => LangPack::english_spelling(tt),
+        KeywordStyle::Roman => pack.roman_spelling(tt),
+        KeywordStyle::Native => pack.native_spelling(tt),
+    }
+}
+
+/// `0x`, `0b` and `0o` literals are kept as written, since their letters
+/// are ASCII whatever the digits are
+fn convert_digits(num: &str, script: &Script, style: DigitStyle) -> String {
+    let mut prefix = num.chars();
+    let zero = prefix
+        .next()
+        .is_some_and(|c| script.digit(c).or(c.to_digit(10)) == Some(0));
+    if zero && matches!(prefix.next(), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O')) {
+        return num.to_string();
+    }
+
+    num.chars()
+        .map(|c| match style {
+            DigitStyle::Ascii => script
+                .digit(c)
+                .and_then(|d| char::from_digit(d, 10))
+                .unwrap_or(c),
+            DigitStyle::Native => c
+                .to_digit(10)
+                .and_then(|d| char::from_u32(script.zero as u32 + d))
+                .unwrap_or(c),
+        })
+        .collect()
+}
+
+/// Re-emit the program read by `lexer` with every keyword in
+/// `opts.keywords` style. Keywords without a spelling in that style are kept
+/// as written.
+pub fn translit(mut lexer: Lexer, opts: TranslitOptions) -> Result<String, LexerError> {
+    let src = lexer.input();
+    let mut out = String::with_capacity(src.len());
+    let mut copied = 0;
+
+    loop {
+        let tok = lexer.next_token()?;
+        if tok.ttype == TokenType::Eof {
+            break;
+        }
+
+        let text = &src[tok.span.start..tok.span.end];
+        let replacement = match tok.ttype {
+            TokenType::Number => opts
+                .digits
+                .map(|style| convert_digits(text, &lexer.pack().script, style)),
+            _ => keyword_spelling(lexer.pack(), &tok.ttype, opts.keywords).map(String::from),
+        };
+
+        if let Some(new) = replacement {
+            out.push_str(&src[copied..tok.span.start]);
+            out.push_str(&new);
+            copied = tok.span.end;
+        }
+    }
+
+    out.push_str(&src[copied..]);
+    Ok(out)
+}
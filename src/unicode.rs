@@ -0,0 +1,170 @@
+//! Just enough Unicode for identifiers.
+//!
+//! Identifiers are ASCII or written in one of the scripts of a language pack
+//! (see `langpack`), so canonical normalisation only needs the decompositions,
+//! combining classes and compositions of those blocks, not the full Unicode
+//! database. Everything outside them is already in NFC and passes through.
+
+use std::borrow::Cow;
+
+pub const ZWNJ: char = '\u{200c}';
+pub const ZWJ: char = '\u{200d}';
+
+/// Canonical decompositions. The nukta forms (ড়, ঢ়, য়, क़ ...) are
+/// composition exclusions, so NFC keeps them decomposed.
+const DECOMPOSITIONS: &[(char, [char; 2])] = &[
+    // Bengali
+    ('\u{09cb}', ['\u{09c7}', '\u{09be}']), // ো
+    ('\u{09cc}', ['\u{09c7}', '\u{09d7}']), // ৌ
+    ('\u{09dc}', ['\u{09a1}', '\u{09bc}']), // ড়
+    ('\u{09dd}', ['\u{09a2}', '\u{09bc}']), // ঢ়
+    ('\u{09df}', ['\u{09af}', '\u{09bc}']), // য়
+    // Devanagari
+    ('\u{0929}', ['\u{0928}', '\u{093c}']), // ऩ
+    ('\u{0931}', ['\u{0930}', '\u{093c}']), // ऱ
+    ('\u{0934}', ['\u{0933}', '\u{093c}']), // ऴ
+    ('\u{0958}', ['\u{0915}', '\u{093c}']), // क़
+    ('\u{0959}', ['\u{0916}', '\u{093c}']), // ख़
+    ('\u{095a}', ['\u{0917}', '\u{093c}']), // ग़
+    ('\u{095b}', ['\u{091c}', '\u{093c}']), // ज़
+    ('\u{095c}', ['\u{0921}', '\u{093c}']), // ड़
+    ('\u{095d}', ['\u{0922}', '\u{093c}']), // ढ़
+    ('\u{095e}', ['\u{092b}', '\u{093c}']), // फ़
+    ('\u{095f}', ['\u{092f}', '\u{093c}']), // य़
+];
+
+/// Decompositions that are recomposed by NFC
+const COMPOSITIONS: &[(char, char, char)] = &[
+    ('\u{09c7}', '\u{09be}', '\u{09cb}'),
+    ('\u{09c7}', '\u{09d7}', '\u{09cc}'),
+    ('\u{0928}', '\u{093c}', '\u{0929}'),
+    ('\u{0930}', '\u{093c}', '\u{0931}'),
+    ('\u{0933}', '\u{093c}', '\u{0934}'),
+];
+
+/// Canonical combining class; 0 for starters
+fn combining_class(c: char) -> u8 {
+    match c {
+        '\u{093c}' | '\u{09bc}' => 7,
+        '\u{094d}' | '\u{09cd}' => 9,
+        '\u{0952}' => 220,
+        '\u{0951}' | '\u{0953}' | '\u{0954}' | '\u{09fe}' => 230,
+        '\u{0300}'..='\u{0314}' | '\u{033d}'..='\u{0344}' | '\u{0346}' => 230,
+        '\u{0316}'..='\u{0319}' | '\u{031c}'..='\u{0320}' | '\u{0323}'..='\u{0326}' => 220,
+        _ => 0,
+    }
+}
+
+/// Is `c` a combining mark (vowel sign, virama, nukta, candrabindu...)
+/// that can only follow another character of an identifier
+pub fn is_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036f}'
+        // Devanagari
+        | '\u{0900}'..='\u{0903}'
+        | '\u{093a}'..='\u{093c}'
+        | '\u{093e}'..='\u{094f}'
+        | '\u{0951}'..='\u{0957}'
+        | '\u{0962}'..='\u{0963}'
+        // Bengali
+        | '\u{0981}'..='\u{0983}'
+        | '\u{09bc}'
+        | '\u{09be}'..='\u{09c4}'
+        | '\u{09c7}'..='\u{09c8}'
+        | '\u{09cb}'..='\u{09cd}'
+        | '\u{09d7}'
+        | '\u{09e2}'..='\u{09e3}'
+        | '\u{09fe}'
+    )
+}
+
+/// Is `c` a zero width joiner or non-joiner; these select conjunct and
+/// half forms and are part of the identifier they appear in
+pub const fn is_joiner(c: char) -> bool {
+    c == ZWJ || c == ZWNJ
+}
+
+fn decomposition(c: char) -> Option<[char; 2]> {
+    DECOMPOSITIONS
+        .iter()
+        .find(|(from, _)| *from == c)
+        .map(|(_, to)| *to)
+}
+
+fn composition(a: char, b: char) -> Option<char> {
+    COMPOSITIONS
+        .iter()
+        .find(|(x, y, _)| *x == a && *y == b)
+        .map(|(_, _, c)| *c)
+}
+
+fn needs_normalising(s: &str) -> bool {
+    let mut prev: Option<char> = None;
+    let mut last_ccc = 0;
+    for c in s.chars() {
+        let ccc = combining_class(c);
+        if decomposition(c).is_some()
+            || (ccc != 0 && ccc < last_ccc)
+            || prev.is_some_and(|p| composition(p, c).is_some())
+        {
+            return true;
+        }
+        prev = Some(c);
+        last_ccc = ccc;
+    }
+    false
+}
+
+/// Normalisation Form C of `s`, for the scripts identifiers can use
+pub fn nfc(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() || !needs_normalising(s) {
+        return Cow::Borrowed(s);
+    }
+
+    // canonical decomposition
+    let mut chars: Vec<char> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        match decomposition(c) {
+            Some(d) => chars.extend(d),
+            None => chars.push(c),
+        }
+    }
+
+    // canonical ordering of each run of combining marks
+    let mut i = 0;
+    while i < chars.len() {
+        if combining_class(chars[i]) == 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && combining_class(chars[i]) != 0 {
+            i += 1;
+        }
+        chars[start..i].sort_by_key(|c| combining_class(*c));
+    }
+
+    // canonical composition
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter: Option<usize> = None;
+    let mut last_ccc = 0;
+    for c in chars {
+        let ccc = combining_class(c);
+        if let Some(si) = starter {
+            let blocked = out.len() > si + 1 && (last_ccc == 0 || last_ccc >= ccc);
+            if !blocked {
+                if let Some(composed) = composition(out[si], c) {
+                    out[si] = composed;
+                    continue;
+                }
+            }
+        }
+        if ccc == 0 {
+            starter = Some(out.len());
+        }
+        last_ccc = ccc;
+        out.push(c);
+    }
+
+    Cow::Owned(out.into_iter().collect())
+}
@@ -0,0 +1,59 @@
+use std::fmt::Display;
+
+use crate::token::Span;
+
+/// Why `Vm::run` stopped before the end of the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    /// Calls nested deeper than the frame limit, or more values than the
+    /// stack limit; `depth` is the number of frames at the time
+    StackOverflow { depth: usize },
+    /// The instruction budget set with `Vm::set_fuel` ran out
+    OutOfFuel,
+    /// The deadline set with `Vm::set_deadline` passed
+    DeadlineExceeded,
+    /// The flag from `Vm::interrupt_handle` was set
+    Interrupted,
+    /// Making an object would take the program past the limit set with
+    /// `Vm::set_memory_limit`; `used` is what it would have come to
+    OutOfMemory { used: usize, limit: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    /// Function that was running; empty at the top level and for
+    /// functions never bound to a name
+    pub function: String,
+    /// Source of the instruction that failed, if the code has a line table
+    pub span: Option<Span>,
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeErrorKind::StackOverflow { depth } => {
+                write!(f, "stack overflow ({depth} calls deep)")
+            }
+            RuntimeErrorKind::OutOfFuel => write!(f, "instruction budget used up"),
+            RuntimeErrorKind::DeadlineExceeded => write!(f, "time limit exceeded"),
+            RuntimeErrorKind::Interrupted => write!(f, "interrupted"),
+            RuntimeErrorKind::OutOfMemory { used, limit } => {
+                write!(f, "out of memory ({used} bytes, limit {limit})")
+            }
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(span) = self.span {
+            write!(f, "{}:{}: ", span.line, span.col)?;
+        }
+        write!(f, "{}", self.kind)?;
+        if !self.function.is_empty() {
+            write!(f, " in {}", self.function)?;
+        }
+        Ok(())
+    }
+}
@@ -3,7 +3,9 @@ use std::{cell::RefCell, fmt::Display};
 
 use crate::{compiler::code, obj::Closure};
 
-static FRAMES_SIZE: usize = 1024;
+/// Default limit on nested calls, the top level included
+pub const FRAMES_SIZE: usize = 1024;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Frame {
     pub cl: Rc<Closure>,
@@ -75,6 +77,12 @@ pub struct FramePool {
     pub len: usize,
 }
 
+impl Default for FramePool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FramePool {
     pub fn new() -> Self {
         Self {
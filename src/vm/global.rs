@@ -1,15 +1,19 @@
-use std::{cell::RefCell, rc::Rc};
-
-use crate::obj::Object;
+use super::value::Value;
 
 const GLOBALS_SIZE: usize = 1024; //Change
 
 #[derive(Debug)]
 pub struct GlobalStack {
-    pub globals: Vec<Rc<Object>>,
+    pub globals: Vec<Value>,
     pub len: usize,
 }
 
+impl Default for GlobalStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GlobalStack {
     pub fn new() -> Self {
         Self {
@@ -17,26 +21,22 @@ impl GlobalStack {
             len: 0,
         }
     }
-    pub fn push_value(&mut self, index: usize, obj: Rc<Object>) {
-        if index >= self.len {
-            self.globals.push(obj);
-            self.len += 1;
+    /// Set global `index`; the ones before it that were never set (their
+    /// `dhori` did not run) are null
+    pub fn push_value(&mut self, index: usize, obj: Value) {
+        if index < self.len {
+            self.globals[index] = obj;
         } else {
-            //self.globals.borrow_mut()[index] = obj;
-            //let ptr = self.globals.as_ptr();
-            unsafe {
-                //(*ptr)[index] = obj;
-
-                _ = std::mem::replace(self.globals.get_unchecked_mut(index), obj);
-            }
+            self.globals.resize(index, Value::Null);
+            self.globals.push(obj);
+            self.len = self.globals.len();
         }
     }
 
-    pub fn get_value(&self, index: usize) -> Rc<Object> {
-        if index >= self.len || index > GLOBALS_SIZE {
-            Rc::new(Object::Null)
-        } else {
-            unsafe { Rc::clone(&self.globals.get_unchecked(index)) }
+    pub fn get_value(&self, index: usize) -> Value {
+        match self.globals.get(index) {
+            Some(obj) => obj.clone(),
+            None => Value::Null,
         }
     }
 }
@@ -0,0 +1,128 @@
+//! Approximate accounting of the memory a program's strings, arrays,
+//! hashes and closures take.
+//!
+//! The VM adds the size of each such object it makes to a running total.
+//! Objects are freed as soon as nothing refers to them, which the VM does
+//! not see, so from time to time it sets the total to the size of what the
+//! stack, the globals and the running calls can still reach. Between those
+//! measurements the total also counts garbage, much like the heap size a
+//! garbage collected runtime reports.
+//!
+//! Reference counting frees most objects. Objects are never changed once
+//! made, so they can only refer to older ones, with one exception: a
+//! closure made in the value of a local's `dhori` gets that local once it
+//! is set (`OpFillFrees`), and so can end up inside the hash or array it
+//! refers to. When the VM measures, and on `Vm::collect`, it also empties
+//! the captures of every such closure the roots no longer reach, which
+//! breaks the cycles it was part of and lets reference counting free them.
+//! Dropping the VM empties the captures of all of them.
+
+use std::{collections::HashSet, mem::size_of, rc::Rc};
+
+use crate::obj::{Closure, HashKey, HashPair, Object};
+
+/// The total is measured again once it has grown past this many bytes
+pub const MEASURE_AFTER: usize = 1 << 20;
+
+/// Close to the memory limit, the total is measured again once it has
+/// grown by this fraction of the limit rather than at the limit itself
+pub const LIMIT_STEPS: usize = 8;
+
+/// Memory in use by a program, in bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Reachable objects at the time of asking
+    pub current: usize,
+    /// Most the total ever was, garbage not yet noticed included
+    pub peak: usize,
+}
+
+/// Bytes `obj` takes by itself, not counting the objects it refers to.
+/// Zero for everything but strings, arrays, hashes and closures.
+pub fn heap_size(obj: &Object) -> usize {
+    let own = size_of::<Object>();
+    match obj {
+        Object::String { value, .. } => own + value.capacity(),
+        Object::Array { value, .. } => own + value.capacity() * size_of::<Rc<Object>>(),
+        Object::Hash { pairs, .. } => {
+            let slot = size_of::<Rc<HashKey>>() + size_of::<Rc<HashPair>>();
+            let pair = size_of::<HashKey>() + size_of::<HashPair>();
+            own + pairs.capacity() * slot + pairs.len() * pair
+        }
+        Object::Closure(cl) => own + closure_size(cl),
+        _ => 0,
+    }
+}
+
+fn closure_size(cl: &Closure) -> usize {
+    size_of::<Closure>() + cl.frees.borrow().capacity() * size_of::<Rc<Object>>()
+}
+
+/// Walks objects from the roots, counting each one once
+#[derive(Default)]
+pub struct Measure {
+    seen: HashSet<*const Object>,
+    seen_closures: HashSet<*const Closure>,
+    todo: Vec<Rc<Object>>,
+    total: usize,
+}
+
+impl Measure {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave `obj` and what it refers to out of the total; for constants,
+    /// which the compiler made
+    pub fn skip(&mut self, obj: &Rc<Object>) {
+        self.seen.insert(Rc::as_ptr(obj));
+    }
+
+    pub fn root(&mut self, obj: &Rc<Object>) {
+        if self.seen.insert(Rc::as_ptr(obj)) {
+            self.todo.push(Rc::clone(obj));
+        }
+    }
+
+    /// A closure a call is running, which no object may hold
+    pub fn root_closure(&mut self, cl: &Rc<Closure>) {
+        if self.seen_closures.insert(Rc::as_ptr(cl)) {
+            self.total += closure_size(cl);
+            for free in cl.frees.borrow().iter() {
+                self.root(free);
+            }
+        }
+    }
+
+    /// Bytes taken by everything reachable from the roots
+    pub fn total(&mut self) -> usize {
+        while let Some(obj) = self.todo.pop() {
+            match obj.as_ref() {
+                Object::Array { value, .. } => {
+                    self.total += heap_size(&obj);
+                    for item in value {
+                        self.root(item);
+                    }
+                }
+                Object::Hash { pairs, .. } => {
+                    self.total += heap_size(&obj);
+                    for pair in pairs.values() {
+                        self.root(&pair.key);
+                        self.root(&pair.value);
+                    }
+                }
+                Object::Closure(cl) => {
+                    self.total += size_of::<Object>();
+                    self.root_closure(cl);
+                }
+                _ => self.total += heap_size(&obj),
+            }
+        }
+        self.total
+    }
+
+    /// Whether `cl` was reached; after `total`
+    pub fn reached(&self, cl: &Rc<Closure>) -> bool {
+        self.seen_closures.contains(&Rc::as_ptr(cl))
+    }
+}
@@ -1,18 +1,41 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
+pub mod error;
 pub mod frame;
 pub mod global;
+pub mod memory;
+pub mod value;
 
 use crate::{
+    bn::NumeralStyle,
     compiler::code::{self, Bytecode, Instructions},
-    obj::{Closure, HashKey, HashPair, Object, ARRAY_OBJ, HASH_OBJ, NUMBER_OBJ, STRING_OBJ},
+    obj::{
+        builtins::{get_builtin, Builtin},
+        Closure, CompFunc, HashKey, HashPair, Object, ARRAY_OBJ, HASH_OBJ, NUMBER_OBJ, STRING_OBJ,
+    },
     token::NumberToken,
 };
 
-use self::frame::{Frame, FramePool};
+use self::error::{RuntimeError, RuntimeErrorKind};
+use self::frame::{Frame, FramePool, FRAMES_SIZE};
 use self::global::GlobalStack;
+use self::memory::{heap_size, Measure, MemoryStats, LIMIT_STEPS, MEASURE_AFTER};
+use self::value::Value;
+
+/// Default limit on the values on the stack, locals included
+pub const STACK_SIZE: usize = 2048;
 
-static STACK_SIZE: usize = 2048;
+/// Instructions run between looks at the clock and the interrupt flag
+const CHECK_EVERY: u32 = 1024;
 
 //const TRUE: Object = Object::Bool {
 //    token: None,
@@ -27,26 +50,76 @@ static STACK_SIZE: usize = 2048;
 
 #[derive(Debug)]
 pub struct Vm {
-    constants: Vec<Rc<Object>>,
+    constants: Vec<Value>,
     stack: StackPool, //Vec<Object>,
     sp: usize,
     globals: GlobalStack, //Rc<RefCell<[Object]>>,
     frames: FramePool,
     frame_index: usize,
-    last_popped: Rc<Object>,
-    c_null: Rc<Object>,
-    c_false: Rc<Object>,
-    c_true: Rc<Object>,
+    last_popped: Value,
+    numerals: NumeralStyle,
+    stack_limit: usize,
+    frame_limit: usize,
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    interrupt: Arc<AtomicBool>,
+    until_check: u32,
+    memory_limit: Option<usize>,
+    /// Bytes of objects made, as of the last measurement
+    heap_used: usize,
+    heap_peak: usize,
+    /// `heap_used` past which it is measured again
+    measure_at: usize,
+    /// Captures of locals still being defined (`OpDeferFree`): the local's
+    /// stack slot, and the closure and free index it goes in
+    deferred: Vec<(usize, Rc<Closure>, usize)>,
+    /// Closures given a capture after they were made (`OpFillFrees`), the
+    /// only way a cycle forms; see `memory`
+    filled: Vec<Weak<Closure>>,
 }
 
 //pub type Pframe = Rc<RefCell<Frame>>;
 
+/// The running function's code and where it is in it, kept out of its
+/// frame while it runs
+struct Regs {
+    ins: Rc<Instructions>,
+    cl: Rc<Closure>,
+    /// Offset of the next byte to read, one past `Frame::ip`
+    ip: usize,
+    bp: usize,
+}
+
+/// Why `dispatch` stopped; `run` makes a `RuntimeError` of it once the ip
+/// the error is located by is back in its frame
+struct Fault {
+    kind: RuntimeErrorKind,
+    /// Blame the call into the running function rather than the
+    /// instruction being run
+    in_caller: bool,
+}
+
+impl From<RuntimeErrorKind> for Fault {
+    fn from(kind: RuntimeErrorKind) -> Self {
+        Self {
+            kind,
+            in_caller: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct StackPool {
-    pub stack: Vec<Rc<Object>>,
+    pub stack: Vec<Value>,
     pub len: usize,
 }
 
+impl Default for StackPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StackPool {
     pub fn new() -> Self {
         Self {
@@ -55,24 +128,23 @@ impl StackPool {
         }
     }
 
-    pub fn push(&mut self, index: Option<usize>, obj: Rc<Object>) {
-        if let Some(idx) = index {
-            if idx >= self.len {
-                self.stack.push(obj);
-                self.len += 1;
-            } else {
-                unsafe {
-                    _ = std::mem::replace(self.stack.get_unchecked_mut(idx), obj);
+    /// Put `obj` at `index`, or on top without one. Slots skipped to reach
+    /// `index` (locals not set yet) are filled with null.
+    pub fn push(&mut self, index: Option<usize>, obj: Value) {
+        match index {
+            Some(idx) if idx < self.stack.len() => self.stack[idx] = obj,
+            Some(idx) => {
+                if idx > self.stack.len() {
+                    self.stack.resize(idx, Value::Null);
                 }
-                //self.stack[idx] = obj
+                self.stack.push(obj);
             }
-        } else {
-            self.stack.push(obj);
-            self.len += 1;
+            None => self.stack.push(obj),
         }
+        self.len = self.stack.len();
     }
 
-    pub fn pop(&mut self) -> Rc<Object> {
+    pub fn pop(&mut self) -> Value {
         if self.len > 0 {
             self.len -= 1;
         }
@@ -80,18 +152,14 @@ impl StackPool {
         self.stack.pop().expect("stack is empty")
     }
 
-    pub fn get(&self, index: usize) -> &Rc<Object> {
-        //unsafe { self.stack.get_unchecked(index) }
-        // if index >= self.len(){
-        //    return self.stack.last().expect("stack is empty");
-        //}
+    pub fn get(&self, index: usize) -> &Value {
         self.stack
             .get(index)
-            .expect(format!("{:?} -> {index}", self.stack).as_str())
+            .unwrap_or_else(|| panic!("stack index out of range -> {index}"))
     }
 
-    pub fn get_mut(&mut self, index: usize) -> &mut Rc<Object> {
-        unsafe { self.stack.get_unchecked_mut(index) }
+    pub fn get_mut(&mut self, index: usize) -> &mut Value {
+        &mut self.stack[index]
     }
 
     pub fn len(&self) -> usize {
@@ -105,169 +173,348 @@ impl StackPool {
 
 impl Vm {
     pub fn new(bc: Bytecode) -> Self {
-        let main_cl = Rc::new(Closure::new(bc.instructions));
+        let main_fn = CompFunc {
+            lines: bc.lines,
+            ..CompFunc::new(Rc::clone(&bc.instructions), bc.instructions.ins.len())
+        };
+        let main_cl = Rc::new(Closure::new_from_cfn(Rc::new(main_fn)));
         let main_frame = Frame::new(main_cl, 0);
         let mut frames = FramePool::new();
         frames.frames = vec![Rc::new(RefCell::new(main_frame))];
         frames.len += 1;
         Self {
-            constants: bc.constants,
+            constants: bc.constants.into_iter().map(Value::from).collect(),
             stack: StackPool::new(), //Vec::with_capacity(STACK_SIZE),
             globals: GlobalStack::new(),
             sp: 0,
             frames,
             frame_index: 1,
-            last_popped: Rc::new(Object::Null),
-            c_null: Rc::new(Object::Null),
-            c_false: Rc::new(Object::Bool {
-                token: None,
-                value: false,
-            }),
-            c_true: Rc::new(Object::Bool {
-                token: None,
-                value: true,
-            }),
+            last_popped: Value::Null,
+            numerals: NumeralStyle::default(),
+            stack_limit: STACK_SIZE,
+            frame_limit: FRAMES_SIZE,
+            fuel: None,
+            deadline: None,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            until_check: CHECK_EVERY,
+            memory_limit: None,
+            heap_used: 0,
+            heap_peak: 0,
+            measure_at: MEASURE_AFTER,
+            deferred: Vec::new(),
+            filled: Vec::new(),
         }
     }
-    fn bool_native_to_obj(&self, b: bool) -> Rc<Object> {
-        if b {
-            Rc::clone(&self.c_true)
-        } else {
-            Rc::clone(&self.c_false)
+
+    /// Set which digits `dekhao` uses to print numbers
+    pub fn set_numerals(&mut self, numerals: NumeralStyle) {
+        self.numerals = numerals;
+    }
+
+    pub const fn numerals(&self) -> NumeralStyle {
+        self.numerals
+    }
+
+    /// Set how many values the stack can hold, locals of every active call
+    /// included (default `STACK_SIZE`)
+    pub fn set_stack_limit(&mut self, limit: usize) {
+        self.stack_limit = limit;
+    }
+
+    /// Set how deep calls can nest, the top level counting as one (default
+    /// `FRAMES_SIZE`)
+    pub fn set_frame_limit(&mut self, limit: usize) {
+        self.frame_limit = limit;
+    }
+
+    /// Set how many more instructions `run` may execute; `None` for no
+    /// limit. Running out stops the program with `OutOfFuel`, and it goes
+    /// on from there if `run` is called again with more fuel.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// Instructions left before running out of fuel
+    pub const fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Stop the program with `DeadlineExceeded` once `deadline` has passed.
+    /// The clock is read every few instructions, so it may run a little
+    /// longer.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// A flag that stops the program with `Interrupted` when set, from this
+    /// or any other thread. It is cleared when the error is returned.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    /// Set how many bytes the program's strings, arrays, hashes and
+    /// closures may take; `None` for no limit. Making an object that would
+    /// go past it stops the program with `OutOfMemory`. A program using
+    /// nearly all of it is only checked after making another
+    /// `limit / LIMIT_STEPS` bytes, so it may go past it by that much.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+        self.measure_at = self.next_measure();
+    }
+
+    /// How much memory the program takes now and took at most; see
+    /// `memory` for how it is counted
+    pub fn memory_stats(&self) -> MemoryStats {
+        let current = self.measure();
+        MemoryStats {
+            current,
+            peak: self.heap_peak.max(current),
         }
     }
 
-    pub fn top_stack(&self) -> Rc<Object> {
-        if self.sp == 0 {
-            Rc::new(Object::Null)
-        } else {
-            //&self.stack[self.sp - 1]
-            Rc::clone(self.stack.get(self.sp - 1))
+    /// Bytes taken by the objects the program can still reach
+    fn measure(&self) -> usize {
+        self.reach(None).total()
+    }
+
+    /// The roots: the stack, the globals, the running calls, captures
+    /// waiting for their local and `new`, an object being made
+    fn reach(&self, new: Option<&Rc<Object>>) -> Measure {
+        let mut m = Measure::new();
+        // slots above the top are left over from calls that have returned
+        let values = self.stack.stack.iter().take(self.sp);
+        for c in &self.constants {
+            if let Value::Obj(obj) = c {
+                m.skip(obj);
+            }
         }
+        for v in values
+            .chain(&self.globals.globals)
+            .chain([&self.last_popped])
+        {
+            if let Value::Obj(obj) = v {
+                m.root(obj);
+            }
+        }
+        if let Some(obj) = new {
+            m.root(obj);
+        }
+        // the first frame is the top level, which the program did not make
+        for frame in &self.frames.frames[1..self.frame_index] {
+            m.root_closure(&frame.borrow().cl);
+        }
+        for (_, cl, _) in &self.deferred {
+            m.root_closure(cl);
+        }
+        m
     }
 
-    fn current_frame(&self) -> &Rc<RefCell<Frame>> {
-        //&self.frames.frames[self.frame_index - 1]
-        unsafe { self.frames.frames.get_unchecked(self.frame_index - 1) }
+    /// Free the cycles the program can no longer reach, returning how many
+    /// closures they held. Measuring memory does this too, and dropping
+    /// the VM frees every cycle left, so calling it is only needed to free
+    /// them sooner.
+    pub fn collect(&mut self) -> usize {
+        let mut m = self.reach(None);
+        self.heap_used = m.total();
+        self.measure_at = self.next_measure();
+        self.sweep(&m)
     }
 
-    fn get_cur_frame_ip(&self) -> i64 {
-        unsafe {
-            let x = self
-                .frames
-                .frames
-                .get_unchecked(self.frame_index - 1)
-                .as_ptr();
-            (*x).get_ip()
+    /// Empty the captures of the filled closures `m` did not reach,
+    /// returning how many there were, and let go of what returned calls
+    /// left above the top of the stack
+    fn sweep(&mut self, m: &Measure) -> usize {
+        for v in self.stack.stack.iter_mut().skip(self.sp) {
+            *v = Value::Null;
         }
+        let mut freed = 0;
+        self.filled.retain(|cl| match cl.upgrade() {
+            Some(cl) if m.reached(&cl) => true,
+            Some(cl) => {
+                drop(cl.frees.take());
+                freed += 1;
+                false
+            }
+            None => false,
+        });
+        freed
     }
 
-    fn get_cur_frame_ilen(&self) -> i64 {
-        unsafe {
-            let x = self
-                .frames
-                .frames
-                .get_unchecked(self.frame_index - 1)
-                .as_ptr();
-            (*x).cl.fun.in_len as i64
+    /// `heap_used` past which to measure again
+    fn next_measure(&self) -> usize {
+        let at = MEASURE_AFTER.max(2 * self.heap_used);
+        match self.memory_limit {
+            // measuring at the limit itself would measure on every object
+            // made by a program living just below it
+            Some(limit) => at.min(limit.max(self.heap_used + limit / LIMIT_STEPS)),
+            None => at,
         }
     }
 
-    fn push_frame(&mut self, f: Frame) {
-        if self.frame_index >= self.frames.len {
-            self.frames.frames.push(Rc::new(RefCell::new(f)))
-        } else {
-            unsafe {
-                //_ = std::mem::replace(self.frames.frames.get_unchecked_mut(self.frame_index), Rc::new(RefCell::new(f)));
+    /// Count the bytes of `new`, an object just made, failing if that goes
+    /// past the memory limit even after measuring what is still in use
+    fn account(&mut self, new: &Rc<Object>) -> Result<(), Fault> {
+        let size = heap_size(new);
+        self.heap_used += size;
+        if self.heap_used > self.measure_at {
+            let mut m = self.reach(Some(new));
+            self.heap_used = m.total();
+            self.sweep(&m);
+            self.measure_at = self.next_measure();
+            if let Some(limit) = self.memory_limit.filter(|l| self.heap_used > *l) {
+                let used = self.heap_used;
+                self.heap_used -= size;
+                return Err(RuntimeErrorKind::OutOfMemory { used, limit }.into());
+            }
+        }
+        self.heap_peak = self.heap_peak.max(self.heap_used);
+        Ok(())
+    }
+
+    /// `obj` on the heap, counted against the memory limit
+    fn alloc(&mut self, obj: Object) -> Result<Value, Fault> {
+        let obj = Rc::new(obj);
+        self.account(&obj)?;
+        Ok(Value::Obj(obj))
+    }
+
+    /// Whether the next instruction may run
+    fn check_limits(&mut self) -> Result<(), Fault> {
+        if let Some(fuel) = self.fuel {
+            if fuel == 0 {
+                return Err(RuntimeErrorKind::OutOfFuel.into());
+            }
+            self.fuel = Some(fuel - 1);
+        }
 
-                (*self.frames.frames.get_unchecked(self.frame_index).as_ptr()) = f;
+        self.until_check -= 1;
+        if self.until_check == 0 {
+            self.until_check = CHECK_EVERY;
+            if self.interrupt.swap(false, Ordering::Relaxed) {
+                return Err(RuntimeErrorKind::Interrupted.into());
+            }
+            if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(RuntimeErrorKind::DeadlineExceeded.into());
             }
         }
+        Ok(())
+    }
+
+    pub fn top_stack(&self) -> Value {
+        if self.sp == 0 {
+            Value::Null
+        } else {
+            //&self.stack[self.sp - 1]
+            self.stack.get(self.sp - 1).clone()
+        }
+    }
+
+    fn current_frame(&self) -> &Rc<RefCell<Frame>> {
+        &self.frames.frames[self.frame_index - 1]
+    }
 
+    fn push_frame(&mut self, f: Frame) -> Result<(), Fault> {
+        if self.frame_index >= self.frame_limit {
+            return Err(RuntimeErrorKind::StackOverflow {
+                depth: self.frame_index,
+            }
+            .into());
+        }
+        self.frames.frames.push(Rc::new(RefCell::new(f)));
+        self.frames.len = self.frames.frames.len();
         self.frame_index += 1;
+        Ok(())
     }
 
     fn pop_frame(&mut self) -> Rc<RefCell<Frame>> {
         self.frame_index -= 1;
+        self.frames.len -= 1;
 
-        self.frames.frames.pop().unwrap()
+        let frame = self.frames.frames.pop().unwrap();
+        self.drop_deferred(frame.borrow().bp as usize);
+        frame
     }
 
-    fn adv_ip(&mut self, by: usize) {
-        unsafe {
-            let ptr = self
-                .frames
-                .frames
-                .get_unchecked(self.frame_index - 1)
-                .as_ptr();
-            (*ptr).ip += by as i64
-        }
+    /// Forget the captures waiting for locals from `bp` on, when `ferao`
+    /// leaves a call before they are set
+    fn drop_deferred(&mut self, bp: usize) {
+        while self.deferred.pop_if(|(slot, ..)| *slot >= bp).is_some() {}
     }
 
-    fn set_ip(&mut self, t: usize) {
-        unsafe {
-            let ptr = self
-                .frames
-                .frames
-                .get_unchecked(self.frame_index - 1)
-                .as_ptr();
-            (*ptr).ip = t as i64;
+    /// An error located at the instruction frame `index` is at
+    fn error_in(&self, index: usize, kind: RuntimeErrorKind) -> RuntimeError {
+        let frame = self.frames.frames[index].borrow();
+        let fun = &frame.cl.fun;
+        RuntimeError {
+            kind,
+            function: fun.name.clone(),
+            span: usize::try_from(frame.ip)
+                .ok()
+                .and_then(|ip| fun.lines.lookup(ip)),
         }
     }
 
-    fn get_ip(&self) -> usize {
-        unsafe {
-            let ptr = self
-                .frames
-                .frames
-                .get_unchecked(self.frame_index - 1)
-                .as_ptr();
-            (*ptr).ip as usize
+    /// Registers for the current frame
+    fn load(&self) -> Regs {
+        let frame = self.current_frame().borrow();
+        Regs {
+            ins: frame.get_instructions(),
+            cl: frame.get_cl(),
+            ip: (frame.ip + 1) as usize,
+            bp: frame.bp as usize,
         }
     }
 
-    fn get_cur_frame_ins(&self) -> Rc<Instructions> {
-        unsafe {
-            let ptr = self
-                .frames
-                .frames
-                .get_unchecked(self.frame_index - 1)
-                .as_ptr();
-            //Rc::clone(&(*ptr).cl.fun.fnin)
-            (*ptr).get_instructions()
+    /// Write the position in `r` back to the current frame
+    fn save(&self, r: &Regs) {
+        self.current_frame().borrow_mut().ip = r.ip as i64 - 1;
+    }
+
+    /// Run the program to its end, or until an error stops it. After
+    /// hitting a limit on fuel or time, or an interrupt, calling it again
+    /// goes on where it stopped.
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let mut r = self.load();
+        let result = self.dispatch(&mut r);
+        self.save(&r);
+        let Err(fault) = result else {
+            return Ok(());
+        };
+
+        let index = if fault.in_caller {
+            self.frame_index.saturating_sub(2)
+        } else {
+            self.frame_index - 1
+        };
+        let error = self.error_in(index, fault.kind);
+        if matches!(
+            error.kind,
+            RuntimeErrorKind::OutOfFuel
+                | RuntimeErrorKind::DeadlineExceeded
+                | RuntimeErrorKind::Interrupted
+        ) {
+            // run the instruction that was stopped first when resumed
+            self.current_frame().borrow_mut().ip -= 1;
         }
+        Err(error)
     }
 
-    pub fn run(&mut self) {
-        while self.get_cur_frame_ip() //self.current_frame().borrow().get_ip()
-            < self.get_cur_frame_ilen() - 1
-        //self.current_frame().borrow().get_ins_len() - 1
-        {
-            self.adv_ip(1);
-            let ip = self.get_ip();
-            let ins = self.get_cur_frame_ins();
-            let op = code::u8_to_op(ins.ins[ip]);
-            //println!("OP->{:?}", op);
+    /// The interpreter loop. The running function's code, ip and bp live
+    /// in `r` and are only written back to its frame around calls and
+    /// returns, and by `run` once this returns.
+    fn dispatch(&mut self, r: &mut Regs) -> Result<(), Fault> {
+        while r.ip < r.ins.ins.len() {
+            let ins = &r.ins.ins;
+            let op = code::u8_to_op(ins[r.ip]);
+            r.ip += 1;
+            self.check_limits()?;
 
             match op {
                 code::Opcode::Const => {
-                    let op_ins = &ins.ins;
-                    //println!("CON_INS{ins}");
-                    //println!("CON_INDEX_U16{:?}" , Instructions::read_uint16(op_ins, ip + 1));
-                    let mut con_index = Instructions::read_uint16(op_ins, ip + 1) as usize;
-                    //println!("CON_INDEX{con_index}");
-                    //if con_index > 255 { //What con_index would be 256?
-                    //    con_index = 0;
-                    //}
-                    unsafe {
-                        let con_obj = self.constants.get_unchecked(con_index); //[con_index].clone();
-
-                        self.push(Rc::clone(con_obj));
-                    }
-
-                    //println!("{con_index:?}");
-                    //ip += 2;
-                    self.adv_ip(2);
+                    let con_index = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
+                    let con_obj = self.constants[con_index].clone();
+                    self.push(con_obj)?;
                 }
                 code::Opcode::Pop => {
                     self.last_popped = self.pop();
@@ -276,218 +523,243 @@ impl Vm {
                 | code::Opcode::Sub
                 | code::Opcode::Mul
                 | code::Opcode::Div
-                | code::Opcode::Mod => self.exe_binary_op(op),
+                | code::Opcode::Mod => self.exe_binary_op(op)?,
 
-                code::Opcode::True => self.push(Rc::clone(&self.c_true)),
-                code::Opcode::False => self.push(Rc::clone(&self.c_false)),
+                code::Opcode::True => self.push(Value::Bool(true))?,
+                code::Opcode::False => self.push(Value::Bool(false))?,
                 code::Opcode::Equal | code::Opcode::NotEqual | code::Opcode::GT => {
-                    self.exe_comparison(op)
+                    self.exe_comparison(op)?
                 }
-                code::Opcode::Bang => self.exe_bang_op(),
-                code::Opcode::Minus => self.exe_pref_minux(),
-                code::Opcode::Null => self.push(Rc::clone(&self.c_null)),
+                code::Opcode::Bang => self.exe_bang_op()?,
+                code::Opcode::Minus => self.exe_pref_minux()?,
+                code::Opcode::Null => self.push(Value::Null)?,
                 code::Opcode::SetGlobal => {
-                    let gi = code::Instructions::read_uint16(&ins.ins, ip + 1) as usize;
-                    //ip += 2;
-                    self.adv_ip(2);
-                    //self.globals[gi] = self.pop()
+                    let gi = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
                     let pop_item = self.pop();
                     self.globals.push_value(gi, pop_item);
                 }
                 code::Opcode::GetGlobal => {
-                    let gi = code::Instructions::read_uint16(&ins.ins, ip + 1) as usize;
-                    //ip += 2;
-                    self.adv_ip(2);
-
-                    //self.push(&self.globals[gi].clone())
-                    self.push(self.globals.get_value(gi))
+                    let gi = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
+                    self.push(self.globals.get_value(gi))?
                 }
                 code::Opcode::Jump => {
-                    let pos = code::Instructions::read_uint16(&ins.ins, ip + 1);
-                    //println!("{:?}" , pos);
-
-                    //ip = (pos - 1) as usize
-                    if pos > 0 {
-                        self.set_ip((pos - 1) as usize)
-                    } else {
-                        self.set_ip(0)
-                    }
+                    r.ip = Instructions::read_uint16(ins, r.ip) as usize;
                 }
-
                 code::Opcode::JumpNotTruthy => {
-                    let pos = code::Instructions::read_uint16(&ins.ins, ip + 1) as usize;
-
-                    //ip += 2;
-                    self.adv_ip(2);
+                    let pos = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
 
                     let cond = self.pop();
-                    //println!("STACK->{:?}", self.stack.stack);
-                    //println!("COND->{cond}");
+                    if !cond.is_truthy() {
+                        r.ip = pos;
+                    }
+                }
+                code::Opcode::JumpNotGT | code::Opcode::JumpNotEqual | code::Opcode::JumpEqual => {
+                    let pos = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
 
-                    if !self.is_obj_truthy(&cond) {
-                        //ip = pos - 1;
-                        self.set_ip(pos - 1)
+                    let right = self.pop();
+                    let left = self.pop();
+                    let jump = match op {
+                        code::Opcode::JumpNotGT => !self.compare(code::Opcode::GT, &left, &right),
+                        code::Opcode::JumpNotEqual => {
+                            !self.compare(code::Opcode::Equal, &left, &right)
+                        }
+                        _ => self.compare(code::Opcode::Equal, &left, &right),
+                    };
+                    if jump {
+                        r.ip = pos;
                     }
                 }
+                code::Opcode::ConstZero => self.push(Value::Number(NumberToken::Int(0)))?,
+                code::Opcode::ConstOne => self.push(Value::Number(NumberToken::Int(1)))?,
+                code::Opcode::IncLocal => {
+                    let from = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    let con_index = Instructions::read_uint16(ins, r.ip + 1) as usize;
+                    let to = Instructions::read_u8(&ins[r.ip + 3..]) as usize;
+                    r.ip += 4;
+
+                    let local = self.stack.get(r.bp + from).clone();
+                    let con_obj = self.constants[con_index].clone();
+                    let sum = match (&local, &con_obj) {
+                        (Value::Number(lval), Value::Number(rval)) => {
+                            Value::Number(lval.clone() + rval.clone())
+                        }
+                        // whatever `GetLocal; Const; Add` would leave
+                        _ => {
+                            self.push(local)?;
+                            self.push(con_obj)?;
+                            self.exe_binary_op(code::Opcode::Add)?;
+                            self.pop()
+                        }
+                    };
+                    self.stack.push(Some(r.bp + to), sum)
+                }
                 code::Opcode::Array => {
-                    let num_of_elms = code::Instructions::read_uint16(&ins.ins, ip + 1) as usize;
-                    //ip += 2;
-                    self.adv_ip(2);
+                    let num_of_elms = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
 
                     let arr = self.build_arr(self.sp - num_of_elms, self.sp);
                     self.sp -= num_of_elms;
-                    self.push(Rc::new(arr));
+                    let arr = self.alloc(arr)?;
+                    self.push(arr)?;
                 }
-
                 code::Opcode::Hash => {
-                    let num_of_elms = code::Instructions::read_uint16(&ins.ins, ip + 1) as usize;
-                    //ip += 2;
-                    self.adv_ip(2);
+                    let num_of_elms = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
 
                     let hash = self.build_hash(self.sp - num_of_elms, self.sp);
                     self.sp -= num_of_elms;
 
-                    self.push(Rc::new(hash))
+                    let hash = self.alloc(hash)?;
+                    self.push(hash)?
                 }
                 code::Opcode::Index => {
                     let index = self.pop();
                     let left = self.pop();
-                    self.exe_index_expr(left, index)
+                    self.exe_index_expr(left, index)?
                 }
                 code::Opcode::ReturnValue => {
                     let rvalue = self.pop();
-                    let frm = self.pop_frame();
-                    //self.pop();
-
-                    unsafe {
-                        let ptr = frm.as_ptr();
-                        self.sp = ((*ptr).bp - 1) as usize;
-
-                        self.push(rvalue)
-                        //frm.borrow().get_bp() as usize - 1;
-                    }
-                    //frm.get_bp() as usize - 1;
-                    //(*frm).borrow().get_bp() as usize - 1; //frm.as_ref().borrow().get_bp() as usize - 1; //frm.bp as usize - 1;
+                    self.pop_frame();
+                    self.sp = r.bp - 1;
+                    self.push(rvalue)?;
+                    *r = self.load();
                 }
                 code::Opcode::Return => {
-                    let frm = self.pop_frame();
-                    unsafe {
-                        self.sp = ((*frm.as_ptr()).bp - 1) as usize;
-                    }
-                    //self.sp = frm.borrow().get_bp() as usize - 1;
-                    //(*frm).borrow().get_bp() as usize - 1; //frm.as_ref().borrow().get_bp() as usize -1; //frm.bp as usize - 1;
-                    //self.pop();
-                    self.push(Rc::clone(&self.c_null));
+                    self.pop_frame();
+                    self.sp = r.bp - 1;
+                    self.push(Value::Null)?;
+                    *r = self.load();
                 }
                 code::Opcode::SetLocal => {
-                    //let prelen = self.stack.len();
-                    let local_index = code::Instructions::read_u8(&ins.ins[ip + 1..]);
-                    self.adv_ip(1);
-                    let frm_index: usize;
-                    let pop_item = unsafe {
-                        frm_index = (*self.current_frame().as_ptr()).bp as usize;
-                        self.pop()
-                        //let frm = self.current_frame().borrow().get_bp();
-                    };
-                    //self.current_frame().as_ref().borrow().get_bp(); //self.current_frame().bp;
-                    //self.stack[(frm as usize) + (local_index as usize)] = self.pop()
-                    //
-                    //let pop_item = self.pop();
-                    self.stack
-                        .push(Some(frm_index + local_index as usize), pop_item)
+                    let local_index = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    r.ip += 1;
+                    let pop_item = self.pop();
+                    self.stack.push(Some(r.bp + local_index), pop_item)
                 }
                 code::Opcode::GetLocal => {
-                    let local_index = code::Instructions::read_u8(&ins.ins[ip + 1..]) as usize;
-                    self.adv_ip(1);
-                    //let frm_bp : usize;
-                    unsafe {
-                        let frm_bp = (*self.current_frame().as_ptr()).bp as usize;
-
-                        let stack_obj = Rc::clone(self.stack.get(frm_bp + local_index));
-
-                        self.push(stack_obj);
-                        //self.current_frame().borrow().get_bp() as usize;
-                    }
-                    //self.current_frame().as_ref().borrow().get_bp() as usize; //self.current_frame().bp as usize;
-                    //unsafe {
-
-                    //}
-
-                    //self.stack[frm_bp + local_index].clone();
+                    let local_index = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    r.ip += 1;
+                    let stack_obj = self.stack.get(r.bp + local_index).clone();
+                    self.push(stack_obj)?;
                 }
                 code::Opcode::Call => {
-                    let num_args = code::Instructions::read_u8(&ins.ins[ip + 1..]);
-                    self.adv_ip(1);
-                    self.call_func(num_args as usize);
+                    let num_args = Instructions::read_u8(&ins[r.ip..]);
+                    r.ip += 1;
+                    self.save(r);
+                    self.call_func(num_args as usize)?;
+                    *r = self.load();
+                }
+                code::Opcode::TailCall => {
+                    let num_args = Instructions::read_u8(&ins[r.ip..]);
+                    r.ip += 1;
+                    self.save(r);
+                    self.tail_call(num_args as usize)?;
+                    *r = self.load();
                 }
                 code::Opcode::Closure => {
-                    let const_index = code::Instructions::read_uint16(&ins.ins, ip + 1);
-                    let num_free = code::Instructions::read_u8(&ins.ins[ip + 3..]);
-
-                    self.adv_ip(3);
-                    self.push_closure(const_index as usize, num_free as usize);
+                    let const_index = Instructions::read_uint16(ins, r.ip);
+                    let num_free = Instructions::read_u8(&ins[r.ip + 2..]);
+                    r.ip += 3;
+                    self.push_closure(const_index as usize, num_free as usize)?;
                 }
                 code::Opcode::GetFree => {
-                    let f_index = code::Instructions::read_u8(&ins.ins[ip + 1..]);
-                    self.adv_ip(1);
-                    //                    let curframe = self.current_frame();
-
-                    //let ccl : Rc<Closure>;
-                    unsafe {
-                        let ccl = Rc::clone(&(*self.current_frame().as_ptr()).cl);
+                    let f_index = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    r.ip += 1;
+                    self.push(Value::from(Rc::clone(&r.cl.frees.borrow()[f_index])))?
+                }
+                code::Opcode::DeferFree => {
+                    let f_index = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    let local_index = Instructions::read_u8(&ins[r.ip + 1..]) as usize;
+                    r.ip += 2;
 
-                        self.push(Rc::clone(&ccl.frees[f_index as usize]))
-                        //= Rc::clone(&self.current_frame().borrow().cl);
+                    let Some(Object::Closure(cl)) = self.stack.get(self.sp - 1).as_object() else {
+                        panic!("not closure -> {f_index}");
+                    };
+                    self.deferred
+                        .push((r.bp + local_index, Rc::clone(cl), f_index));
+                }
+                code::Opcode::FillFrees => {
+                    let slot = r.bp + Instructions::read_u8(&ins[r.ip..]) as usize;
+                    r.ip += 1;
+
+                    let value = self.stack.get(slot).to_object();
+                    while let Some((_, cl, f_index)) = self.deferred.pop_if(|(s, ..)| *s == slot) {
+                        cl.frees.borrow_mut()[f_index] = Rc::clone(&value);
+                        self.filled.push(Rc::downgrade(&cl));
                     }
-
-                    //Rc::clone(&self.current_frame().cl);
-                    //Rc::clone(&(*self.current_frame()).borrow().cl); //&curframe.as_ref().borrow().cl; //&self.current_frame().cl.clone();
                 }
-
                 code::Opcode::CurrentClosure => {
-                    //                    let ccl = self.current_frame().cl.clone();
-                    //let ccl :Rc<Closure>;
-
-                    unsafe {
-                        let ccl = Rc::clone(&(*self.current_frame().as_ptr()).cl);
-
-                        self.push(Rc::new(Object::Closure(ccl)));
+                    // the closure is already counted
+                    let obj = Object::Closure(Rc::clone(&r.cl));
+                    self.push(Value::Obj(Rc::new(obj)))?;
+                }
+                code::Opcode::ToString => {
+                    let obj = self.pop();
+                    if obj.get_type() == STRING_OBJ {
+                        self.push(obj)?;
+                    } else {
+                        let s = self.alloc(Object::String {
+                            token: None,
+                            value: obj.render(self.numerals),
+                        })?;
+                        self.push(s)?;
                     }
-                    //= Rc::clone(&self.current_frame().borrow().cl);
-                    //Rc::clone(&self.current_frame().cl);
-                    //&self.current_frame().as_ref().borrow().cl.clone();
                 }
-                code::Opcode::Show => {
-                    let num_items = code::Instructions::read_u8(&ins.ins[ip + 1..]) as usize;
+                code::Opcode::Concat => {
+                    let num_items = Instructions::read_uint16(ins, r.ip) as usize;
+                    r.ip += 2;
+
+                    let mut value = String::new();
+                    for i in self.sp - num_items..self.sp {
+                        let Some(Object::String { token: _, value: s }) =
+                            self.stack.get(i).as_object()
+                        else {
+                            panic!("can only concat strings");
+                        };
+                        value.push_str(s);
+                    }
+                    self.sp -= num_items;
+                    let s = self.alloc(Object::String { token: None, value })?;
+                    self.push(s)?;
+                }
+                code::Opcode::GetBuiltin => {
+                    let b_index = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    r.ip += 1;
 
-                    let mut i = 0;
+                    let Some(bf) = get_builtin(b_index) else {
+                        panic!("unknown builtin -> {b_index}");
+                    };
+                    self.push(Value::Obj(Rc::new(Object::Builtin(Rc::new(Builtin {
+                        name: bf.name,
+                        func: bf.func,
+                    })))))?;
+                }
+                code::Opcode::Show => {
+                    let num_items = Instructions::read_u8(&ins[r.ip..]) as usize;
+                    r.ip += 1;
 
-                    //                    let mut objs: Vec<Object> = Vec::new();
                     let mut result: Vec<String> = Vec::with_capacity(num_items);
-
-                    while i < num_items {
-                        result.push(self.pop().to_string());
-                        //result.push(' ');
-                        //objs.push(self.pop());
-                        i += 1
+                    for _ in 0..num_items {
+                        result.push(self.pop().render(self.numerals));
                     }
                     result.reverse();
-                    //let mut result = String::new();
-
                     println!("{}", result.join(" "));
-                    self.adv_ip(2);
                 }
 
                 _ => {}
             }
-            //ip += 1;
         }
+        Ok(())
     }
 
-    fn push_closure(&mut self, index: usize, num_free: usize) {
+    fn push_closure(&mut self, index: usize, num_free: usize) -> Result<(), Fault> {
         let obj = &self.constants[index];
 
-        let Object::Compfunc(cf) = obj.as_ref() else{
+        let Some(Object::Compfunc(cf)) = obj.as_object() else {
             panic!("not fun");
         };
 
@@ -495,38 +767,78 @@ impl Vm {
         let mut i = 0;
         while i < num_free {
             //fr[i] = self.stack[self.sp - num_free + i].clone().into();
-            fr.push(Rc::clone(self.stack.get(self.sp - num_free + i)));
+            fr.push(self.stack.get(self.sp - num_free + i).to_object());
             i += 1;
         }
 
         self.sp -= num_free;
 
-        let cls = Object::Closure(Rc::new(Closure {
+        let cls = self.alloc(Object::Closure(Rc::new(Closure {
             fun: cf.clone(),
-            frees: fr,
-        }));
-        self.push(Rc::new(cls));
+            frees: RefCell::new(fr),
+        })))?;
+        self.push(cls)
     }
 
-    fn call_func(&mut self, num_args: usize) {
+    fn call_func(&mut self, num_args: usize) -> Result<(), Fault> {
         //println!("X{:?}->{:?}" , self.sp , num_args);
-        let stack_object = Rc::clone(self.stack.get(self.sp - 1 - num_args));
-        let Object::Closure(cf) = &*stack_object else
-        {
+        let stack_object = self.stack.get(self.sp - 1 - num_args).clone();
+        if let Some(Object::Builtin(bf)) = stack_object.as_object() {
+            return self.call_builtin(bf, num_args);
+        }
+        let Some(Object::Closure(cf)) = stack_object.as_object() else {
             //    println!("not closure");
             //    if let Object::Closure(lcf) = self.last_pop(){
             //
             //        self.call_closure(lcf, num_args);
             //        return;
-             //   };
-                    panic!("not closure -> panic");
+            //   };
+            panic!("not closure -> panic");
+        };
+
+        self.call_closure(cf.clone(), num_args)
+    }
 
+    /// `OpCall` that reuses the current frame: the callee and its arguments
+    /// take the place of the running function and its own
+    fn tail_call(&mut self, num_args: usize) -> Result<(), Fault> {
+        let stack_object = self.stack.get(self.sp - 1 - num_args).clone();
+        let Some(Object::Closure(cl)) = stack_object.as_object() else {
+            // a builtin returns at once, and `OpReturnValue` follows
+            return self.call_func(num_args);
         };
+        if cl.fun.num_params != num_args {
+            panic!(
+                "arg number and params number is not same| W=>{} G={}",
+                cl.fun.num_params, num_args
+            );
+        }
+
+        let bp = self.current_frame().borrow().bp as usize;
+        self.check_locals(bp + cl.fun.num_locals)?;
+        self.drop_deferred(bp);
+        let from = self.sp - 1 - num_args;
+        for i in 0..=num_args {
+            let obj = self.stack.get(from + i).clone();
+            self.stack.push(Some(bp - 1 + i), obj);
+        }
+        *self.current_frame().borrow_mut() = Frame::new(cl.clone(), bp as i64);
+        self.sp = bp + cl.fun.num_locals;
+        Ok(())
+    }
 
-        self.call_closure(cf.clone(), num_args);
+    fn call_builtin(&mut self, bf: &Builtin, num_args: usize) -> Result<(), Fault> {
+        let args: Vec<Rc<Object>> = (self.sp - num_args..self.sp)
+            .map(|i| self.stack.get(i).to_object())
+            .collect();
+
+        let result = (bf.func)(&args);
+        self.account(&result)?;
+        self.sp -= num_args + 1;
+        self.push(Value::from(result))
     }
 
-    fn call_closure(&mut self, cal: Rc<Closure>, num_args: usize) {
+    fn call_closure(&mut self, cal: Rc<Closure>, num_args: usize) -> Result<(), Fault> {
         if cal.fun.num_params != num_args {
             panic!(
                 "arg number and params number is not same| W=>{} G={}",
@@ -536,12 +848,25 @@ impl Vm {
 
         let frame = Frame::new(cal.clone(), (self.sp - num_args) as i64);
         let fbp = frame.bp as usize;
-        self.push_frame(frame);
+        self.check_locals(fbp + cal.fun.num_locals)?;
+        self.push_frame(frame)?;
 
         self.sp = fbp + cal.fun.num_locals;
+        Ok(())
+    }
+
+    /// Fail if a call's locals would end past the stack limit at `top`
+    fn check_locals(&self, top: usize) -> Result<(), Fault> {
+        if top > self.stack_limit {
+            return Err(RuntimeErrorKind::StackOverflow {
+                depth: self.frame_index,
+            }
+            .into());
+        }
+        Ok(())
     }
 
-    fn exe_index_expr(&mut self, left: Rc<Object>, index: Rc<Object>) {
+    fn exe_index_expr(&mut self, left: Value, index: Value) -> Result<(), Fault> {
         if left.get_type() == ARRAY_OBJ && index.get_type() == NUMBER_OBJ {
             self.exe_arr_index(left, index)
         } else if left.get_type() == HASH_OBJ {
@@ -551,9 +876,11 @@ impl Vm {
         }
     }
 
-    fn exe_arr_index(&mut self, arr: Rc<Object>, index: Rc<Object>) {
-        let Object::Array { token : _ , value } = &*arr else { panic!("not array") };
-        let id: Option<i64> = if let Object::Number { token: _, value } = &*index {
+    fn exe_arr_index(&mut self, arr: Value, index: Value) -> Result<(), Fault> {
+        let Some(Object::Array { token: _, value }) = arr.as_object() else {
+            panic!("not array")
+        };
+        let id: Option<i64> = if let Value::Number(value) = &index {
             Some(value.get_as_i64())
         } else {
             None
@@ -562,14 +889,17 @@ impl Vm {
         let max = (value.len() - 1) as i64;
 
         if id.unwrap() < 0 || id.unwrap() > max {
-            self.push(Rc::clone(&self.c_null))
+            self.push(Value::Null)
         } else {
-            self.push(value[id.unwrap() as usize].clone())
+            self.push(Value::from(value[id.unwrap() as usize].clone()))
         }
     }
 
-    fn exe_hash_index(&mut self, hash: Rc<Object>, index: Rc<Object>) {
-        let Object::Hash { token : _, pairs } = &*hash else{ panic!("not hash") };
+    fn exe_hash_index(&mut self, hash: Value, index: Value) -> Result<(), Fault> {
+        let Some(Object::Hash { token: _, pairs }) = hash.as_object() else {
+            panic!("not hash")
+        };
+        let index = index.to_object();
         if !index.hashable() {
             panic!("index key is not hashable")
         }
@@ -578,9 +908,9 @@ impl Vm {
         };
         //println!("{:?}" , pairs);
         if let Some(v) = pairs.get(&hk) {
-            self.push(v.value.clone())
+            self.push(Value::from(v.value.clone()))
         } else {
-            self.push(Rc::clone(&self.c_null))
+            self.push(Value::Null)
         }
     }
 
@@ -590,9 +920,9 @@ impl Vm {
         let mut i = start;
 
         while i < end {
-            let k: Rc<Object> = Rc::clone(self.stack.get(i));
+            let k: Rc<Object> = self.stack.get(i).to_object();
 
-            let v: Rc<Object> = Rc::clone(self.stack.get(i + 1));
+            let v: Rc<Object> = self.stack.get(i + 1).to_object();
 
             //unsafe {
             //    k = *self.stack.get(i); //Rc::new(self.stack.get_unchecked(i).clone());
@@ -618,13 +948,13 @@ impl Vm {
 
     fn build_arr(&mut self, start: usize, end: usize) -> Object {
         let mut elms: Vec<Rc<Object>> = {
-            let data = Rc::clone(&self.c_null);
+            let data = Rc::new(Object::Null);
             vec![data; end - start]
         };
         let mut i = start;
 
         while i < end {
-            elms[i - start] = Rc::clone(self.stack.get(i)); //Rc::new(self.stack[i].clone());
+            elms[i - start] = self.stack.get(i).to_object(); //Rc::new(self.stack[i].clone());
             i += 1;
         }
 
@@ -633,127 +963,115 @@ impl Vm {
             value: elms,
         }
     }
-    const fn is_obj_truthy(&self, obj: &Object) -> bool {
-        match obj {
-            Object::Bool { token: _, value } => *value,
-            Object::Null => false,
-            _ => true,
-        }
-    }
 
-    fn exe_pref_minux(&mut self) {
+    fn exe_pref_minux(&mut self) -> Result<(), Fault> {
         let op = self.pop();
 
         if op.get_type() != NUMBER_OBJ {
             panic!("negetion can only be applied on numbers -> {op:?}")
         }
 
-        let Object::Number { token : _, value } = &*op else {
+        let Value::Number(value) = &op else {
             panic!("not a number")
         };
 
-        self.push(Rc::new(Object::Number {
-            token: None,
-            value: value.make_neg(),
-        }))
+        self.push(Value::Number(value.make_neg()))
     }
 
-    fn exe_bang_op(&mut self) {
+    fn exe_bang_op(&mut self) -> Result<(), Fault> {
         let o = self.pop();
 
-        match *o {
-            Object::Bool { token: _, value } => {
-                if value {
-                    self.push(Rc::clone(&self.c_false))
-                } else {
-                    self.push(Rc::clone(&self.c_true))
-                }
-            }
-            Object::Null => self.push(Rc::clone(&self.c_true)),
-            _ => self.push(Rc::clone(&self.c_false)),
-        };
+        match o {
+            Value::Bool(value) => self.push(Value::Bool(!value)),
+            Value::Null => self.push(Value::Bool(true)),
+            _ => self.push(Value::Bool(false)),
+        }
     }
 
-    fn exe_comparison(&mut self, op: code::Opcode) {
+    fn exe_comparison(&mut self, op: code::Opcode) -> Result<(), Fault> {
         let right = self.pop();
         let left = self.pop();
-        println!("{left:?} , {right:?}");
-        if left.get_type() == NUMBER_OBJ && right.get_type() == NUMBER_OBJ {
-            self.exe_comparison_number(op, left, right);
-            return;
+        let result = self.compare(op, &left, &right);
+        self.push(Value::Bool(result))
+    }
+
+    /// `left op right` for `OpEqual`, `OpNotEqual` and `OpGT`
+    fn compare(&self, op: code::Opcode, left: &Value, right: &Value) -> bool {
+        if let (Value::Number(l), Value::Number(r)) = (left, right) {
+            return self.compare_numbers(op, l, r);
         }
 
+        let equal = left == right;
         match op {
-            code::Opcode::Equal => self.push(self.bool_native_to_obj(right == left)),
-            code::Opcode::NotEqual => self.push(self.bool_native_to_obj(left != right)),
+            code::Opcode::Equal => equal,
+            code::Opcode::NotEqual => !equal,
             _ => {
                 panic!("unknonwn operator -> {op:?}")
             }
         }
     }
 
-    fn exe_comparison_number(&mut self, op: code::Opcode, left: Rc<Object>, right: Rc<Object>) {
-        //let lval: Option<NumberToken> = if let Object::Number { token: _, value } = &*left {
-        //    Some(value.clone())
-        //} else {
-        //    None
-        //};
-
-        let Object::Number { token : _, value  : lval} = &*left else{
-            panic!("not number");
-        } ;
-
-        let Object::Number { token : _, value : rval } = &*right else{
-            panic!("not a number");
-        };
-
+    fn compare_numbers(&self, op: code::Opcode, lval: &NumberToken, rval: &NumberToken) -> bool {
         match op {
-            code::Opcode::Equal => self.push(self.bool_native_to_obj(lval == rval)),
-            code::Opcode::GT => self.push(self.bool_native_to_obj(lval > rval)),
-            code::Opcode::NotEqual => self.push(self.bool_native_to_obj(lval != rval)),
+            code::Opcode::Equal => lval == rval,
+            code::Opcode::GT => lval > rval,
+            code::Opcode::NotEqual => lval != rval,
 
             _ => panic!("unknown comparison"),
         }
     }
 
-    fn exe_binary_op(&mut self, op: code::Opcode) {
+    fn exe_binary_op(&mut self, op: code::Opcode) -> Result<(), Fault> {
         let right = self.pop();
         let left = self.pop();
         if right.get_type() == NUMBER_OBJ && left.get_type() == NUMBER_OBJ {
-            self.exe_binary_op_number(op, left, right)
+            self.exe_binary_op_number(op, left, right)?
         } else if right.get_type() == STRING_OBJ && left.get_type() == STRING_OBJ {
             if op != code::Opcode::Add {
                 panic!("only '+' is supported for strings")
             }
 
-            let Object::String { token : _, value : lval } = &*left else{
+            let Some(Object::String {
+                token: _,
+                value: lval,
+            }) = left.as_object()
+            else {
                 panic!("left object is not string")
             };
 
-            let Object::String { token : _, value : rval } = &*right else{
+            let Some(Object::String {
+                token: _,
+                value: rval,
+            }) = right.as_object()
+            else {
                 panic!("left object is not string")
             };
 
-            self.push(Rc::new(Object::String {
+            let s = self.alloc(Object::String {
                 token: None,
                 value: format!("{lval}{rval}"),
-            }));
+            })?;
+            self.push(s)?;
 
             //            self.exe_binary_op_str(op, left, right)
         }
+        Ok(())
     }
 
-    fn exe_binary_op_number(&mut self, op: code::Opcode, left: Rc<Object>, right: Rc<Object>) {
-        let Object::Number { token : _, value } = &*left else {
+    fn exe_binary_op_number(
+        &mut self,
+        op: code::Opcode,
+        left: Value,
+        right: Value,
+    ) -> Result<(), Fault> {
+        let Value::Number(lval) = left else {
             panic!("not a number")
         };
-        let lval = value.clone();
 
-        let Object::Number { token : _, value } = &*right else {
+        let Value::Number(rval) = right else {
             panic!("rval is not a number")
         };
 
-        let rval = value.clone();
         let value: NumberToken;
 
         match op {
@@ -767,26 +1085,26 @@ impl Vm {
             }
         }
 
-        self.push(Rc::new(Object::Number { token: None, value }));
+        self.push(Value::Number(value))
     }
 
-    fn push(&mut self, obj: Rc<Object>) {
-        if self.sp >= STACK_SIZE {
-            panic!("stack overflow");
-        }
-        if self.sp >= self.stack.len {
-            //self.stack.push(obj.to_owned())
-            self.stack.push(None, obj)
-        } else {
-            //self.stack[self.sp] = obj.to_owned()
-            self.stack.push(Some(self.sp), obj)
+    fn push(&mut self, obj: Value) -> Result<(), Fault> {
+        if self.sp >= self.stack_limit {
+            // blame the call into the running function, which is where a
+            // runaway recursion is
+            return Err(Fault {
+                kind: RuntimeErrorKind::StackOverflow {
+                    depth: self.frame_index,
+                },
+                in_caller: true,
+            });
         }
-        //self.stack[self.sp] = obj.clone();
-        //self.stack.push(obj.to_owned());
+        self.stack.push(Some(self.sp), obj);
         self.sp += 1;
+        Ok(())
     }
 
-    fn pop(&mut self) -> Rc<Object> {
+    fn pop(&mut self) -> Value {
         //let ip = self.sp - 1;
         //let obj = &self.stack[ip];
         //
@@ -807,6 +1125,16 @@ impl Vm {
     }
 
     pub fn last_pop(&self) -> Object {
-        self.last_popped.as_ref().to_owned()
+        self.last_popped.to_object().as_ref().to_owned()
+    }
+}
+
+impl Drop for Vm {
+    /// Nothing the program made can be reached once the VM is gone, so
+    /// break every cycle left
+    fn drop(&mut self) {
+        for cl in self.filled.drain(..).filter_map(|cl| cl.upgrade()) {
+            drop(cl.frees.take());
+        }
     }
 }
@@ -0,0 +1,104 @@
+//! What the VM keeps on its stack and in its globals. Numbers, booleans
+//! and null are stored as they are; strings, arrays, hashes and functions
+//! live on the heap behind an `Rc`, as `Object`s.
+//!
+//! Arrays, hashes and closures hold `Rc<Object>`s, so a number put in one
+//! is boxed there and unboxed again when read back out.
+
+use std::rc::Rc;
+
+use crate::{
+    bn::{render_number, NumeralStyle},
+    obj::{Object, BOOL_OBJ, NULL_OBJ, NUMBER_OBJ},
+    token::NumberToken,
+};
+
+#[derive(Debug, Default, Clone)]
+pub enum Value {
+    Number(NumberToken),
+    Bool(bool),
+    #[default]
+    Null,
+    Obj(Rc<Object>),
+}
+
+impl From<Rc<Object>> for Value {
+    fn from(obj: Rc<Object>) -> Self {
+        match obj.as_ref() {
+            Object::Number { value, .. } => Self::Number(value.clone()),
+            Object::Bool { value, .. } => Self::Bool(*value),
+            Object::Null => Self::Null,
+            _ => Self::Obj(obj),
+        }
+    }
+}
+
+impl From<NumberToken> for Value {
+    fn from(value: NumberToken) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Number(l), Self::Number(r)) => l == r,
+            (Self::Bool(l), Self::Bool(r)) => l == r,
+            (Self::Null, Self::Null) => true,
+            // string literals are interned by the compiler, so equal ones
+            // are usually the same object
+            (Self::Obj(l), Self::Obj(r)) => Rc::ptr_eq(l, r) || l == r,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// The value as an object, boxing scalars
+    pub fn to_object(&self) -> Rc<Object> {
+        match self {
+            Self::Number(value) => Rc::new(Object::Number {
+                token: None,
+                value: value.clone(),
+            }),
+            Self::Bool(value) => Rc::new(Object::Bool {
+                token: None,
+                value: *value,
+            }),
+            Self::Null => Rc::new(Object::Null),
+            Self::Obj(obj) => Rc::clone(obj),
+        }
+    }
+
+    /// The heap object, if this is one
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            Self::Obj(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    /// `Object::get_type` of the value
+    pub fn get_type(&self) -> u8 {
+        match self {
+            Self::Number(_) => NUMBER_OBJ,
+            Self::Bool(_) => BOOL_OBJ,
+            Self::Null => NULL_OBJ,
+            Self::Obj(obj) => obj.get_type(),
+        }
+    }
+
+    pub const fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Bool(false) | Self::Null)
+    }
+
+    /// See `Object::render`
+    pub fn render(&self, numerals: NumeralStyle) -> String {
+        match self {
+            Self::Number(value) => render_number(value, numerals),
+            Self::Bool(value) => value.to_string(),
+            Self::Null => "null".to_string(),
+            Self::Obj(obj) => obj.render(numerals),
+        }
+    }
+}
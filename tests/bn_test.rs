@@ -1,4 +1,5 @@
-use pras::bn::{is_bn_char, is_bn_num, parse_bn_num};
+use pras::bn::{is_bn_char, is_bn_num, is_bn_source, number_to_bn, parse_bn_num, to_bn_num};
+use pras::token::NumberToken;
 use std::collections::HashMap;
 
 #[test]
@@ -54,3 +55,36 @@ fn test_is_bn_char() {
         assert_eq!(is_bn_char(k), v);
     }
 }
+
+#[test]
+fn test_to_bn_num() {
+    let test_cases: HashMap<&str, &str> = HashMap::from([
+        ("100", "১০০"),
+        ("22", "২২"),
+        ("-9", "-৯"),
+        ("3.14", "৩.১৪"),
+        ("-0.5", "-০.৫"),
+        ("১২", "১২"),
+    ]);
+
+    for (k, v) in test_cases {
+        assert_eq!(to_bn_num(k), v.to_string());
+        assert_eq!(parse_bn_num(&to_bn_num(k)), parse_bn_num(k));
+    }
+}
+
+#[test]
+fn test_number_to_bn() {
+    assert_eq!(number_to_bn(&NumberToken::Int(2023)), "২০২৩");
+    assert_eq!(number_to_bn(&NumberToken::Int(-42)), "-৪২");
+    assert_eq!(number_to_bn(&NumberToken::Float(2.5)), "২.৫");
+    assert_eq!(number_to_bn(&NumberToken::Float(-0.25)), "-০.২৫");
+}
+
+#[test]
+fn test_is_bn_source() {
+    assert!(is_bn_source("ধরি ক = ১০\nদেখাও(ক)"));
+    assert!(!is_bn_source("dhori a = 10\ndekhao(a)"));
+    assert!(!is_bn_source("dhori a = \"আমার সোনার বাংলা\"\ndekhao(a)"));
+    assert!(!is_bn_source("# ধরি ক = ১০\ndekhao(1)"));
+}
@@ -1,6 +1,12 @@
 use std::collections::HashMap;
 
-use pras::{compiler::Compiler, lexer::Lexer, parser::Parser};
+use pras::{
+    compiler::{disasm::disassemble, Compiler},
+    lexer::Lexer,
+    obj::Object,
+    parser::Parser,
+    token::Span,
+};
 
 fn check_compiler_instr(src: &str, output: &str) {
     let lx = Lexer::new(src);
@@ -19,6 +25,10 @@ fn test_compiler() {
             "1+2",
             "0000 OpConst 0\n0003 OpConst 1\n0006 OpAdd\n0007 OpPop\n",
         ),
+        (
+            "\"a {1}\"",
+            "0000 OpConst 0\n0003 OpConst 1\n0006 OpToString\n0007 OpConcat 2\n0010 OpPop\n",
+        ),
         (
             "dhori global = 55
 
@@ -42,3 +52,177 @@ sesh",
         check_compiler_instr(k, v)
     }
 }
+
+#[test]
+fn test_line_table() {
+    let src = "dhori a = 1\nshow(a + 2 * 3)\nekti kaj(x)\n    x - 1\nsesh";
+    let mut p = Parser::new(Lexer::new(src));
+    let prog = p.parse_program().expect("parsed AST has errors");
+    let bc = Compiler::new().compile(prog);
+    let text = |span: Span| &src[span.start..span.end];
+
+    // instruction offset -> source of the node it was emitted for
+    let test_cases = HashMap::from([
+        (0, "1"),                // OpConst
+        (3, "dhori a = 1"),      // OpSetGlobal
+        (6, "a"),                // OpGetGlobal
+        (15, "2 * 3"),           // OpMul
+        (16, "a + 2 * 3"),       // OpAdd
+        (17, "show(a + 2 * 3)"), // OpShow
+    ]);
+    for (pos, covered) in test_cases {
+        let span = bc.lines.lookup(pos).expect("no line entry");
+        assert_eq!(text(span), covered, "at {pos}");
+    }
+
+    let fun = bc
+        .constants
+        .iter()
+        .find_map(|c| match c.as_ref() {
+            Object::Compfunc(f) => Some(f.clone()),
+            _ => None,
+        })
+        .expect("no function constant");
+    // OpGetLocal 0, OpConst 0, OpSub
+    let sub = fun.lines.lookup(5).expect("no line entry");
+    assert_eq!(text(sub), "x - 1");
+    assert_eq!((sub.line, sub.col), (4, 5));
+}
+
+#[test]
+fn test_disassembler() {
+    let src = "dhori f = ekti kaj(x)
+    jodi (x > 1) tahole \"big\" nahole ekti kaj() x sesh sesh
+sesh
+f(2)";
+    let mut p = Parser::new(Lexer::new(src));
+    let prog = p.parse_program().expect("parsed AST has errors");
+    let bc = Compiler::new().compile(prog);
+
+    let expected = "== <main> ==
+   1 | dhori f = ekti kaj(x)
+0000 OpClosure 3 0             ; <fn f>
+0004 OpSetGlobal 0
+   4 | f(2)
+0007 OpGetGlobal 0
+0010 OpConst 4                 ; 2
+0013 OpCall 1
+0015 OpPop
+
+== fn f (constant 3, params 1, locals 1) ==
+   2 |     jodi (x > 1) tahole \"big\" nahole ekti kaj() x sesh sesh
+0000 OpGetLocal 0
+0002 OpConst 0                 ; 1
+0005 OpGT
+0006 OpJumpNotTruthy 15        ; L0
+0009 OpConst 1                 ; \"big\"
+0012 OpJump 21                 ; L1
+L0:
+0015 OpGetLocal 0
+0017 OpClosure 2 1             ; <fn <anonymous>>
+L1:
+0021 OpReturnValue
+
+== fn <anonymous> (constant 2, params 0, locals 0) ==
+   2 |     jodi (x > 1) tahole \"big\" nahole ekti kaj() x sesh sesh
+0000 OpGetFree 0
+0002 OpReturnValue
+";
+    assert_eq!(disassemble(&bc, Some(src)), expected);
+    // without the source, only the code
+    assert!(!bc.to_string().contains(" | "));
+    assert!(bc
+        .to_string()
+        .contains("0006 OpJumpNotTruthy 15        ; L0\n"));
+}
+
+#[test]
+fn test_constant_pool_dedup() {
+    // source -> the constant pool, as the disassembler describes it
+    let test_cases = HashMap::from([
+        ("1 + 1 + 1", vec!["1"]),
+        ("1 + 1.0 + 1", vec!["1", "1"]),
+        ("0.0 + -0.0", vec!["0"]),
+        ("\"a\" + \"b\" + \"a\"", vec!["a", "b"]),
+        ("\"{x} and {x}\"; \" and \"", vec![" and "]),
+        ("[ekti kaj() 1 sesh, ekti kaj() 1 sesh]", vec!["1", "FN"]),
+        (
+            "dhori f = ekti kaj() 1 sesh; dhori g = ekti kaj() 1 sesh",
+            vec!["1", "FN", "FN"],
+        ),
+        (
+            "ekti kaj() 2 sesh; ekti kaj(a) 2 sesh",
+            vec!["2", "FN", "FN"],
+        ),
+    ]);
+
+    for (src, pool) in test_cases {
+        let src = format!("dhori x = 0; {src}");
+        let mut p = Parser::new(Lexer::new(&src));
+        let prog = p.parse_program().expect("parsed AST has errors");
+        let bc = Compiler::new().compile(prog);
+        let consts: Vec<String> = bc.constants[1..]
+            .iter()
+            .map(|c| match c.as_ref() {
+                Object::Compfunc(_) => "FN".to_string(),
+                other => other.to_string(),
+            })
+            .collect();
+        assert_eq!(consts, pool, "{src}");
+    }
+}
+
+#[test]
+fn test_tail_calls() {
+    // source -> code of its one function
+    let test_cases = HashMap::from([
+        (
+            "dhori f = ekti kaj(x) ferao(f(x)) sesh",
+            "0000 OpCurrentClosure\n0001 OpGetLocal 0\n0003 OpTailCall 1\n0005 OpReturnValue\n",
+        ),
+        // the call is not the last thing the function does
+        (
+            "dhori f = ekti kaj(x) ferao(f(x) + 1) sesh",
+            "0000 OpCurrentClosure\n0001 OpGetLocal 0\n0003 OpCall 1\n0005 OpConst 0\n0008 OpAdd\n0009 OpReturnValue\n",
+        ),
+        (
+            "ekti kaj(g) ferao(g()) sesh",
+            "0000 OpGetLocal 0\n0002 OpTailCall 0\n0004 OpReturnValue\n",
+        ),
+    ]);
+
+    for (src, code) in test_cases {
+        let mut p = Parser::new(Lexer::new(src));
+        let bc = Compiler::new().compile(p.parse_program().expect("parsed AST has errors"));
+        let fun = bc
+            .constants
+            .iter()
+            .find_map(|c| match c.as_ref() {
+                Object::Compfunc(f) => Some(f.fnin.to_string()),
+                _ => None,
+            })
+            .expect("no function constant");
+        assert_eq!(fun, code, "{src}");
+    }
+}
+
+#[test]
+fn test_capture_of_binding() {
+    // the local is captured once `OpSetLocal` has set it
+    let src = "ekti kaj() dhori h = {\"f\": ekti kaj() h sesh}; h sesh";
+    let mut p = Parser::new(Lexer::new(src));
+    let bc = Compiler::new().compile(p.parse_program().expect("parsed AST has errors"));
+    let outer = bc
+        .constants
+        .iter()
+        .rev()
+        .find_map(|c| match c.as_ref() {
+            Object::Compfunc(f) => Some(f.fnin.to_string()),
+            _ => None,
+        })
+        .expect("no function constant");
+    assert_eq!(
+        outer,
+        "0000 OpConst 0\n0003 OpNull\n0004 OpClosure 1 1\n0008 OpDeferFree 0 0\n0011 OpHash 2\n0014 OpSetLocal 0\n0016 OpFillFrees 0\n0018 OpGetLocal 0\n0020 OpReturnValue\n"
+    );
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use pras::{
+    compiler::Compiler,
+    export::{bytecode_json, program_json, tokens_json, SCHEMA_VERSION},
+    json::Json,
+    lexer::Lexer,
+    parser::Parser,
+};
+
+fn ast(src: &str) -> Json {
+    let mut p = Parser::new(Lexer::new(src));
+    program_json(&p.parse_program().expect("parsed AST has errors"))
+}
+
+fn bytecode(src: &str) -> Json {
+    let mut p = Parser::new(Lexer::new(src));
+    let prog = p.parse_program().expect("parsed AST has errors");
+    bytecode_json(&Compiler::new().compile(prog))
+}
+
+fn strs<'a>(items: &'a Json, key: &str) -> Vec<&'a str> {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i.get(key).and_then(Json::as_str).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_tokens_json() {
+    let out = tokens_json(Lexer::new("ধরি ক = ১০;")).unwrap();
+    assert_eq!(
+        out.get("version").and_then(Json::as_usize),
+        Some(SCHEMA_VERSION)
+    );
+
+    let tokens = out.get("tokens").unwrap();
+    assert_eq!(
+        strs(tokens, "kind"),
+        vec!["Let", "Ident", "Eq", "Number", "Semicolon", "Eof"]
+    );
+    assert_eq!(strs(tokens, "literal")[3], "10");
+
+    let number = &tokens.as_array().unwrap()[3];
+    let span: Vec<usize> = ["start", "end", "char_start", "char_end", "line", "col"]
+        .iter()
+        .map(|k| number.path(&["span", k]).and_then(Json::as_usize).unwrap())
+        .collect();
+    assert_eq!(span, vec![16, 22, 8, 10, 1, 9]);
+
+    assert!(tokens_json(Lexer::new("dhori a = \"open")).is_err());
+}
+
+#[test]
+fn test_ast_json() {
+    // source -> kinds along a path from the first statement
+    let test_cases = HashMap::from([
+        (
+            "1 + 2 * 3",
+            vec![
+                (vec!["kind"], "ExprStmt"),
+                (vec!["expr", "kind"], "InfixExpr"),
+                (vec!["expr", "op"], "+"),
+                (vec!["expr", "right", "op"], "*"),
+                (vec!["expr", "right", "left", "kind"], "NumExpr"),
+            ],
+        ),
+        (
+            "## doc\ndhori f = ekti kaj(x) ferao(-x) sesh",
+            vec![
+                (vec!["kind"], "LetStmt"),
+                (vec!["name", "name"], "f"),
+                (vec!["doc"], "doc"),
+                (vec!["value", "name"], "f"),
+                (vec!["value", "body", "kind"], "BlockStmt"),
+            ],
+        ),
+        (
+            "jodi (a ebong b) tahole show(1) nahole 2 sesh",
+            vec![
+                (vec!["expr", "kind"], "IfExpr"),
+                (vec!["expr", "cond", "op"], "and"),
+                (vec!["expr", "then", "kind"], "BlockStmt"),
+            ],
+        ),
+        (
+            "{\"k\": [1]}[\"k\"]",
+            vec![
+                (vec!["expr", "kind"], "IndexExpr"),
+                (vec!["expr", "left", "kind"], "HashExpr"),
+                (vec!["expr", "index", "value"], "k"),
+            ],
+        ),
+    ]);
+
+    for (src, paths) in test_cases {
+        let out = ast(src);
+        let first = &out.path(&["program", "body"]).unwrap().as_array().unwrap()[0];
+        for (path, expected) in paths {
+            assert_eq!(
+                first.path(&path).and_then(Json::as_str),
+                Some(expected),
+                "{src}: {path:?}"
+            );
+        }
+    }
+
+    let out = ast("jodi (a) tahole 1 nahole sesh");
+    let stmt = &out.path(&["program", "body"]).unwrap().as_array().unwrap()[0];
+    assert!(stmt.path(&["expr", "else"]).unwrap().is_null());
+    assert_eq!(
+        stmt.path(&["expr", "span", "end"]).and_then(Json::as_usize),
+        Some(29)
+    );
+}
+
+#[test]
+fn test_bytecode_json() {
+    let out = bytecode("dhori f = ekti kaj(x) x + 10 sesh\nf(\"a\")");
+    let ins = out.get("instructions").unwrap();
+    assert_eq!(
+        strs(ins, "op"),
+        vec![
+            "OpClosure",
+            "OpSetGlobal",
+            "OpGetGlobal",
+            "OpConst",
+            "OpCall",
+            "OpPop"
+        ]
+    );
+    let closure = &ins.as_array().unwrap()[0];
+    assert_eq!(closure.get("operands").unwrap().to_string(), "[1,0]");
+    assert_eq!(
+        closure.path(&["span", "line"]).and_then(Json::as_usize),
+        Some(1)
+    );
+
+    let constants = out.get("constants").unwrap().as_array().unwrap();
+    assert_eq!(constants.len(), 3);
+    assert_eq!(
+        constants[0].to_string(),
+        r#"{"index":0,"type":"number","value":10,"int":true}"#
+    );
+    assert_eq!(
+        constants[1].get("type").and_then(Json::as_str),
+        Some("function")
+    );
+    assert_eq!(
+        strs(constants[1].get("instructions").unwrap(), "op"),
+        vec!["OpGetLocal", "OpConst", "OpAdd", "OpReturnValue"]
+    );
+    assert_eq!(constants[2].get("value").and_then(Json::as_str), Some("a"));
+}
+
+#[test]
+fn test_pretty_json_parses_back() {
+    let out = bytecode("show([1, 2.5], {\"a\": ekti kaj() 1 sesh})");
+    let pretty = format!("{out:#}");
+    assert!(pretty.starts_with("{\n  \"version\": 1,\n  \"instructions\": [\n    {"));
+    assert_eq!(Json::parse(&pretty), Ok(out));
+    assert_eq!(
+        format!("{:#}", Json::parse("[[], {}]").unwrap()),
+        "[\n  [],\n  {}\n]"
+    );
+}
@@ -0,0 +1,182 @@
+use pras::{
+    formatter::{format_source, FmtError, FmtOptions},
+    lexer::Lexer,
+    parser::Parser,
+};
+
+fn fmt(src: &str) -> String {
+    format_source(src, None, FmtOptions::default()).expect("format error")
+}
+
+fn ast(src: &str) -> String {
+    let mut p = Parser::new(Lexer::new(src));
+    p.parse_program().expect("parser error").to_string()
+}
+
+#[test]
+fn test_fmt_layout() {
+    let src = "dhori fib = ekti kaj(x)
+jodi (x==0) tahole ferao(0) nahole
+      ferao(fib(x-1)+fib(x-2)) sesh sesh
+dekhao(fib(10),[1,2],{\"a\":-(1+2)*3})";
+    let expected = "dhori fib = ekti kaj(x)
+    jodi (x == 0) tahole
+        ferao(0)
+    nahole
+        ferao(fib(x - 1) + fib(x - 2))
+    sesh
+sesh
+dekhao(fib(10), [1, 2], {\"a\": -(1 + 2) * 3})
+";
+    assert_eq!(fmt(src), expected);
+}
+
+#[test]
+fn test_fmt_keyword_style() {
+    let test_cases = vec![
+        (
+            "let a = if (true) then 1 else 2 end",
+            "let a = if (true) then\n    1\nelse\n    2\nend\n",
+        ),
+        (
+            "ধরি ক = একটি কাজ() যদি (সত্যি) তাহলে 1 নাহলে শেষ শেষ",
+            "ধরি ক = একটি কাজ()\n    যদি (সত্যি) তাহলে\n        1\n    নাহলে\n    শেষ\nশেষ\n",
+        ),
+        (
+            "# lang: hi\nमाना x = एक कार्य(क) लौटाओ(क) अंत",
+            "# lang: hi\nमाना x = एक कार्य(क)\n    लौटाओ(क)\nअंत\n",
+        ),
+        (
+            "jotokhon a < 3: dhori a = a + 1 sesh",
+            "jotokhon a < 3:\n    dhori a = a + 1\nsesh\n",
+        ),
+    ];
+
+    for (src, expected) in test_cases {
+        assert_eq!(fmt(src), expected);
+    }
+}
+
+#[test]
+fn test_fmt_comments() {
+    let src = "# header
+
+
+## doc for f
+dhori f = ekti kaj(x)   # on the header
+    # first
+  ferao(x)   #[ trailing block ]#
+      # last in body
+sesh # after end
+show(f(1)) # shown
+
+
+# at the end";
+    let expected = "# header
+
+## doc for f
+dhori f = ekti kaj(x)  # on the header
+    # first
+    ferao(x)  #[ trailing block ]#
+    # last in body
+sesh  # after end
+show(f(1))  # shown
+
+# at the end
+";
+    assert_eq!(fmt(src), expected);
+}
+
+#[test]
+fn test_fmt_literals_as_written() {
+    let src = "show(0x1F, 1_000, ১২, 2.5e3, r\"raw\\n\", \"esc\\t\", \"a {b+1}{c} d\", \"{x}\")";
+    let expected =
+        "show(0x1F, 1_000, ১২, 2.5e3, r\"raw\\n\", \"esc\\t\", \"a {b + 1}{c} d\", \"{x}\")\n";
+    assert_eq!(fmt(src), expected);
+}
+
+#[test]
+fn test_fmt_wrapping() {
+    let src = "show(first_argument, second_argument, [third_argument, fourth_argument])";
+    let opts = FmtOptions {
+        width: 40,
+        ..Default::default()
+    };
+    let expected = "show(
+    first_argument,
+    second_argument,
+    [third_argument, fourth_argument]
+)
+";
+    assert_eq!(format_source(src, None, opts).unwrap(), expected);
+
+    let opts = FmtOptions {
+        width: 20,
+        indent: 2,
+    };
+    let expected = "show(
+  first_argument,
+  second_argument,
+  [
+    third_argument,
+    fourth_argument
+  ]
+)
+";
+    assert_eq!(format_source(src, None, opts).unwrap(), expected);
+}
+
+#[test]
+fn test_fmt_semicolons() {
+    // only where the next statement would go on with the expression before it
+    let src = "dhori a = 1; (a + 1) * 2; dhori b = a; [b]; f(); -1; jodi (a) tahole 1 nahole 2 sesh; (3 + 1) * 2; ferao(4); (5 + 1) * 2; show(6); -7";
+    let expected = "dhori a = 1;
+(a + 1) * 2
+dhori b = a;
+[b]
+f();
+-1
+jodi (a) tahole
+    1
+nahole
+    2
+sesh;
+(3 + 1) * 2
+ferao(4)
+(5 + 1) * 2
+show(6)
+-7
+";
+    assert_eq!(fmt(src), expected);
+    assert_eq!(ast(&fmt(src)), ast(src));
+}
+
+#[test]
+fn test_fmt_preserves_meaning_and_is_idempotent() {
+    let inputs = vec![
+        "1 - (2 - 3) - 4 * (5 + 6) / -(7)",
+        "!(a == b) ebong (c ba d)",
+        "f(g(1), [h()][0], {1: ekti kaj(x) ferao(x) sesh})",
+        "dhori a = [1, 2, 3]\ndhori b = a[1]\n\n\n\nshow(a, b)",
+        "dhori x = jodi (a) tahole jodi (b) tahole 1 nahole 2 sesh nahole sesh",
+        "dhori f = ekti kaj() 1 sesh; (f)(); [1][0]; f; -2",
+    ];
+
+    for src in inputs {
+        let once = fmt(src);
+        assert_eq!(ast(&once), ast(src), "{once}");
+        assert_eq!(fmt(&once), once);
+    }
+}
+
+#[test]
+fn test_fmt_errors() {
+    assert!(matches!(
+        format_source("dhori = ;", None, FmtOptions::default()),
+        Err(FmtError::Parser(_))
+    ));
+    assert!(matches!(
+        format_source("show(\"open", None, FmtOptions::default()),
+        Err(FmtError::Lexer(_))
+    ));
+}
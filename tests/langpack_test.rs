@@ -0,0 +1,141 @@
+use pras::langpack::{find_pragma, LangPack};
+use pras::lexer::Lexer;
+use pras::token::TokenType;
+
+#[test]
+fn test_builtin_packs() {
+    let test_cases = vec![
+        ("bn", "ধরি", TokenType::Let),
+        ("bn", "dhori", TokenType::Let),
+        ("bn", "let", TokenType::Let),
+        ("as", "ধৰা", TokenType::Let),
+        ("as", "আৰু", TokenType::And),
+        ("as", "ঘূৰাই", TokenType::Return),
+        ("hi", "माना", TokenType::Let),
+        ("hi", "यदि", TokenType::If),
+        ("hi", "तोड़ो", TokenType::Break),
+        ("hi", "while", TokenType::While),
+    ];
+
+    for (pack, id, tt) in test_cases {
+        let pack = LangPack::builtin(pack).expect("builtin pack");
+        assert_eq!(pack.lookup(id), Some(tt), "{id}");
+    }
+
+    let hi = LangPack::builtin("hi").unwrap();
+    assert_eq!(hi.lookup("ধরি"), None);
+    assert_eq!(hi.lookup("dhori"), None);
+    assert_eq!(hi.native_spelling(&TokenType::Show), Some("दिखाओ"));
+    assert_eq!(LangPack::builtin_names(), vec!["bn", "as", "hi"]);
+}
+
+#[test]
+fn test_parse_pack() {
+    let pack = LangPack::parse(
+        "# test pack
+name = test
+script = devanagari
+
+let = rakho रखो
+show = dikhao
+",
+    )
+    .expect("valid pack");
+    assert_eq!(pack.name, "test");
+    assert_eq!(pack.lookup("रखो"), Some(TokenType::Let));
+    assert_eq!(pack.lookup("rakho"), Some(TokenType::Let));
+    assert_eq!(pack.lookup("if"), Some(TokenType::If));
+    assert_eq!(pack.native_spelling(&TokenType::Show), None);
+
+    let errors = vec![
+        ("name = x\nscript = latin", "line 2: unknown script `latin`"),
+        (
+            "name = x\nscript = bengali\nlett = a",
+            "line 3: unknown keyword `lett`",
+        ),
+        (
+            "name = x\nscript = bengali\nif = a\nelse = a",
+            "line 4: `a` is already a spelling of If",
+        ),
+        ("name = x\nif", "line 2: expected `key = value`, found `if`"),
+        ("script = bengali", "language pack has no `name`"),
+    ];
+    for (def, msg) in errors {
+        let err = LangPack::parse(def).expect_err(def);
+        assert_eq!(err.to_string(), msg);
+    }
+}
+
+#[test]
+fn test_find_pragma() {
+    let test_cases = vec![
+        ("# lang: hi\nx", Some(("hi", 1))),
+        (
+            "#!/usr/bin/env pras\n\n# some comment\n#bhasha: as\nx",
+            Some(("as", 4)),
+        ),
+        ("x\n# lang: hi", None),
+        ("## lang: hi\nx", None),
+        ("", None),
+    ];
+
+    for (src, expected) in test_cases {
+        assert_eq!(find_pragma(src), expected, "{src:?}");
+    }
+}
+
+fn token_types(mut lx: Lexer) -> Vec<(TokenType, String)> {
+    let mut tokens = vec![];
+    loop {
+        let t = lx.next_token().expect("lexer error");
+        if t.ttype == TokenType::Eof {
+            return tokens;
+        }
+        tokens.push((t.ttype, t.literal.clone()));
+    }
+}
+
+#[test]
+fn test_lexer_hindi_pragma() {
+    let lx = Lexer::new("# lang: hi\nमाना गिनती = १२३;");
+    assert_eq!(lx.pack().name, "hi");
+    assert_eq!(
+        token_types(lx),
+        vec![
+            (TokenType::Let, "माना".to_string()),
+            (TokenType::Ident, "गिनती".to_string()),
+            (TokenType::Eq, "=".to_string()),
+            (TokenType::Number, "123".to_string()),
+            (TokenType::Semicolon, ";".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_lexer_script_follows_pack() {
+    // Bengali letters are not identifier characters in the Hindi pack
+    let mut lx = Lexer::with_pack("ক", LangPack::builtin("hi").unwrap());
+    assert_eq!(lx.next_token().unwrap().ttype, TokenType::Illegal);
+
+    // ... and Devanagari ones are not in the Bengali pack
+    let mut lx = Lexer::new("क");
+    assert_eq!(lx.next_token().unwrap().ttype, TokenType::Illegal);
+
+    // an explicit pack wins over the pragma
+    let lx = Lexer::with_pack("# lang: hi\nধৰা ৰং", LangPack::builtin("as").unwrap());
+    assert_eq!(
+        token_types(lx),
+        vec![
+            (TokenType::Let, "ধৰা".to_string()),
+            (TokenType::Ident, "ৰং".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_lexer_unknown_pragma_pack() {
+    let mut lx = Lexer::new("\n# lang: xx\nlet a = 1;");
+    let err = lx.next_token().expect_err("unknown pack");
+    assert_eq!(err.to_string(), "2:1: unknown language pack `xx`");
+    assert_eq!(lx.next_token().unwrap().ttype, TokenType::Let);
+}
@@ -1,4 +1,6 @@
-use pras::lexer::Lexer;
+use std::collections::HashMap;
+
+use pras::lexer::{Lexer, LexerError};
 use pras::token::TokenType;
 
 #[test]
@@ -26,3 +28,352 @@ fn test_lexer_next_token() {
         }
     }
 }
+
+fn number_literal(input: &str) -> Result<String, LexerError> {
+    let mut lx = Lexer::new(input);
+    lx.next_token().map(|t| {
+        assert_eq!(t.ttype, TokenType::Number);
+        t.literal.clone()
+    })
+}
+
+#[test]
+fn test_lexer_numbers() {
+    let test_cases = HashMap::from([
+        ("100", "100"),
+        ("১০০", "100"),
+        ("1_000_000", "1000000"),
+        ("১_০০০", "1000"),
+        ("3.14", "3.14"),
+        ("৩.১৪", "3.14"),
+        ("1_000.000_1", "1000.0001"),
+        ("1e9", "1e9"),
+        ("2.5E-3", "2.5e-3"),
+        ("১e৯", "1e9"),
+        ("1e+2", "1e+2"),
+        ("0x1F", "31"),
+        ("0xff_ff", "65535"),
+        ("0b1010", "10"),
+        ("০b১০১০", "10"),
+        ("0o17", "15"),
+        ("০o৭৭", "63"),
+    ]);
+
+    for (k, v) in test_cases {
+        assert_eq!(number_literal(k).ok(), Some(v.to_string()), "{k}");
+    }
+}
+
+#[test]
+fn test_lexer_number_errors() {
+    let test_cases = HashMap::from([
+        ("1__0", (1, 3)),
+        ("1_", (1, 3)),
+        ("1._5", (1, 3)),
+        ("12.", (1, 4)),
+        ("1e", (1, 3)),
+        ("1e+", (1, 4)),
+        ("0x", (1, 3)),
+        ("0b102", (1, 5)),
+        ("0o8", (1, 3)),
+        ("12abc", (1, 3)),
+        ("1.2.3", (1, 4)),
+        ("0xffffffffffffffffff", (1, 21)),
+        ("\n\n  ১২ক", (3, 5)),
+    ]);
+
+    for (k, (line, col)) in test_cases {
+        let err = number_literal(k).expect_err(k);
+        assert_eq!((err.lineno, err.colno), (line, col), "{k} -> {}", err.msg);
+        assert_eq!(err.token.map(|t| t.lineno), Some(line));
+    }
+}
+
+#[test]
+fn test_lexer_skips_bad_number() {
+    let mut lx = Lexer::new("1__2abc + 3");
+    assert!(lx.next_token().is_err());
+    assert_eq!(lx.next_token().ok().map(|t| t.ttype), Some(TokenType::Plus));
+    assert_eq!(
+        lx.next_token().ok().map(|t| t.literal.clone()),
+        Some("3".to_string())
+    );
+}
+
+fn string_literal(input: &str) -> Result<String, LexerError> {
+    let mut lx = Lexer::new(input);
+    lx.next_token().map(|t| {
+        assert_eq!(t.ttype, TokenType::String);
+        t.literal.clone()
+    })
+}
+
+#[test]
+fn test_lexer_strings() {
+    let test_cases = HashMap::from([
+        ("\"hello\"", "hello"),
+        ("\"a\\nb\"", "a\nb"),
+        ("\"tab\\there\"", "tab\there"),
+        ("\"say \\\"hi\\\"\"", "say \"hi\""),
+        ("\"back\\\\slash\"", "back\\slash"),
+        ("\"\\u{0985}\\u{9a8}\"", "অন"),
+        ("\"\\u{1F600}\"", "\u{1F600}"),
+        ("r\"C:\\new\\u\"", "C:\\new\\u"),
+        (
+            "\"\"\"line 1\nline \"2\" end\"\"\"",
+            "line 1\nline \"2\" end",
+        ),
+        ("r\"\"\"raw\\n\n\"\"\"", "raw\\n\n"),
+        ("\"\"", ""),
+    ]);
+
+    for (k, v) in test_cases {
+        assert_eq!(string_literal(k).ok(), Some(v.to_string()), "{k}");
+    }
+}
+
+#[test]
+fn test_lexer_string_errors() {
+    let test_cases = HashMap::from([
+        ("\"abc", (1, 1)),
+        ("dhori a =\n  \"abc\n", (2, 3)),
+        ("\"\"\"abc\"\"", (1, 1)),
+        ("\"a\\qb\"", (1, 3)),
+        ("\"\\u{110000}\"", (1, 2)),
+        ("\"\\u0985\"", (1, 2)),
+        ("\"\\u{}\"", (1, 2)),
+    ]);
+
+    for (k, (line, col)) in test_cases {
+        let mut lx = Lexer::new(k);
+        let mut res = lx.next_token();
+        while let Ok(t) = &res {
+            if t.ttype == TokenType::Eof {
+                break;
+            }
+            res = lx.next_token();
+        }
+        let err = res.expect_err(k);
+        assert_eq!((err.lineno, err.colno), (line, col), "{k} -> {}", err.msg);
+    }
+}
+
+#[test]
+fn test_lexer_multiline_string_lines() {
+    let mut lx = Lexer::new("\"\"\"a\nb\nc\"\"\" x \"bad\\z\" y");
+    let s = lx.next_token().ok().unwrap();
+    assert_eq!((s.ttype, s.lineno), (TokenType::String, 1));
+    let x = lx.next_token().ok().unwrap();
+    assert_eq!((x.literal.as_str(), x.lineno, x.colno), ("x", 3, 6));
+    assert!(lx.next_token().is_err());
+    let y = lx.next_token().ok().unwrap();
+    assert_eq!((y.literal.as_str(), y.lineno), ("y", 3));
+}
+
+#[test]
+fn test_lexer_interpolation() {
+    let input = "\"নাম {name}, বয়স {age + 1}!\" \"{ {1: \"{x}\"}[1] }\" \"{{raw}}\"";
+    let expected = vec![
+        (TokenType::InterpStart, "নাম "),
+        (TokenType::Ident, "name"),
+        (TokenType::InterpMid, ", বয়স "),
+        (TokenType::Ident, "age"),
+        (TokenType::Plus, "+"),
+        (TokenType::Number, "1"),
+        (TokenType::InterpEnd, "!"),
+        (TokenType::InterpStart, ""),
+        (TokenType::Lbrace, "{"),
+        (TokenType::Number, "1"),
+        (TokenType::Colon, ":"),
+        (TokenType::InterpStart, ""),
+        (TokenType::Ident, "x"),
+        (TokenType::InterpEnd, ""),
+        (TokenType::Rbrace, "}"),
+        (TokenType::LSBracket, "["),
+        (TokenType::Number, "1"),
+        (TokenType::RSBracket, "]"),
+        (TokenType::InterpEnd, ""),
+        (TokenType::String, "{raw}"),
+        (TokenType::Eof, "\0"),
+    ];
+
+    let mut lx = Lexer::new(input);
+    for (tt, lit) in expected {
+        let t = lx.next_token().ok().unwrap();
+        assert_eq!((t.ttype, t.literal.as_str()), (tt, lit));
+    }
+}
+
+#[test]
+fn test_lexer_comments() {
+    let input = "# first comment
+# second comment
+
+   # indented comment
+a #[ block #[ nested ]# still
+comment ]# + b # trailing
+## doc for c
+##   indented doc
+c";
+    let expected = vec![
+        (TokenType::Ident, "a", 5),
+        (TokenType::Plus, "+", 6),
+        (TokenType::Ident, "b", 6),
+        (TokenType::DocComment, "doc for c", 7),
+        (TokenType::DocComment, "  indented doc", 8),
+        (TokenType::Ident, "c", 9),
+        (TokenType::Eof, "\0", 9),
+    ];
+
+    let mut lx = Lexer::new(input);
+    for (tt, lit, line) in expected {
+        let t = lx.next_token().ok().unwrap();
+        assert_eq!((t.ttype, t.literal.as_str(), t.lineno), (tt, lit, line));
+    }
+}
+
+#[test]
+fn test_lexer_unterminated_block_comment() {
+    let mut lx = Lexer::new("a\n  #[ outer #[ inner ]#\n b");
+    assert_eq!(
+        lx.next_token().ok().map(|t| t.ttype),
+        Some(TokenType::Ident)
+    );
+    let err = lx.next_token().expect_err("block comment is not closed");
+    assert_eq!((err.lineno, err.colno), (2, 3));
+}
+
+#[test]
+fn test_lexer_unicode_identifiers() {
+    let input = "x1 a_b_2 কলম২ র\u{200d}্য স\u{200c}্ক \u{09af}\u{09bc} \u{09df} আন\u{09df}ন";
+    let expected = vec![
+        (TokenType::Ident, "x1"),
+        (TokenType::Ident, "a_b_2"),
+        (TokenType::Ident, "কলম২"),
+        (TokenType::Ident, "র\u{200d}্য"),
+        (TokenType::Ident, "স\u{200c}্ক"),
+        (TokenType::Ident, "\u{09af}\u{09bc}"),
+        (TokenType::Ident, "\u{09af}\u{09bc}"),
+        (TokenType::Include, "আন\u{09af}\u{09bc}ন"),
+        (TokenType::Eof, "\0"),
+    ];
+
+    let mut lx = Lexer::new(input);
+    for (tt, lit) in expected {
+        let t = lx.next_token().ok().unwrap();
+        assert_eq!((t.ttype, t.literal.as_str()), (tt, lit));
+    }
+}
+
+#[test]
+fn test_lexer_identifier_start() {
+    // a digit, `_` or a combining mark can not start an identifier
+    let expected = vec![
+        ("_a", TokenType::Illegal),
+        ("\u{09be}ক", TokenType::Illegal),
+        ("\u{200d}ক", TokenType::Illegal),
+        ("২ক", TokenType::Illegal),
+    ];
+
+    for (input, tt) in expected {
+        let mut lx = Lexer::new(input);
+        let t = lx
+            .next_token()
+            .map(|t| t.ttype)
+            .unwrap_or(TokenType::Illegal);
+        assert_eq!(t, tt, "{input:?}");
+    }
+}
+
+fn lossless_concat(input: &str) -> String {
+    Lexer::new(input)
+        .lossless_tokens()
+        .expect("lexer error")
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+
+#[test]
+fn test_lexer_lossless_round_trip() {
+    let inputs = vec![
+        "",
+        "   \n\t\n",
+        "# only a comment",
+        "let a = 5;\nlet b = a + 10;\n",
+        "ধরি ফল = একটি কাজ(x)   # মন্তব্য\r\n\tferao(x * ০x১f)\r\nsesh;\r\n",
+        "## doc\n## more doc\nlet f = fn() 1_000 end; #[ block #[ nested ]#\n comment ]#  show(f())",
+        "show(\"a {b + 1} c {{d}}\" + r\"raw\\n\" + \"\"\"multi\nline {x}\"\"\")   \n\n",
+        "# lang: hi\nमाना x = १२;  ",
+    ];
+
+    for input in inputs {
+        assert_eq!(lossless_concat(input), input);
+    }
+}
+
+#[test]
+fn test_lexer_lossless_trivia() {
+    use pras::token::TriviaKind;
+
+    let tokens = Lexer::new("# head\n\nlet x  # tail\n  = 1")
+        .lossless_tokens()
+        .unwrap();
+
+    let let_tok = &tokens[0];
+    assert_eq!(let_tok.text, "let");
+    let kinds: Vec<TriviaKind> = let_tok.leading.iter().map(|t| t.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            TriviaKind::Comment,
+            TriviaKind::Newline,
+            TriviaKind::Newline
+        ]
+    );
+    assert_eq!(let_tok.trailing[0].text, " ");
+
+    // a token owns the rest of its line
+    let x_tok = &tokens[1];
+    let trailing: Vec<&str> = x_tok.trailing.iter().map(|t| t.text).collect();
+    assert_eq!(trailing, vec!["  ", "# tail", "\n"]);
+
+    let eq_tok = &tokens[2];
+    assert_eq!(eq_tok.leading[0].kind, TriviaKind::Whitespace);
+    assert_eq!(eq_tok.leading[0].text, "  ");
+
+    let eof = tokens.last().unwrap();
+    assert_eq!(eof.token.ttype, TokenType::Eof);
+    assert_eq!(eof.text, "");
+}
+
+#[test]
+fn test_lexer_token_spans() {
+    let input = "ধরি ক = 0x1_0;";
+    let mut lx = Lexer::new(input);
+    let expected = vec![
+        ("ধরি", 0, 9, 0, 3),
+        ("ক", 10, 13, 4, 5),
+        ("=", 14, 15, 6, 7),
+        ("0x1_0", 16, 21, 8, 13),
+        (";", 21, 22, 13, 14),
+        ("", 22, 22, 14, 14),
+    ];
+
+    for (text, start, end, char_start, char_end) in expected {
+        let t = lx.next_token().unwrap();
+        let s = t.span;
+        assert_eq!(&input[s.start..s.end], text);
+        assert_eq!(
+            (s.start, s.end, s.char_start, s.char_end),
+            (start, end, char_start, char_end)
+        );
+    }
+
+    // error tokens have spans too
+    let err = Lexer::new("a = 12ab;")
+        .next_token()
+        .and(Lexer::new("12ab").next_token());
+    let tok = err.expect_err("malformed number").token.unwrap();
+    assert_eq!((tok.span.start, tok.span.end), (0, 4));
+}
@@ -0,0 +1,338 @@
+use std::{collections::HashMap, io::Cursor};
+
+use pras::{
+    json::Json,
+    lsp::{self, LineIndex, Server},
+};
+
+const URI: &str = "file:///test.pank";
+
+fn request(id: usize, method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn at(line: usize, character: usize) -> Json {
+    Json::object([
+        ("textDocument", Json::object([("uri", URI.into())])),
+        (
+            "position",
+            Json::object([("line", line.into()), ("character", character.into())]),
+        ),
+    ])
+}
+
+/// A server with `src` open; returns it and the published diagnostics
+fn open(src: &str) -> (Server, Json) {
+    let mut server = Server::new();
+    server.handle(&request(0, "initialize", Json::object([])));
+    let out = server.handle(&notification(
+        "textDocument/didOpen",
+        Json::object([(
+            "textDocument",
+            Json::object([
+                ("uri", URI.into()),
+                ("languageId", "pras".into()),
+                ("version", 1usize.into()),
+                ("text", src.into()),
+            ]),
+        )]),
+    ));
+    assert_eq!(out.len(), 1);
+    let diags = out[0].path(&["params", "diagnostics"]).unwrap().clone();
+    (server, diags)
+}
+
+fn result(server: &mut Server, method: &str, params: Json) -> Json {
+    let out = server.handle(&request(1, method, params));
+    assert_eq!(out.len(), 1);
+    out[0]
+        .get("result")
+        .unwrap_or_else(|| panic!("{method} failed: {}", out[0]))
+        .clone()
+}
+
+fn start(range: &Json) -> (usize, usize) {
+    let p = range.get("start").unwrap();
+    (
+        p.get("line").unwrap().as_usize().unwrap(),
+        p.get("character").unwrap().as_usize().unwrap(),
+    )
+}
+
+const PROGRAM: &str = "## adds one
+dhori inc = ekti kaj(n)
+    n + 1
+sesh
+dhori naam = \"pras\"
+dhori step = 2
+dhori make = ekti kaj(x)
+    dhori y = x
+    ekti kaj() y + step sesh
+sesh
+inc(step)
+";
+
+#[test]
+fn test_json_round_trip() {
+    let test_cases = HashMap::from([
+        ("null", "null"),
+        ("[1, 2.5, -3e2]", "[1,2.5,-300]"),
+        (
+            r#"{"a": {"b": [true, false]}}"#,
+            r#"{"a":{"b":[true,false]}}"#,
+        ),
+        (r#""é\n\"😀""#, "\"é\\n\\\"😀\""),
+        (r#""ধরি""#, r#""ধরি""#),
+    ]);
+
+    for (input, printed) in test_cases {
+        let v = Json::parse(input).unwrap_or_else(|e| panic!("{input}: {e}"));
+        assert_eq!(v.to_string(), printed);
+        assert_eq!(Json::parse(printed), Ok(v));
+    }
+
+    for bad in ["", "[1,", "{\"a\" 1}", "tru", "\"abc", "1 2"] {
+        assert!(Json::parse(bad).is_err(), "{bad} should not parse");
+    }
+}
+
+#[test]
+fn test_line_index() {
+    let text = "ab\nধরি x\n😀y";
+    let lines = LineIndex::new(text);
+    let test_cases = HashMap::from([
+        (0, (0, 0)),
+        (2, (0, 2)),
+        (3, (1, 0)),
+        ("ab\nধরি".len(), (1, 3)),
+        ("ab\nধরি x\n😀".len(), (2, 2)),
+        (text.len(), (2, 3)),
+    ]);
+
+    for (offset, (line, col)) in test_cases {
+        assert_eq!(lines.position(text, offset), (line, col));
+        assert_eq!(lines.offset(text, line, col), offset);
+    }
+    // past the end of a line
+    assert_eq!(lines.offset(text, 0, 10), 2);
+}
+
+#[test]
+fn test_diagnostics() {
+    let (_, diags) = open(PROGRAM);
+    assert_eq!(diags, Json::Array(vec![]));
+
+    let (_, diags) = open("dhori a = 1\ndhori = 2\nshow(b)\n");
+    let diags = diags.as_array().unwrap();
+    let messages: Vec<(usize, &str)> = diags
+        .iter()
+        .map(|d| {
+            (
+                start(d.get("range").unwrap()).0,
+                d.get("message").unwrap().as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert!(
+        messages.contains(&(1, "Expected Eq but got Number")),
+        "{messages:?}"
+    );
+    // error recovery carries on to the next statement
+    assert!(messages.contains(&(2, "undefined variable b")));
+}
+
+#[test]
+fn test_definition() {
+    let (mut server, _) = open(PROGRAM);
+
+    // (line, col) of a use -> (line, col) of its definition
+    let test_cases = HashMap::from([
+        ((10, 1), (1, 6)),  // inc
+        ((10, 5), (5, 6)),  // step
+        ((2, 4), (1, 21)),  // parameter n
+        ((8, 15), (7, 10)), // captured y
+        ((8, 19), (5, 6)),  // global step in a closure
+        ((5, 7), (5, 6)),   // a definition is its own definition
+    ]);
+
+    for ((line, col), def) in test_cases {
+        let loc = result(&mut server, "textDocument/definition", at(line, col));
+        assert_eq!(loc.get("uri").unwrap().as_str(), Some(URI));
+        assert_eq!(start(loc.get("range").unwrap()), def, "at {line}:{col}");
+    }
+
+    let none = result(&mut server, "textDocument/definition", at(3, 1));
+    assert!(none.is_null());
+}
+
+#[test]
+fn test_hover() {
+    let (mut server, _) = open(PROGRAM);
+
+    let test_cases = HashMap::from([
+        ((10, 1), "```pras\ninc(n)\n```\nglobal\n\n---\n\nadds one"),
+        ((4, 7), "```pras\nnaam: string\n```\nglobal"),
+        ((2, 4), "```pras\nn: unknown\n```\nparameter"),
+        ((7, 15), "```pras\nx: unknown\n```\nparameter"),
+        (
+            (8, 15),
+            "```pras\ny: unknown\n```\ncaptured from an enclosing function",
+        ),
+        ((8, 19), "```pras\nstep: number\n```\nglobal"),
+    ]);
+
+    for ((line, col), text) in test_cases {
+        let hover = result(&mut server, "textDocument/hover", at(line, col));
+        assert_eq!(
+            hover.path(&["contents", "value"]).and_then(Json::as_str),
+            Some(text),
+            "at {line}:{col}"
+        );
+    }
+}
+
+#[test]
+fn test_document_symbols() {
+    let (mut server, _) = open(PROGRAM);
+    let symbols = result(&mut server, "textDocument/documentSymbol", at(0, 0));
+
+    fn names(symbols: &Json) -> Vec<String> {
+        symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                let name = s.get("name").unwrap().as_str().unwrap();
+                let children = names(s.get("children").unwrap());
+                if children.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{name}[{}]", children.join(","))
+                }
+            })
+            .collect()
+    }
+    assert_eq!(names(&symbols), vec!["inc", "naam", "step", "make[y]"]);
+
+    let kinds: Vec<usize> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.get("kind").unwrap().as_usize().unwrap())
+        .collect();
+    assert_eq!(kinds, vec![12, 15, 16, 12]);
+}
+
+#[test]
+fn test_completion_has_all_spellings() {
+    let (mut server, _) = open(PROGRAM);
+    let items = result(&mut server, "textDocument/completion", at(11, 0));
+    let labels: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i.get("label").unwrap().as_str().unwrap())
+        .collect();
+
+    for label in ["let", "dhori", "ধরি", "if", "jodi", "যদি", "inc", "step"] {
+        assert!(labels.contains(&label), "missing {label}");
+    }
+
+    // the pragma picks the pack
+    let (mut server, _) = open("# lang: hi\nमाना x = 1\n");
+    let items = result(&mut server, "textDocument/completion", at(2, 0));
+    let has = |label: &str| {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|i| i.get("label").and_then(Json::as_str) == Some(label))
+    };
+    assert!(has("माना"));
+    assert!(!has("ধরি"));
+}
+
+#[test]
+fn test_rename() {
+    let (mut server, _) = open(PROGRAM);
+    let mut params = at(5, 7);
+    if let Json::Object(pairs) = &mut params {
+        pairs.push(("newName".to_string(), "dhap".into()));
+    }
+    let edit = result(&mut server, "textDocument/rename", params.clone());
+    let edits = edit.path(&["changes", URI]).unwrap().as_array().unwrap();
+    let mut starts: Vec<(usize, usize)> = edits
+        .iter()
+        .map(|e| start(e.get("range").unwrap()))
+        .collect();
+    starts.sort();
+    assert_eq!(starts, vec![(5, 6), (8, 19), (10, 4)]);
+    assert!(edits
+        .iter()
+        .all(|e| e.get("newText").and_then(Json::as_str) == Some("dhap")));
+
+    // keywords and malformed names are refused
+    for (line, col, name) in [(5, 7, "jodi"), (5, 7, "1x"), (5, 7, "")] {
+        let mut p = at(line, col);
+        if let Json::Object(pairs) = &mut p {
+            pairs.push(("newName".to_string(), name.into()));
+        }
+        let out = server.handle(&request(2, "textDocument/rename", p));
+        assert!(out[0].get("error").is_some(), "renamed to {name}");
+    }
+
+    // so are builtins and places without a name
+    let (mut server, _) = open("bangla(1)\n");
+    for (line, col) in [(0, 2), (1, 0)] {
+        let mut p = at(line, col);
+        if let Json::Object(pairs) = &mut p {
+            pairs.push(("newName".to_string(), "z".into()));
+        }
+        let out = server.handle(&request(3, "textDocument/rename", p));
+        assert!(out[0].get("error").is_some(), "renamed at {line}:{col}");
+    }
+}
+
+#[test]
+fn test_run_over_stdio() {
+    let messages = [
+        request(1, "initialize", Json::object([])),
+        notification("initialized", Json::object([])),
+        request(2, "textDocument/hover", at(0, 0)),
+        request(3, "shutdown", Json::Null),
+        notification("exit", Json::Null),
+    ];
+    let mut input = Vec::new();
+    for m in &messages {
+        lsp::write_message(&mut input, m).unwrap();
+    }
+    let mut output = Vec::new();
+    let code = lsp::run(&mut Cursor::new(input), &mut output).unwrap();
+    assert_eq!(code, 0);
+
+    let mut output = Cursor::new(output);
+    let mut replies = Vec::new();
+    while let Some(reply) = lsp::read_message(&mut output).unwrap() {
+        replies.push(reply.unwrap());
+    }
+    assert_eq!(replies.len(), 3);
+    assert!(replies[0]
+        .path(&["result", "capabilities", "renameProvider"])
+        .is_some());
+    // hover on a document that was never opened
+    assert!(replies[1].get("error").is_some());
+    assert_eq!(replies[2].get("result"), Some(&Json::Null));
+}
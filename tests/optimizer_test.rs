@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use pras::{
+    ast::Program, compiler::Compiler, lexer::Lexer, optimizer::optimize, parser::Parser, vm::Vm,
+};
+
+fn parse(src: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(src));
+    p.parse_program().expect("parsed AST has errors")
+}
+
+fn run(prog: Program) -> String {
+    let mut vm = Vm::new(Compiler::new().compile(prog));
+    vm.run().expect("runtime error");
+    vm.last_pop().to_string()
+}
+
+#[test]
+fn test_constant_folding() {
+    // source -> the same program written by hand after folding
+    let test_cases = HashMap::from([
+        ("60 * 60 * 24", "86400"),
+        ("1 + 2 * x", "1 + 2 * x"),
+        ("x * (2 + 3)", "x * 5"),
+        ("7 / 2; 7.0 / 2; 7 % 3", "3; 3.5; 1"),
+        ("1 + 0.5", "1.5"),
+        ("\"ab\" + \"c\"", "\"abc\""),
+        ("!sotti; !mittha; !0", "mittha; sotti; mittha"),
+        (
+            "1 == 1; 1 == 1.0; \"a\" != \"a\"; 2 > 10",
+            "sotti; mittha; mittha; mittha",
+        ),
+        ("sotti == 1", "mittha"),
+        ("0.0 / 0 > 1; 1 > 0.0 / 0", "mittha; mittha"),
+        // left for the VM, which fails on them
+        ("1 / 0; 1 % 0", "1 / 0; 1 % 0"),
+        ("9223372036854775807 + 1", "9223372036854775807 + 1"),
+        (
+            "\"a\" - \"b\"; \"b\" > \"a\"; -\"a\"",
+            "\"a\" - \"b\"; \"b\" > \"a\"; -\"a\"",
+        ),
+        // not supported by the compiler
+        ("1 < 2", "1 < 2"),
+    ]);
+
+    for (src, folded) in test_cases {
+        assert_eq!(
+            optimize(parse(src)).to_string(),
+            parse(folded).to_string(),
+            "{src}"
+        );
+    }
+    // a negative literal can't be written
+    assert_eq!(optimize(parse("-(2 * 3)")).to_string(), "PROG[(-6);]");
+}
+
+#[test]
+fn test_dead_code_elimination() {
+    let test_cases = HashMap::from([
+        ("jodi (sotti) tahole 1 nahole 2 sesh", "1"),
+        ("jodi (2 > 1) tahole x nahole y sesh", "x"),
+        (
+            "jodi (mittha) tahole 1 nahole show(2); 3 sesh",
+            "jodi (mittha) tahole nahole show(2); 3 sesh",
+        ),
+        (
+            "jodi (mittha) tahole 1 nahole sesh",
+            "jodi (mittha) tahole nahole sesh",
+        ),
+        (
+            "jodi (0) tahole show(1); 1 nahole 2 sesh",
+            "jodi (0) tahole show(1); 1 nahole sesh",
+        ),
+        ("jotokhon mittha: show(1) sesh", "jotokhon mittha: sesh"),
+        (
+            "ekti kaj() ferao(1); show(2); dhori a = 3 sesh",
+            "ekti kaj() ferao(1) sesh",
+        ),
+        (
+            "ekti kaj(x) jodi (x) tahole ferao(1); show(2) nahole sesh sesh",
+            "ekti kaj(x) jodi (x) tahole ferao(1) nahole sesh sesh",
+        ),
+        // names the rest of the program may use are kept
+        (
+            "jodi (mittha) tahole dhori a = 1 nahole 2 sesh; a",
+            "jodi (mittha) tahole dhori a = 1 nahole 2 sesh; a",
+        ),
+        (
+            "ekti kaj(x) jodi (x) tahole ferao(1); dhori b = 2 nahole sesh; b sesh",
+            "ekti kaj(x) jodi (x) tahole ferao(1); dhori b = 2 nahole sesh; b sesh",
+        ),
+    ]);
+
+    for (src, optimized) in test_cases {
+        assert_eq!(
+            optimize(parse(src)).to_string(),
+            parse(optimized).to_string(),
+            "{src}"
+        );
+    }
+}
+
+#[test]
+fn test_optimized_programs_behave_the_same() {
+    let programs = [
+        "dhori day = 60 * 60 * 24; day / (6 * 4)",
+        "jodi (1 + 1 == 2) tahole \"yes\" nahole \"no\" sesh",
+        "jodi (mittha) tahole 1 nahole sesh",
+        "dhori f = ekti kaj(n) jodi (n > 2 - 1) tahole ferao(n * f(n - 1)); 0 nahole ferao(1) sesh sesh; f(10)",
+        "dhori s = \"x\" + \"y\"; \"{s}{!sotti}\"",
+        "dhori a = 0; jotokhon mittha: dhori a = 1 sesh; a",
+        "[1 + 2, -3.5 * 2, 10 % 4][2 - 1]",
+    ];
+
+    for src in programs {
+        assert_eq!(run(optimize(parse(src))), run(parse(src)), "{src}");
+    }
+}
@@ -1,15 +1,18 @@
 use std::collections::HashMap;
 
-use pras::{lexer::Lexer, parser::Parser};
+use pras::{
+    ast::{Expr, Stmt},
+    lexer::Lexer,
+    parser::Parser,
+};
 
 fn validate_ast(input: &str, expected: &str) {
-    let result: String;
     let ex: String = format!("PROG[{};]", expected);
 
     let lx = Lexer::new(input);
     let mut p = Parser::new(lx);
     let prog = p.parse_program();
-    result = prog.expect("parsed AST has errors").to_string();
+    let result = prog.expect("parsed AST has errors").to_string();
     assert_eq!(ex, result)
 }
 
@@ -42,9 +45,142 @@ fn test_exprs() {
         ("return(1)", "ret<(1)>"),
         ("return(1);", "ret<(1)>"),
         ("include(\"h.pank\")", "inc(str(h.pank))"),
+        ("\"a {b} c\"", "interp(str(a ),ident(b),str( c),)"),
+        ("\"{1+2}\"", "interp(inf((1)+(2)),)"),
+        ("f()", "call(ident(f):)"),
+        ("f(g(1), 2)", "call(ident(f):call(ident(g):(1),),(2),)"),
+        (
+            "f(g(), [1][0])",
+            "call(ident(f):call(ident(g):),index(arr((1)):(0)),)",
+        ),
+        ("[f(), 2]", "arr(call(ident(f):)(2))"),
     ]);
 
     for (k, v) in test_cases {
         validate_ast(k, v)
     }
 }
+
+#[test]
+fn test_malformed_number_is_error() {
+    let lx = Lexer::new("dhori a = 1__0\ndhori b = 0b12");
+    let mut p = Parser::new(lx);
+    assert!(p.parse_program().is_err());
+    assert_eq!(p.errors.len(), 2);
+    assert_eq!(p.errors[1].token.as_ref().map(|t| t.lineno), Some(2));
+}
+
+#[test]
+fn test_doc_comments() {
+    let input = "## number of days
+## in a week
+dhori days = 7
+
+## adds two numbers
+dhori add = ekti kaj(a, b)
+    ## not attached to anything
+    a + b
+sesh
+
+# plain comment
+dhori x = 1
+
+## anonymous function
+ekti kaj() 1 sesh";
+
+    let lx = Lexer::new(input);
+    let mut p = Parser::new(lx);
+    let prog = p.parse_program().expect("parsed AST has errors");
+
+    let docs: Vec<Option<String>> = prog
+        .stmts
+        .iter()
+        .map(|s| match s.as_ref() {
+            Stmt::LetStmt { doc, .. } => doc.clone(),
+            Stmt::ExprStmt { expr, .. } => match expr.as_ref() {
+                Expr::FuncExpr(f) => f.doc.clone(),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        docs,
+        vec![
+            Some("number of days\nin a week".to_string()),
+            Some("adds two numbers".to_string()),
+            None,
+            Some("anonymous function".to_string()),
+        ]
+    );
+
+    let Stmt::LetStmt { value, .. } = prog.stmts[1].as_ref() else {
+        panic!("expected let statement")
+    };
+    let Expr::FuncExpr(f) = value.as_ref() else {
+        panic!("expected function")
+    };
+    assert_eq!(f.doc.as_deref(), Some("adds two numbers"));
+}
+
+#[test]
+fn test_spans_cover_whole_nodes() {
+    // source -> text covered by the first statement and by its expression
+    let test_cases = HashMap::from([
+        ("1 + 2*3", ("1 + 2*3", "1 + 2*3")),
+        ("(1 + 2) * 3;", ("(1 + 2) * 3;", "(1 + 2) * 3")),
+        ("-(a)", ("-(a)", "-(a)")),
+        (
+            "dhori a = f(1, [2])[0];",
+            ("dhori a = f(1, [2])[0];", "f(1, [2])[0]"),
+        ),
+        ("ferao(x == y)", ("ferao(x == y)", "x == y")),
+        ("show(a, b)", ("show(a, b)", "show(a, b)")),
+        (
+            "jodi (a) tahole 1 nahole 2 sesh",
+            (
+                "jodi (a) tahole 1 nahole 2 sesh",
+                "jodi (a) tahole 1 nahole 2 sesh",
+            ),
+        ),
+        (
+            "ekti kaj(x) x + 1 sesh",
+            ("ekti kaj(x) x + 1 sesh", "ekti kaj(x) x + 1 sesh"),
+        ),
+    ]);
+
+    for (src, (stmt_text, expr_text)) in test_cases {
+        let mut p = Parser::new(Lexer::new(src));
+        let prog = p.parse_program().expect("parsed AST has errors");
+        let stmt = prog.stmts[0].as_ref();
+        let expr = match stmt {
+            Stmt::ExprStmt { expr, .. } => expr.span(),
+            Stmt::LetStmt { value, .. } => value.span(),
+            Stmt::ReturnStmt { rval, .. } => rval.span(),
+            Stmt::ShowStmt { .. } => stmt.span(),
+            _ => panic!("unexpected statement {stmt}"),
+        };
+        assert_eq!(&src[stmt.span().start..stmt.span().end], stmt_text);
+        assert_eq!(&src[expr.start..expr.end], expr_text, "{src}");
+    }
+}
+
+#[test]
+fn test_span_lines_and_columns() {
+    let src = "dhori ক = 1\nshow(ক +\n  22)\n";
+    let mut p = Parser::new(Lexer::new(src));
+    let prog = p.parse_program().expect("parsed AST has errors");
+
+    let first = prog.stmts[0].span();
+    assert_eq!(
+        (first.line, first.col, first.end_line, first.end_col),
+        (1, 1, 1, 12)
+    );
+    let Stmt::ShowStmt { value, .. } = prog.stmts[1].as_ref() else {
+        panic!("expected show statement")
+    };
+    let sum = value[0].span();
+    assert_eq!((sum.line, sum.col, sum.end_line, sum.end_col), (2, 6, 3, 5));
+    assert_eq!((sum.char_start, sum.char_end), (17, 25));
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use pras::{
+    compiler::{code::Bytecode, peephole::peephole, Compiler},
+    lexer::Lexer,
+    obj::Object,
+    parser::Parser,
+    vm::Vm,
+};
+
+fn compile(src: &str) -> Bytecode {
+    let mut p = Parser::new(Lexer::new(src));
+    Compiler::new().compile(p.parse_program().expect("parsed AST has errors"))
+}
+
+fn run(bc: Bytecode) -> String {
+    let mut vm = Vm::new(bc);
+    vm.run().expect("runtime error");
+    vm.last_pop().to_string()
+}
+
+/// Code of the first function in `bc`
+fn function_code(bc: &Bytecode) -> String {
+    bc.constants
+        .iter()
+        .find_map(|c| match c.as_ref() {
+            Object::Compfunc(f) => Some(f.fnin.to_string()),
+            _ => None,
+        })
+        .expect("no function")
+}
+
+#[test]
+fn test_superinstructions() {
+    let test_cases = HashMap::from([
+        ("0; 1; 2", "0000 OpConstZero\n0001 OpPop\n0002 OpConstOne\n0003 OpPop\n0004 OpConst 2\n0007 OpPop\n"),
+        // 1.0 is not the integer 1
+        ("1.0", "0000 OpConst 0\n0003 OpPop\n"),
+        (
+            "jodi (2 > 3) tahole 4 nahole 5 sesh",
+            "0000 OpConst 0\n0003 OpConst 1\n0006 OpJumpNotGT 15\n0009 OpConst 2\n0012 OpJump 18\n0015 OpConst 3\n0018 OpPop\n",
+        ),
+        (
+            "jodi (2 == 3) tahole 4 nahole 5 sesh",
+            "0000 OpConst 0\n0003 OpConst 1\n0006 OpJumpNotEqual 15\n0009 OpConst 2\n0012 OpJump 18\n0015 OpConst 3\n0018 OpPop\n",
+        ),
+        (
+            "jodi (2 != 3) tahole 4 nahole 5 sesh",
+            "0000 OpConst 0\n0003 OpConst 1\n0006 OpJumpEqual 15\n0009 OpConst 2\n0012 OpJump 18\n0015 OpConst 3\n0018 OpPop\n",
+        ),
+        // the condition and its jump go; the jump over `nahole` stays
+        (
+            "jodi (sotti) tahole 4 nahole 5 sesh",
+            "0000 OpConst 0\n0003 OpJump 9\n0006 OpConst 1\n0009 OpPop\n",
+        ),
+        (
+            "jotokhon mittha: sesh",
+            "0000 OpJump 6\n0003 OpJump 0\n0006 OpNull\n0007 OpPop\n",
+        ),
+    ]);
+
+    for (src, expected) in test_cases {
+        let bc = peephole(compile(src));
+        assert_eq!(bc.instructions.to_string(), expected, "{src}");
+    }
+
+    let bc = peephole(compile("ekti kaj(x) dhori y = x + 2; y sesh"));
+    assert_eq!(
+        function_code(&bc),
+        "0000 OpIncLocal 0 0 1\n0005 OpGetLocal 1\n0007 OpReturnValue\n"
+    );
+    // adding a string is left alone
+    let bc = peephole(compile("ekti kaj(x) dhori y = x + \"s\"; y sesh"));
+    assert!(function_code(&bc).contains("OpAdd"));
+}
+
+#[test]
+fn test_jump_targets_and_lines_move() {
+    let src = "dhori f = ekti kaj(x)
+    jodi (x == 1) tahole
+        ferao(x)
+    nahole
+        ferao(f(x - 1) + 1)
+    sesh
+sesh
+f(3)";
+    let before = compile(src);
+    let after = peephole(before.clone());
+
+    let Some(Object::Compfunc(f)) = after
+        .constants
+        .iter()
+        .map(AsRef::as_ref)
+        .find(|c| matches!(c, Object::Compfunc(_)))
+    else {
+        panic!("no function");
+    };
+    assert_eq!(
+        f.fnin.to_string(),
+        "0000 OpGetLocal 0
+0002 OpConstOne
+0003 OpJumpNotEqual 12
+0006 OpGetLocal 0
+0008 OpReturnValue
+0009 OpJump 22
+0012 OpCurrentClosure
+0013 OpGetLocal 0
+0015 OpConstOne
+0016 OpSub
+0017 OpCall 1
+0019 OpConstOne
+0020 OpAdd
+0021 OpReturnValue
+0022 OpReturnValue
+"
+    );
+    assert_eq!(f.in_len, f.fnin.ins.len());
+
+    // every line is still there, each at the instruction now in its place
+    let lines = |entries: &[(usize, pras::token::Span)]| -> Vec<usize> {
+        let mut lines: Vec<usize> = entries.iter().map(|(_, s)| s.line).collect();
+        lines.dedup();
+        lines
+    };
+    let Some(Object::Compfunc(g)) = before
+        .constants
+        .iter()
+        .map(AsRef::as_ref)
+        .find(|c| matches!(c, Object::Compfunc(_)))
+    else {
+        panic!("no function");
+    };
+    assert_eq!(lines(f.lines.entries()), lines(g.lines.entries()));
+    for (pos, line) in [(0, 2), (6, 3), (12, 5), (22, 2)] {
+        assert_eq!(f.lines.lookup(pos).map(|s| s.line), Some(line), "{pos}");
+    }
+}
+
+#[test]
+fn test_same_results() {
+    let programs = [
+        "dhori fib = ekti kaj(x)
+            jodi (x == 0) tahole ferao(0) nahole
+                jodi (x == 1) tahole ferao(1) nahole ferao(fib(x - 1) + fib(x - 2)) sesh
+            sesh
+        sesh
+        fib(15)",
+        "dhori f = ekti kaj(a) dhori b = a + 0.5; b sesh; f(1)",
+        "dhori f = ekti kaj(a) dhori b = a + 1; b sesh; f(1.5)",
+        "jodi (1 != 1) tahole 0 nahole 1 sesh",
+        "jodi (\"a\" == \"a\") tahole 10 nahole 20 sesh",
+        "jodi (mittha) tahole 0 nahole 1 sesh",
+        "[0, 1, 0][1]",
+    ];
+
+    for src in programs {
+        let bc = compile(src);
+        assert_eq!(run(peephole(bc.clone())), run(bc), "{src}");
+    }
+}
@@ -55,4 +55,9 @@ fn test_number_token() {
         NumberToken::from(5.0) / NumberToken::from(2),
         NumberToken::from(2.5)
     );
+    assert!(NumberToken::from(2.5) > NumberToken::from(2));
+    assert_eq!(
+        NumberToken::from(f64::NAN).partial_cmp(&NumberToken::from(1)),
+        None
+    );
 }
@@ -0,0 +1,105 @@
+use pras::langpack::LangPack;
+use pras::lexer::Lexer;
+use pras::translit::{translit, DigitStyle, KeywordStyle, TranslitOptions};
+
+static SOURCE: &str = "# let, jodi and ধরি in a comment stay
+dhori x = ekti kaj(a)   #[ block
+   comment ]#
+\tjodi (a == 10) tahole ferau(1_000) nahole ferao(\"jodi {a + ১}\") sesh
+sesh;\r
+দেখাও(x(0x1f));
+";
+
+fn run(src: &str, keywords: KeywordStyle, digits: Option<DigitStyle>) -> String {
+    translit(Lexer::new(src), TranslitOptions { keywords, digits }).expect("lexer error")
+}
+
+#[test]
+fn test_translit_keywords() {
+    let test_cases = vec![
+        (
+            KeywordStyle::English,
+            "# let, jodi and ধরি in a comment stay
+let x = one fn(a)   #[ block
+   comment ]#
+\tif (a == 10) then return(1_000) else return(\"jodi {a + ১}\") end
+end;\r
+show(x(0x1f));
+",
+        ),
+        (
+            KeywordStyle::Roman,
+            "# let, jodi and ধরি in a comment stay
+dhori x = ekti kaj(a)   #[ block
+   comment ]#
+\tjodi (a == 10) tahole ferao(1_000) nahole ferao(\"jodi {a + ১}\") sesh
+sesh;\r
+dekhao(x(0x1f));
+",
+        ),
+        (
+            KeywordStyle::Native,
+            "# let, jodi and ধরি in a comment stay
+ধরি x = একটি কাজ(a)   #[ block
+   comment ]#
+\tযদি (a == 10) তাহলে ফেরাও(1_000) নাহলে ফেরাও(\"jodi {a + ১}\") শেষ
+শেষ;\r
+দেখাও(x(0x1f));
+",
+        ),
+    ];
+
+    for (style, expected) in test_cases {
+        assert_eq!(run(SOURCE, style, None), expected, "{style:?}");
+    }
+}
+
+#[test]
+fn test_translit_digits() {
+    let src = "show(10 + ১২ + 1_000 + 2.5e3 + 0x1f + ০b১০ + 0O17)";
+    // radix literals are left as written
+    assert_eq!(
+        run(src, KeywordStyle::English, Some(DigitStyle::Native)),
+        "show(১০ + ১২ + ১_০০০ + ২.৫e৩ + 0x1f + ০b১০ + 0O17)"
+    );
+    assert_eq!(
+        run(src, KeywordStyle::Native, Some(DigitStyle::Ascii)),
+        "দেখাও(10 + 12 + 1_000 + 2.5e3 + 0x1f + ০b১০ + 0O17)"
+    );
+}
+
+#[test]
+fn test_translit_round_trip() {
+    let native = run(SOURCE, KeywordStyle::Native, None);
+    let english = run(&native, KeywordStyle::English, None);
+    assert_eq!(english, run(SOURCE, KeywordStyle::English, None));
+}
+
+#[test]
+fn test_translit_pack() {
+    let src = "# lang: hi\nlet x = if true then 1 else 2 end;";
+    assert_eq!(
+        run(src, KeywordStyle::Native, Some(DigitStyle::Native)),
+        "# lang: hi\nमाना x = यदि सत्य तो १ अन्यथा २ अंत;"
+    );
+
+    let lx = Lexer::with_pack("माना x = १;", LangPack::builtin("hi").unwrap());
+    let opts = TranslitOptions {
+        keywords: KeywordStyle::Roman,
+        digits: Some(DigitStyle::Ascii),
+    };
+    assert_eq!(translit(lx, opts).unwrap(), "mana x = 1;");
+}
+
+#[test]
+fn test_translit_lexer_error() {
+    let err = translit(
+        Lexer::new("let x = \"open"),
+        TranslitOptions {
+            keywords: KeywordStyle::English,
+            digits: None,
+        },
+    )
+    .expect_err("unterminated string");
+    assert_eq!(err.lineno, 1);
+}
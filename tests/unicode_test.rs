@@ -0,0 +1,40 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use pras::unicode::{is_mark, nfc};
+
+#[test]
+fn test_nfc() {
+    let test_cases = HashMap::from([
+        // nukta forms are composition exclusions: always decomposed
+        ("\u{09df}", "\u{09af}\u{09bc}"),
+        ("\u{09af}\u{09bc}", "\u{09af}\u{09bc}"),
+        ("\u{09dc}\u{09be}", "\u{09a1}\u{09bc}\u{09be}"),
+        ("\u{095c}", "\u{0921}\u{093c}"),
+        // two part vowel signs compose
+        ("\u{0995}\u{09c7}\u{09be}", "\u{0995}\u{09cb}"),
+        ("\u{0995}\u{09c7}\u{09d7}", "\u{0995}\u{09cc}"),
+        ("\u{0928}\u{093c}", "\u{0929}"),
+        // nukta sorts before virama
+        ("\u{0995}\u{09cd}\u{09bc}", "\u{0995}\u{09bc}\u{09cd}"),
+        ("abc", "abc"),
+        ("কলম", "কলম"),
+    ]);
+
+    for (k, v) in test_cases {
+        assert_eq!(nfc(k), v, "{k:?}");
+    }
+
+    assert!(matches!(nfc("ধরি"), Cow::Borrowed(_)));
+}
+
+#[test]
+fn test_is_mark() {
+    for c in [
+        '\u{09bc}', '\u{09be}', '\u{09cd}', '\u{0981}', '\u{093c}', '\u{094d}', '\u{0301}',
+    ] {
+        assert!(is_mark(c), "{c:?}");
+    }
+    for c in ['ক', 'क', 'a', '১', '\u{200d}'] {
+        assert!(!is_mark(c), "{c:?}");
+    }
+}
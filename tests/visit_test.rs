@@ -0,0 +1,131 @@
+use std::{collections::HashMap, rc::Rc};
+
+use pras::{
+    ast::{
+        visit::{fold_expr, walk_expr, walk_expr_mut},
+        AstNode, Expr, Fold, Identifier, MutVisitor, Program, Visitor,
+    },
+    lexer::Lexer,
+    parser::Parser,
+    token::NumberToken,
+};
+
+fn parse(src: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(src));
+    p.parse_program().expect("parsed AST has errors")
+}
+
+/// Names used and names bound
+#[derive(Default)]
+struct Names {
+    used: Vec<String>,
+    bound: Vec<String>,
+}
+
+impl Visitor for Names {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::IdentExpr { value, .. } = expr {
+            self.used.push(value.clone());
+        }
+        walk_expr(self, expr)
+    }
+
+    fn visit_ident(&mut self, ident: &Identifier) {
+        self.bound.push(ident.name.clone());
+    }
+}
+
+struct Rename<'a>(&'a str, &'a str);
+
+impl MutVisitor for Rename<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::IdentExpr { value, .. } if value == self.0 => *value = self.1.to_string(),
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_ident_mut(&mut self, ident: &mut Identifier) {
+        if ident.name == self.0 {
+            ident.name = self.1.to_string();
+        }
+    }
+}
+
+/// Replaces `-n` of a number literal with the negative literal
+struct Negate;
+
+impl Fold for Negate {
+    fn fold_expr(&mut self, expr: Rc<Expr>) -> Rc<Expr> {
+        let expr = fold_expr(self, expr);
+        match expr.as_ref() {
+            Expr::PrefixExpr { op, right, .. } if op.literal == "-" => match right.as_ref() {
+                Expr::NumExpr {
+                    token,
+                    value: NumberToken::Int(i),
+                    is_int,
+                    ..
+                } => Rc::new(Expr::NumExpr {
+                    token: token.clone(),
+                    span: expr.span(),
+                    value: NumberToken::Int(-i),
+                    is_int: *is_int,
+                }),
+                _ => expr,
+            },
+            _ => expr,
+        }
+    }
+}
+
+#[test]
+fn test_visitor_sees_every_name() {
+    let test_cases = HashMap::from([
+        ("dhori a = b + c", (vec!["b", "c"], vec!["a"])),
+        (
+            "dhori f = ekti kaj(x, y) ferao(x + g(y)) sesh",
+            (vec!["x", "g", "y"], vec!["f", "x", "y"]),
+        ),
+        (
+            "jodi (a) tahole show(b) nahole {c: [d]}[e] sesh",
+            (vec!["a", "b", "c", "d", "e"], vec![]),
+        ),
+        ("\"{a} and {b}\"", (vec!["a", "b"], vec![])),
+    ]);
+
+    for (src, (used, bound)) in test_cases {
+        let mut names = Names::default();
+        parse(src).accept(&mut names);
+        assert_eq!(names.used, used, "{src}");
+        assert_eq!(names.bound, bound, "{src}");
+    }
+}
+
+#[test]
+fn test_mut_visitor_renames_in_place() {
+    let original = parse("dhori a = 1; dhori f = ekti kaj(a) a + b sesh; f(a)");
+    let mut prog = original.clone();
+    prog.accept_mut(&mut Rename("a", "z"));
+
+    assert_eq!(
+        prog.to_string(),
+        parse("dhori z = 1; dhori f = ekti kaj(z) z + b sesh; f(z)").to_string()
+    );
+    // the nodes shared with the original were copied, not changed
+    assert!(original.to_string().contains("id<a|false>"));
+}
+
+#[test]
+fn test_fold_rebuilds_tree() {
+    let test_cases = HashMap::from([
+        ("-1", "(-1)"),
+        ("[-1, -x]", "arr((-1)pre(-ident(x)))"),
+        ("dhori a = 2 * -3", "let<id<a|false>:inf((2)*(-3))>"),
+        ("ekti kaj() ferao(-4) sesh", "func(:blk<ret<(-4)>;>)"),
+    ]);
+
+    for (src, folded) in test_cases {
+        let prog = Negate.fold_program(parse(src));
+        assert_eq!(prog.to_string(), format!("PROG[{folded};]"), "{src}");
+    }
+}
@@ -1,11 +1,22 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
 
 use pras::{
+    bn::NumeralStyle,
     compiler::Compiler,
     lexer::Lexer,
     obj::{Object, BOOL_OBJ, NUMBER_OBJ, STRING_OBJ},
     parser::Parser,
-    vm::Vm,
+    vm::{
+        error::{RuntimeError, RuntimeErrorKind},
+        value::Value,
+        Vm,
+    },
 };
 
 pub static FIB_INPUT: &str = "
@@ -48,14 +59,22 @@ fn get_obj(input: &str) -> Object {
     let bc = com.compile(prog);
 
     let mut vm = Vm::new(bc);
-    vm.run();
+    vm.run().expect("runtime error");
     vm.last_pop()
 }
 
+fn run_to_error(input: &str, setup: impl Fn(&mut Vm)) -> RuntimeError {
+    let mut parser = Parser::new(Lexer::new(input));
+    let prog = parser.parse_program().expect("parser error");
+    let mut vm = Vm::new(Compiler::new().compile(prog));
+    setup(&mut vm);
+    vm.run().expect_err("ran without an error")
+}
+
 fn check_last_item_bool(input: &str, output: bool) {
     let obj = get_obj(input);
     assert_eq!(obj.get_type(), BOOL_OBJ);
-    let Object::Bool { token : _, value } = obj else{
+    let Object::Bool { token: _, value } = obj else {
         panic!("check_last_item_bool -> obj not bool")
     };
 
@@ -65,11 +84,11 @@ fn check_last_item_bool(input: &str, output: bool) {
 fn check_last_item_int(input: &str, output: i64) {
     let obj = get_obj(input);
     assert_eq!(obj.get_type(), NUMBER_OBJ);
-    let Object::Number { token : _, value } = obj else{
+    let Object::Number { token: _, value } = obj else {
         panic!("check_last_item_int  -> obj not int")
     };
 
-    assert_eq!(value.is_int(), true);
+    assert!(value.is_int());
 
     assert_eq!(value.get_as_i64(), output)
 }
@@ -77,11 +96,11 @@ fn check_last_item_int(input: &str, output: i64) {
 fn check_last_item_float(input: &str, output: f64) {
     let obj = get_obj(input);
     assert_eq!(obj.get_type(), NUMBER_OBJ);
-    let Object::Number { token : _, value } = obj else{
+    let Object::Number { token: _, value } = obj else {
         panic!("check_last_item_float -> obj not float")
     };
 
-    assert_eq!(value.is_int(), false);
+    assert!(!value.is_int());
 
     assert_eq!(value.get_as_f64(), output)
 }
@@ -89,7 +108,7 @@ fn check_last_item_float(input: &str, output: f64) {
 fn check_last_item_string(input: &str, output: &str) {
     let obj = get_obj(input);
     assert_eq!(obj.get_type(), STRING_OBJ);
-    let Object::String { token : _, value } = obj else{
+    let Object::String { token: _, value } = obj else {
         panic!("check_last_item_float -> obj not float")
     };
 
@@ -107,7 +126,14 @@ fn test_vm_numbers_int() {
         ("4/2", 2),
         ("5/2", 2),
         ("50/2 * 2 + 10 - 5", 55),
+        ("0x10 + 0b11 + 0o10", 27),
+        ("1_000 * ২", 2000),
         (FIB_INPUT, 55),
+        // the global defined in the branch not taken leaves a gap
+        (
+            "jodi (mittha) tahole dhori a = 1 nahole 0 sesh; dhori b = 2; b",
+            2,
+        ),
         (
             "dhori a = ekti kaj() dhori a = 1; ferao(a) sesh;
          a()",
@@ -120,7 +146,7 @@ fn test_vm_numbers_int() {
             sesh
           sesh
           let newaddr = addr(1,2)
-          addr(8)
+          newaddr(8)
           ",
             11,
         ),
@@ -138,6 +164,12 @@ fn test_vm_numbers_float() {
         ("100.0/2", 50.0),
         ("22.0/7", 3.142857142857143),
         ("11.0/3+4", 7.666666666666666),
+        ("1e3", 1000.0),
+        ("২.৫e-১ * 4", 1.0),
+        (
+            "ekti kaj(a) dhori b = a + 1; dhori c = b + 0.5; c sesh(1)",
+            2.5,
+        ),
     ]);
 
     for (k, v) in testcases {
@@ -153,6 +185,14 @@ fn test_vm_string() {
         ("\"100\"", "100"),
         ("jodi (true) tahole \"true\" nahole sesh", "true"),
         ("jodi (1+2 > 3) tahole \"true\" nahole \"false\"", "false"),
+        ("\"ফলাফল: {5}\"", "ফলাফল: 5"),
+        (
+            "dhori name = \"রহিম\"; dhori age = 12; \"নাম {name}, বয়স {age}\"",
+            "নাম রহিম, বয়স 12",
+        ),
+        ("\"{1 == 1} {[1, 2]} {2.5}\"", "true 1 2  2.5"),
+        ("\"{\"in{1}ner\"}!\"", "in1ner!"),
+        ("\"{{}}\"", "{}"),
     ]);
 
     for (k, v) in testcases {
@@ -160,11 +200,436 @@ fn test_vm_string() {
     }
 }
 
+#[test]
+fn test_vm_builtin_bangla() {
+    let testcases = HashMap::from([
+        ("bangla(2023)", "২০২৩"),
+        ("bangla(0-15)", "-১৫"),
+        ("bangla(5.0/2)", "২.৫"),
+        ("bangla(\"ক্লাস 10\")", "ক্লাস ১০"),
+        ("বাংলা([1, 2])", "১ ২ "),
+        ("dhori f = ekti kaj(x) bangla(x * 2) sesh; f(21)", "৪২"),
+    ]);
+
+    for (k, v) in testcases {
+        check_last_item_string(k, v)
+    }
+}
+
+#[test]
+fn test_vm_langpacks() {
+    let testcases = HashMap::from([
+        (
+            "# lang: hi
+            माना जोड़ = एक कार्य(क, ख) लौटाओ(क + ख) अंत;
+            जोड़(२, ४०)",
+            42,
+        ),
+        (
+            "# lang: as
+            ধৰা ৰাশি = এটা কাম(ক)
+                যদি (ক == 0) তেন্তে
+                    ঘূৰাই(0)
+                নহলে
+                    ঘূৰাই(ক + ৰাশি(ক - 1))
+                শেষ
+            শেষ;
+            ৰাশি(৪)",
+            10,
+        ),
+    ]);
+
+    for (k, v) in testcases {
+        check_last_item_int(k, v)
+    }
+}
+
+#[test]
+fn test_object_render_numerals() {
+    let obj = get_obj("[1, 2.5, \"3\"]");
+    assert_eq!(obj.render(NumeralStyle::Bengali), "১ ২.৫ 3 ");
+    assert_eq!(obj.render(NumeralStyle::Ascii), "1 2.5 3 ");
+}
+
+#[test]
+fn test_vm_show_statements() {
+    check_last_item_int("show(1); show(2, 3); 4", 4);
+}
+
 #[test]
 fn test_vm_bool() {
-    let testcases = HashMap::from([("true", true)]);
+    let testcases = HashMap::from([
+        ("true", true),
+        ("\"a\" == \"a\"", true),
+        ("\"a\" != \"a\"", false),
+        ("\"a\" == \"b\"", false),
+        ("\"ab\" == \"a\" + \"b\"", true),
+        ("dhori s = \"x\"; ekti kaj() s == \"x\" sesh()", true),
+        ("0.0 / 0 > 1", false),
+        ("1 > 0.0 / 0", false),
+    ]);
 
     for (k, v) in testcases {
         check_last_item_bool(k, v)
     }
 }
+
+#[test]
+fn test_vm_tail_calls() {
+    // far deeper than the stack could hold one frame per call
+    let testcases = HashMap::from([
+        (
+            "dhori count = ekti kaj(n, acc)
+                jodi (n == 0) tahole ferao(acc) nahole ferao(count(n - 1, acc + 1)) sesh
+            sesh
+            count(1000000, 0)",
+            1_000_000,
+        ),
+        (
+            "dhori apply = ekti kaj(f, n) ferao(f(f, n)) sesh
+            dhori down = ekti kaj(me, n)
+                jodi (n == 0) tahole ferao(7) nahole ferao(apply(me, n - 1)) sesh
+            sesh
+            apply(down, 1000000)",
+            7,
+        ),
+        // into a function with locals of its own
+        (
+            "dhori last = ekti kaj(n) dhori m = n * 2; m sesh
+            dhori f = ekti kaj(a, b) ferao(last(a + b)) sesh
+            f(1, 2) + 1",
+            7,
+        ),
+    ]);
+
+    for (k, v) in testcases {
+        check_last_item_int(k, v)
+    }
+    check_last_item_string("ekti kaj(x) ferao(bangla(x)) sesh(12)", "১২");
+}
+
+#[test]
+fn test_vm_closures_see_their_binding() {
+    // closures made in a local's `dhori` value see the value once it is set
+    let testcases = HashMap::from([
+        (
+            "dhori f = ekti kaj()
+                dhori h = {\"f\": ekti kaj() h sesh, \"n\": 1}
+                h
+            sesh
+            dhori h = f(); h[\"f\"]()[\"f\"]()[\"n\"]",
+            1,
+        ),
+        (
+            "dhori odd = ekti kaj(n)
+                dhori fs = [
+                    ekti kaj(k) jodi (k == 0) tahole 0 nahole fs[1](k - 1) sesh sesh,
+                    ekti kaj(k) jodi (k == 0) tahole 1 nahole fs[0](k - 1) sesh sesh
+                ]
+                fs[0](n)
+            sesh
+            odd(7) * 10 + odd(10)",
+            10,
+        ),
+        // left before the local is set
+        (
+            "dhori f = ekti kaj(c)
+                dhori x = [ekti kaj() x sesh, jodi (c) tahole ferao(2) nahole 1 sesh]
+                x[0]()[1]
+            sesh
+            f(sotti) * 10 + f(mittha)",
+            21,
+        ),
+    ]);
+
+    for (k, v) in testcases {
+        check_last_item_int(k, v)
+    }
+}
+
+#[test]
+fn test_vm_stack_overflow() {
+    let src = "dhori down = ekti kaj(n)
+    jodi (n == 0) tahole ferao(0) nahole ferao(1 + down(n - 1)) sesh
+sesh
+down(DEPTH)";
+    let site = "down(n - 1)";
+    let deep = src.replace("DEPTH", "100000");
+    let text = |e: &RuntimeError| e.span.map(|s| &deep[s.start..s.end]);
+
+    // out of stack space
+    let e = run_to_error(&deep, |_| {});
+    assert!(matches!(e.kind, RuntimeErrorKind::StackOverflow { .. }));
+    assert_eq!((e.function.as_str(), text(&e)), ("down", Some(site)));
+
+    // out of frames
+    let e = run_to_error(&deep, |vm| vm.set_frame_limit(50));
+    assert_eq!(e.kind, RuntimeErrorKind::StackOverflow { depth: 50 });
+    assert_eq!((e.function.as_str(), text(&e)), ("down", Some(site)));
+    assert_eq!(
+        e.to_string(),
+        "2:52: stack overflow (50 calls deep) in down"
+    );
+
+    // at the top level
+    let e = run_to_error("[1, 2, 3, 4, 5, 6]", |vm| vm.set_stack_limit(4));
+    assert_eq!(e.kind, RuntimeErrorKind::StackOverflow { depth: 1 });
+    assert_eq!(e.function, "");
+
+    // within the limits
+    let shallow = src.replace("DEPTH", "48");
+    let mut parser = Parser::new(Lexer::new(&shallow));
+    let mut vm = Vm::new(Compiler::new().compile(parser.parse_program().unwrap()));
+    vm.set_frame_limit(50);
+    vm.run().expect("runtime error");
+    assert_eq!(vm.last_pop().to_string(), "48");
+}
+
+fn new_vm(input: &str) -> Vm {
+    let mut parser = Parser::new(Lexer::new(input));
+    Vm::new(Compiler::new().compile(parser.parse_program().expect("parser error")))
+}
+
+#[test]
+fn test_vm_execution_limits() {
+    let forever = "dhori a = 1; jotokhon sotti: a sesh";
+
+    let e = run_to_error(forever, |vm| vm.set_fuel(Some(1000)));
+    assert_eq!(e.kind, RuntimeErrorKind::OutOfFuel);
+    let e = run_to_error(forever, |vm| vm.set_deadline(Some(Instant::now())));
+    assert_eq!(e.kind, RuntimeErrorKind::DeadlineExceeded);
+
+    let mut vm = new_vm(forever);
+    let flag = vm.interrupt_handle();
+    let setter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        flag.store(true, Ordering::Relaxed);
+    });
+    let e = vm.run().expect_err("ran without an error");
+    setter.join().unwrap();
+    assert_eq!(e.kind, RuntimeErrorKind::Interrupted);
+    // the flag was cleared, so it goes on until the next limit
+    vm.set_fuel(Some(10));
+    let e = vm.run().expect_err("ran without an error");
+    assert_eq!(e.kind, RuntimeErrorKind::OutOfFuel);
+    assert_eq!(vm.fuel(), Some(0));
+}
+
+#[test]
+fn test_vm_resumes_after_limit() {
+    // a few instructions at a time gives the same result as one run
+    let mut vm = new_vm(FIB_INPUT);
+    let mut stops = 0;
+    loop {
+        vm.set_fuel(Some(7));
+        match vm.run() {
+            Ok(()) => break,
+            Err(e) => assert_eq!(e.kind, RuntimeErrorKind::OutOfFuel),
+        }
+        stops += 1;
+    }
+    assert!(stops > 100);
+    assert_eq!(vm.last_pop().to_string(), "55");
+
+    // where it stopped can be seen from the error
+    let src = "dhori a = 1\ndhori b = a + 1\nb";
+    let e = run_to_error(src, |vm| vm.set_fuel(Some(2)));
+    assert_eq!(e.span.map(|s| s.line), Some(2));
+}
+
+const GROW_INPUT: &str = "
+    dhori grow = ekti kaj(s, n)
+        jodi (n == 0) tahole ferao(s) nahole ferao(grow(s + s, n - 1)) sesh
+    sesh
+";
+
+#[test]
+fn test_vm_memory_limit() {
+    let e = run_to_error(&format!("{GROW_INPUT} grow(\"ab\", 30)"), |vm| {
+        vm.set_memory_limit(Some(1 << 20))
+    });
+    let RuntimeErrorKind::OutOfMemory { used, limit } = e.kind else {
+        panic!("not out of memory -> {e}");
+    };
+    assert_eq!(limit, 1 << 20);
+    assert!(used > limit);
+    assert_eq!(e.function, "grow");
+
+    let e = run_to_error("[1, 2, 3, 4, 5, 6, 7, 8]", |vm| {
+        vm.set_memory_limit(Some(64))
+    });
+    assert!(
+        matches!(e.kind, RuntimeErrorKind::OutOfMemory { .. }),
+        "{e}"
+    );
+
+    // garbage does not count once measured: the strings grow() made on
+    // the way are gone when the next one is made
+    let mut vm = new_vm(&format!(
+        "{GROW_INPUT} dhori f = ekti kaj(x) dhori s = grow(\"ab\", 17); 1 sesh
+        f(0); f(0); f(0); f(0)"
+    ));
+    vm.set_memory_limit(Some(1 << 20));
+    vm.run().expect("runtime error");
+}
+
+#[test]
+fn test_vm_memory_reclaimed() {
+    // hashes holding closures that capture arrays, made and dropped on
+    // every call, are freed as they go
+    let src = "
+        dhori churn = ekti kaj(n)
+            dhori h = {\"f\": ekti kaj() [n] sesh, \"a\": [n, \"x\" + \"y\"]}
+            jodi (n == 0) tahole ferao(0) nahole ferao(churn(n - 1)) sesh
+        sesh
+        churn(5000)";
+    let mut vm = new_vm(src);
+    vm.set_memory_limit(Some(16 << 10));
+    vm.run().expect("runtime error");
+    assert!(vm.memory_stats().current < 1024, "{:?}", vm.memory_stats());
+
+    // a closure stored in a hash under the hash's own name holds the hash
+    let mut vm = new_vm(
+        "dhori f = ekti kaj() dhori h = {\"f\": ekti kaj() h sesh}; h sesh
+        dhori h = f(); h[\"f\"]() == h",
+    );
+    vm.run().expect("runtime error");
+    assert_eq!(
+        vm.last_pop(),
+        Object::Bool {
+            token: None,
+            value: true
+        }
+    );
+}
+
+#[test]
+fn test_vm_cycles_collected() {
+    // a closure in a hash under the hash's own name holds the hash
+    let cycle = "dhori f = ekti kaj() dhori h = {\"f\": ekti kaj() h sesh}; h sesh";
+
+    let mut vm = new_vm(&format!("{cycle} f(); f(); 0"));
+    vm.run().expect("runtime error");
+    assert_eq!(vm.collect(), 2);
+    assert_eq!(vm.collect(), 0);
+
+    // one that can still be reached is left alone
+    let mut vm = new_vm(&format!("{cycle} dhori h = f(); f(); h[\"f\"]() == h"));
+    vm.run().expect("runtime error");
+    assert_eq!(vm.collect(), 1);
+    assert_eq!(
+        vm.last_pop(),
+        Object::Bool {
+            token: None,
+            value: true
+        }
+    );
+
+    // the closure is freed, where reference counting alone keeps it
+    let mut vm = new_vm(&format!("{cycle} f(); 0"));
+    let closure = loop {
+        vm.set_fuel(Some(1));
+        vm.run().expect_err("ran to the end");
+        if let Object::Hash { pairs, .. } = vm.last_pop() {
+            let f = pairs.values().next().expect("empty hash");
+            let Object::Closure(cl) = f.value.as_ref() else {
+                panic!("not a closure -> {}", f.value);
+            };
+            break Rc::downgrade(cl);
+        }
+    };
+    vm.set_fuel(None);
+    vm.run().expect("runtime error");
+    assert!(closure.upgrade().is_some());
+    assert_eq!(vm.collect(), 1);
+    assert!(closure.upgrade().is_none());
+
+    // made on every call, they are collected as memory is measured
+    let mut vm = new_vm(
+        "dhori churn = ekti kaj(n)
+            dhori h = {\"f\": ekti kaj() h sesh, \"a\": [n, n, n, n]}
+            jodi (n == 0) tahole ferao(0) nahole ferao(churn(n - 1)) sesh
+        sesh
+        churn(5000)",
+    );
+    vm.set_memory_limit(Some(16 << 10));
+    vm.run().expect("runtime error");
+    assert!(vm.collect() < 100);
+}
+
+#[test]
+fn test_vm_cycles_freed_on_drop() {
+    // cycles still reachable when the program ends go with the VM
+    let mut vm = new_vm(
+        "dhori f = ekti kaj() dhori h = {\"f\": ekti kaj() h sesh}; h sesh
+        dhori h = f(); h",
+    );
+    vm.run().expect("runtime error");
+    let Object::Hash { pairs, .. } = vm.last_pop() else {
+        panic!("not a hash -> {}", vm.last_pop());
+    };
+    let f = pairs.values().next().expect("empty hash");
+    let Object::Closure(cl) = f.value.as_ref() else {
+        panic!("not a closure -> {}", f.value);
+    };
+    let closure = Rc::downgrade(cl);
+    drop(pairs);
+
+    assert!(closure.upgrade().is_some());
+    drop(vm);
+    assert!(closure.upgrade().is_none());
+}
+
+#[test]
+fn test_vm_memory_stats() {
+    let mut vm = new_vm(&format!(
+        "{GROW_INPUT} dhori s = grow(\"ab\", 10); [s, s, s]"
+    ));
+    assert_eq!(vm.memory_stats().peak, 0);
+    vm.run().expect("runtime error");
+    let stats = vm.memory_stats();
+    // the 2 KiB string is counted once however often it is referred to
+    assert!((2048..4096).contains(&stats.current), "{stats:?}");
+    assert!(stats.peak >= stats.current + 1024, "{stats:?}");
+
+    // constants are not the program's to count
+    let mut vm = new_vm("\"a long string that the compiler made\"");
+    vm.run().expect("runtime error");
+    assert_eq!(vm.memory_stats().current, 0);
+
+    // a function calling itself by name makes no new closure
+    let peak = |n: usize| {
+        let mut vm = new_vm(&format!(
+            "dhori f = ekti kaj(n) jodi (n == 0) tahole ferao(0) nahole ferao(f(n - 1)) sesh sesh
+            f({n})"
+        ));
+        vm.run().expect("runtime error");
+        vm.memory_stats().peak
+    };
+    assert_eq!(peak(1000), peak(1));
+}
+
+#[test]
+fn test_vm_values_unboxed() {
+    // a number fits next to its tag, as does the pointer to an object
+    assert_eq!(std::mem::size_of::<Value>(), 16);
+
+    // scalars are boxed going into arrays and hashes, and come out equal
+    let test_cases = HashMap::from([
+        ("[1, 2.5, sotti][1]", "2.5"),
+        ("{1: mittha}[1]", "false"),
+        ("{sotti: 3}[1 == 1]", "3"),
+        ("dhori f = ekti kaj(x) ekti kaj() x sesh sesh; f(7)()", "7"),
+        ("[1, 2] == [1, 2]", "true"),
+        ("dhori a = [1]; a == a", "true"),
+    ]);
+    for (input, expected) in test_cases {
+        assert_eq!(get_obj(input).to_string(), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_vm_normalised_identifiers() {
+    // precomposed `য়` and `য` + nukta name the same binding
+    check_last_item_int("ধরি \u{09df}1 = 7; \u{09af}\u{09bc}1 * 6", 42);
+}